@@ -0,0 +1,144 @@
+use std::time::Duration;
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, Instant},
+};
+use bevy_rapier3d::prelude::*;
+use smooth_bevy_cameras::LookTransform;
+
+use crate::{race::Ball, settings::Settings};
+
+pub const ROLL_SOUND_PATH: &str = "sounds/roll.ogg";
+pub const IMPACT_SOUND_PATH: &str = "sounds/impact.ogg";
+/// Length of the roll sound, after which it is played again for balls that are still rolling.
+pub const ROLL_SOUND_DURATION: Duration = Duration::from_millis(500);
+
+/// Speed at which a ball's roll is at full volume.
+pub const FULL_VOLUME_SPEED: f32 = 50.0;
+/// Change in a ball's velocity in one frame at which its impact is at full volume.
+pub const FULL_VOLUME_IMPACT: f32 = 30.0;
+/// Smallest change in a ball's velocity in one frame that counts as an impact.
+pub const MIN_IMPACT: f32 = 5.0;
+/// Minimum time between impact sounds of the same ball.
+pub const IMPACT_COOLDOWN: Duration = Duration::from_millis(200);
+/// Distance from the camera at which sounds are at half volume.
+pub const HALF_VOLUME_DISTANCE: f32 = 150.0;
+/// Volume below which a sound is not played.
+///
+/// Audio in this version of Bevy can only be played at full volume and cannot be stopped, so
+/// volume is approximated by only playing sounds that would be loud enough to hear.
+pub const AUDIBLE_VOLUME: f32 = 0.25;
+
+pub struct BallSounds {
+    pub roll: Handle<AudioSource>,
+    pub impact: Handle<AudioSource>,
+}
+
+impl BallSounds {
+    pub fn load(asset_server: &AssetServer) -> Self {
+        Self {
+            roll: asset_server.load(ROLL_SOUND_PATH),
+            impact: asset_server.load(IMPACT_SOUND_PATH),
+        }
+    }
+}
+
+/// Volume of the roll of a ball moving at `speed`, from 0 when still to 1 at
+/// [`FULL_VOLUME_SPEED`] and above.
+pub fn roll_volume(speed: f32) -> f32 {
+    (speed / FULL_VOLUME_SPEED).clamp(0.0, 1.0)
+}
+
+/// Volume of an impact that changed a ball's velocity by `impact` in one frame.
+pub fn impact_volume(impact: f32) -> f32 {
+    if impact < MIN_IMPACT {
+        0.0
+    } else {
+        (impact / FULL_VOLUME_IMPACT).clamp(0.0, 1.0)
+    }
+}
+
+/// Fraction of its volume that a sound keeps at `distance` from the camera.
+pub fn distance_falloff(distance: f32) -> f32 {
+    HALF_VOLUME_DISTANCE / (HALF_VOLUME_DISTANCE + distance.max(0.0))
+}
+
+#[derive(Clone, Copy)]
+pub struct BallSoundState {
+    last_velocity: Vec3,
+    next_roll: Instant,
+    last_impact: Option<Instant>,
+}
+
+/// Plays a roll sound for each ball in play, louder the faster it rolls, and an impact sound when
+/// a ball's velocity changes sharply. Both are quieter the further the ball is from the camera.
+/// Balls that are no longer in play are forgotten, so their roll sound is not played again.
+pub fn play_ball_sounds(
+    audio: Res<Audio>,
+    audio_sources: Res<Assets<AudioSource>>,
+    sounds: Res<BallSounds>,
+    settings: Res<Settings>,
+    cameras: Query<&LookTransform>,
+    balls: Query<(Entity, &GlobalTransform, &RigidBodyVelocityComponent), With<Ball>>,
+    mut states: Local<HashMap<Entity, BallSoundState>>,
+) {
+    states.retain(|&entity, _| balls.get(entity).is_ok());
+    let eye = match cameras.get_single() {
+        Ok(look_transform) => look_transform.eye,
+        Err(_) => return,
+    };
+    let roll_loaded = audio_sources.get(&sounds.roll).is_some();
+    let impact_loaded = audio_sources.get(&sounds.impact).is_some();
+    let now = Instant::now();
+    for (entity, transform, velocity) in balls.iter() {
+        let velocity = Vec3::from_slice(velocity.linvel.as_slice());
+        let state = states.entry(entity).or_insert(BallSoundState {
+            last_velocity: velocity,
+            next_roll: now,
+            last_impact: None,
+        });
+        let volume = settings.master_volume * distance_falloff(transform.translation.distance(eye));
+
+        let impact = (velocity - state.last_velocity).length();
+        state.last_velocity = velocity;
+        let cooled_down = state
+            .last_impact
+            .map_or(true, |last_impact| now - last_impact >= IMPACT_COOLDOWN);
+        if impact_loaded && cooled_down && volume * impact_volume(impact) >= AUDIBLE_VOLUME {
+            audio.play(sounds.impact.clone());
+            state.last_impact = Some(now);
+        }
+
+        if roll_loaded
+            && now >= state.next_roll
+            && volume * roll_volume(velocity.length()) >= AUDIBLE_VOLUME
+        {
+            audio.play(sounds.roll.clone());
+            state.next_roll = now + ROLL_SOUND_DURATION;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn faster_balls_roll_louder() {
+        assert_eq!(roll_volume(0.0), 0.0);
+        assert_eq!(roll_volume(0.5 * FULL_VOLUME_SPEED), 0.5);
+        assert_eq!(roll_volume(FULL_VOLUME_SPEED), 1.0);
+        assert_eq!(roll_volume(3.0 * FULL_VOLUME_SPEED), 1.0);
+        assert_eq!(impact_volume(0.5 * MIN_IMPACT), 0.0);
+        assert_eq!(impact_volume(0.5 * FULL_VOLUME_IMPACT), 0.5);
+        assert_eq!(impact_volume(2.0 * FULL_VOLUME_IMPACT), 1.0);
+    }
+
+    #[test]
+    fn sounds_fade_with_distance_from_the_camera() {
+        assert_eq!(distance_falloff(0.0), 1.0);
+        assert_eq!(distance_falloff(HALF_VOLUME_DISTANCE), 0.5);
+        assert!(distance_falloff(10.0 * HALF_VOLUME_DISTANCE) < 0.1);
+    }
+}
@@ -0,0 +1,144 @@
+use std::{cmp::Ordering, fs::File, io, path::Path};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use smooth_bevy_cameras::LookTransform;
+
+/// Where the camera is and what it looks at a number of seconds into a cinematic.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CameraKeyframe {
+    pub time: f32,
+    pub eye: Vec3,
+    pub target: Vec3,
+}
+
+/// A scripted camera path, which moves the camera between keyframes in place of the follow and
+/// free cameras while it is playing.
+pub struct CinematicCamera {
+    /// Keyframes in order of time.
+    pub keyframes: Vec<(f32, LookTransform)>,
+    /// Whether to start again from the first keyframe after the last.
+    pub looping: bool,
+    pub playing: bool,
+    /// Seconds since the cinematic started playing.
+    pub elapsed: f32,
+}
+
+impl CinematicCamera {
+    pub fn new(mut keyframes: Vec<CameraKeyframe>, looping: bool) -> Self {
+        keyframes.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap_or(Ordering::Equal));
+        Self {
+            keyframes: keyframes
+                .into_iter()
+                .map(|keyframe| {
+                    (
+                        keyframe.time,
+                        LookTransform {
+                            eye: keyframe.eye,
+                            target: keyframe.target,
+                        },
+                    )
+                })
+                .collect(),
+            looping,
+            playing: false,
+            elapsed: 0.0,
+        }
+    }
+
+    /// The camera's look transform at `time` seconds into the cinematic, linearly interpolated
+    /// between the keyframes either side. Before the first keyframe the camera holds at it, as it
+    /// does after the last unless looping.
+    pub fn sample(&self, time: f32) -> Option<LookTransform> {
+        let (first_time, first) = self.keyframes.first()?;
+        let (last_time, last) = self.keyframes.last()?;
+        let duration = last_time - first_time;
+        let time = if self.looping && duration > 0.0 {
+            first_time + (time - first_time).rem_euclid(duration)
+        } else {
+            time
+        };
+        if time <= *first_time {
+            return Some(copy_look_transform(first));
+        }
+        let next = match self.keyframes.iter().position(|(t, _)| *t > time) {
+            Some(next) => next,
+            None => return Some(copy_look_transform(last)),
+        };
+        let (from_time, from) = &self.keyframes[next - 1];
+        let (to_time, to) = &self.keyframes[next];
+        let t = (time - from_time) / (to_time - from_time);
+        Some(LookTransform {
+            eye: from.eye.lerp(to.eye, t),
+            target: from.target.lerp(to.target, t),
+        })
+    }
+
+    /// Whether a cinematic that does not loop has passed its last keyframe.
+    pub fn finished(&self) -> bool {
+        !self.looping
+            && self
+                .keyframes
+                .last()
+                .map_or(true, |(last_time, _)| self.elapsed >= *last_time)
+    }
+}
+
+fn copy_look_transform(look_transform: &LookTransform) -> LookTransform {
+    LookTransform {
+        eye: look_transform.eye,
+        target: look_transform.target,
+    }
+}
+
+/// Reads a list of camera keyframes from `path` in RON format.
+pub fn load_keyframes(path: &Path) -> io::Result<Vec<CameraKeyframe>> {
+    let file = File::open(path)?;
+    ron::de::from_reader(file).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A cinematic that moves the eye 10 along +x over each of 2 seconds, keyframed out of order.
+    fn cinematic(looping: bool) -> CinematicCamera {
+        let keyframe = |time: f32| CameraKeyframe {
+            time,
+            eye: Vec3::new(10.0 * time, 0.0, 0.0),
+            target: Vec3::new(10.0 * time, 0.0, -10.0),
+        };
+        CinematicCamera::new(vec![keyframe(2.0), keyframe(0.0), keyframe(1.0)], looping)
+    }
+
+    fn eye_x(cinematic: &CinematicCamera, time: f32) -> f32 {
+        cinematic.sample(time).unwrap().eye.x
+    }
+
+    #[test]
+    fn keyframes_are_interpolated_between() {
+        let cinematic = cinematic(false);
+        assert_eq!(eye_x(&cinematic, 0.0), 0.0);
+        assert_eq!(eye_x(&cinematic, 0.25), 2.5);
+        assert_eq!(eye_x(&cinematic, 1.0), 10.0);
+        assert_eq!(eye_x(&cinematic, 1.5), 15.0);
+        assert_eq!(cinematic.sample(1.5).unwrap().target.z, -10.0);
+        // Holding at the first and last keyframes
+        assert_eq!(eye_x(&cinematic, -1.0), 0.0);
+        assert_eq!(eye_x(&cinematic, 3.0), 20.0);
+        assert!(CinematicCamera::new(Vec::new(), false)
+            .sample(1.0)
+            .is_none());
+    }
+
+    #[test]
+    fn looping_cinematics_start_again_after_the_last_keyframe() {
+        let mut cinematic = cinematic(true);
+        assert_eq!(eye_x(&cinematic, 2.5), 5.0);
+        assert_eq!(eye_x(&cinematic, 5.5), 15.0);
+        cinematic.elapsed = 10.0;
+        assert!(!cinematic.finished());
+        cinematic.looping = false;
+        assert!(cinematic.finished());
+    }
+}
@@ -0,0 +1,123 @@
+use std::{fs::File, io, ops::Range, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::race::{MAX_DISADVANTAGE_MS, N_PLAYERS, SPAWN_RADIUS};
+
+pub const CONFIG_PATH: &str = "config.ron";
+
+pub const EARTH_GRAVITY: f32 = 9.81;
+/// Gravity of the moon mode, for floatier races in which balls jump further.
+pub const MOON_GRAVITY: f32 = 1.62;
+
+/// Tunables of the game and its races, so that a race can be tuned without recompiling. Any that
+/// are left out of the config file keep their default.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameConfig {
+    pub window_width: f32,
+    pub window_height: f32,
+    pub n_players: usize,
+    /// Radius of the track, across which balls are spawned.
+    pub spawn_radius: f32,
+    /// Longest start delay that a player can be given.
+    pub max_disadvantage_ms: u64,
+    pub start_mode: StartMode,
+    /// Acceleration towards the finish given to balls trailing the leader by
+    /// `catch_up_max_deficit` or more, and in proportion to those trailing by less. Zero disables
+    /// catching up.
+    pub catch_up_strength: f32,
+    pub catch_up_max_deficit: f32,
+    /// Downward acceleration due to gravity, applied from the start of each round.
+    pub gravity: f32,
+    pub segment_length: f32,
+    pub n_segments: usize,
+    pub yaw_range: Range<f32>,
+    pub pitch_range: Range<f32>,
+    /// Number of pillars standing in the track for balls to dodge.
+    pub obstacles: usize,
+    /// Number of bars spinning in the track to knock balls about.
+    pub hazards: usize,
+}
+
+/// How the balls of a round are started.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StartMode {
+    /// Each ball is spawned after a start delay of its own, of up to
+    /// [`GameConfig::max_disadvantage_ms`].
+    Staggered,
+    /// All balls are spawned at once, when the countdown begins, behind a gate that opens when it
+    /// ends.
+    Simultaneous,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        Self {
+            window_width: 960.0,
+            window_height: 540.0,
+            n_players: N_PLAYERS,
+            spawn_radius: SPAWN_RADIUS,
+            max_disadvantage_ms: MAX_DISADVANTAGE_MS,
+            start_mode: StartMode::Staggered,
+            catch_up_strength: 0.0,
+            catch_up_max_deficit: 50.0,
+            gravity: EARTH_GRAVITY,
+            segment_length: 100.0,
+            n_segments: 10,
+            yaw_range: (-std::f32::consts::FRAC_PI_4)..std::f32::consts::FRAC_PI_4,
+            pitch_range: (-std::f32::consts::FRAC_PI_4)..(-0.1 * std::f32::consts::FRAC_PI_4),
+            obstacles: 0,
+            hazards: 0,
+        }
+    }
+}
+
+/// Reads a config in RON format from `path`. A missing file gives the default config.
+pub fn load_game_config(path: &Path) -> io::Result<GameConfig> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(GameConfig::default()),
+        Err(e) => return Err(e),
+    };
+    ron::de::from_reader(file).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn configs_round_trip_through_ron() {
+        let config = GameConfig {
+            n_players: 7,
+            start_mode: StartMode::Simultaneous,
+            gravity: MOON_GRAVITY,
+            yaw_range: -0.5..0.25,
+            ..Default::default()
+        };
+        let path = std::env::temp_dir().join("bavy-balls-config-round-trip.ron");
+        let ron = ron::ser::to_string_pretty(&config, ron::ser::PrettyConfig::default()).unwrap();
+        std::fs::write(&path, ron).unwrap();
+        let loaded = load_game_config(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.unwrap(), config);
+    }
+
+    #[test]
+    fn missing_settings_keep_their_defaults() {
+        let path = std::env::temp_dir().join("bavy-balls-missing-config.ron");
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(load_game_config(&path).unwrap(), GameConfig::default());
+
+        let config: GameConfig = ron::de::from_str("(n_players: 4)").unwrap();
+        assert_eq!(
+            config,
+            GameConfig {
+                n_players: 4,
+                ..Default::default()
+            }
+        );
+    }
+}
@@ -0,0 +1,196 @@
+use std::ops::Range;
+
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+use crate::{
+    race::{rank_players, RaceConfig, RoundState},
+    sim::simulate_round,
+};
+
+/// How much the outcome of races on a track depends on where the balls start, from many races on
+/// the same track with different spawn positions and start delays.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FairnessReport {
+    pub trials: usize,
+    /// Number of races won from each starting position, from the leftmost spawn position to the
+    /// rightmost.
+    pub wins_by_position: Vec<usize>,
+    /// Mean over all races of the seconds between the first and last finishers.
+    pub mean_finish_spread: f32,
+    /// Fraction of all players in all races who did not finish.
+    pub dnf_rate: f32,
+}
+
+/// Starting position of each player of `round`, by the order of their spawn points from left to
+/// right.
+fn starting_positions(round: &RoundState) -> Vec<usize> {
+    let mut order = (0..round.players.len()).collect::<Vec<_>>();
+    order.sort_by(|&a, &b| {
+        let (a, b) = (
+            round.players[a].spawn_point.x,
+            round.players[b].spawn_point.x,
+        );
+        a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)
+    });
+    let mut positions = vec![0; order.len()];
+    for (position, player) in order.into_iter().enumerate() {
+        positions[player] = position;
+    }
+    positions
+}
+
+/// Seconds between the first and last finishers of `round`, if anybody finished, and the number
+/// of players who did not finish.
+fn finish_spread_and_dnfs(round: &RoundState) -> (Option<f32>, usize) {
+    let finish_times = round
+        .players
+        .iter()
+        .filter(|player| player.finished)
+        .filter_map(|player| player.end_secs)
+        .collect::<Vec<_>>();
+    let spread = match (
+        finish_times.iter().copied().reduce(f32::min),
+        finish_times.iter().copied().reduce(f32::max),
+    ) {
+        (Some(first), Some(last)) => Some(last - first),
+        _ => None,
+    };
+    (spread, round.players.len() - finish_times.len())
+}
+
+/// Races the track generated from `seed` `trials` times headlessly, each time from different
+/// spawn positions and start delays, and reports how fair the track is.
+///
+/// Every race has a winner, who is whoever got furthest if nobody finished.
+pub fn analyze_seed(seed: u64, trials: usize) -> FairnessReport {
+    let n_players = RaceConfig::default().n_players;
+    let mut rng = SmallRng::seed_from_u64(seed);
+    let mut report = FairnessReport {
+        trials,
+        wins_by_position: vec![0; n_players],
+        ..Default::default()
+    };
+    let mut total_spread = 0.0;
+    let mut n_dnfs = 0;
+    for _ in 0..trials {
+        let round = simulate_round(RaceConfig::default(), seed, Some(rng.gen()));
+        let positions = starting_positions(&round);
+        if let Some(&winner) = rank_players(&round).first() {
+            report.wins_by_position[positions[winner]] += 1;
+        }
+        let (spread, dnfs) = finish_spread_and_dnfs(&round);
+        total_spread += spread.unwrap_or(0.0);
+        n_dnfs += dnfs;
+    }
+    if trials > 0 {
+        report.mean_finish_spread = total_spread / trials as f32;
+        report.dnf_rate = n_dnfs as f32 / (trials * n_players).max(1) as f32;
+    }
+    report
+}
+
+/// How hard a track should be, judged from one headless race on it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DifficultySpec {
+    /// Acceptable fractions of players who do not finish.
+    pub dnf_rate: Range<f32>,
+    /// Minimum seconds between the first and last finishers, so that the race is not decided by
+    /// a photo finish.
+    pub min_finish_spread: f32,
+}
+
+impl Default for DifficultySpec {
+    fn default() -> Self {
+        Self {
+            dnf_rate: 0.2..0.4,
+            min_finish_spread: 5.0,
+        }
+    }
+}
+
+impl DifficultySpec {
+    pub fn matches(&self, round: &RoundState) -> bool {
+        let (spread, n_dnfs) = finish_spread_and_dnfs(round);
+        let dnf_rate = n_dnfs as f32 / round.players.len().max(1) as f32;
+        // A race that nobody finished has no spread, as in `analyze_seed`
+        self.dnf_rate.contains(&dnf_rate) && spread.unwrap_or(0.0) >= self.min_finish_spread
+    }
+}
+
+/// Races the tracks of `budget` random seeds headlessly and returns those whose races match
+/// `target`, in the order they were tried.
+pub fn search_seeds(target: DifficultySpec, budget: usize) -> Vec<u64> {
+    let mut rng = SmallRng::from_entropy();
+    (0..budget)
+        .map(|_| rng.gen())
+        .filter(|&seed| target.matches(&simulate_round(RaceConfig::default(), seed, None)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+
+    use super::*;
+    use crate::race::PlayerState;
+
+    #[test]
+    fn starting_positions_count_from_the_left() {
+        let mut round = RoundState::default();
+        for x in [2.0, -1.0, 0.5] {
+            round.players.push(PlayerState::new(
+                String::new(),
+                Color::WHITE,
+                Vec3::new(x, 0.0, 0.0),
+                0.0,
+            ));
+        }
+        assert_eq!(starting_positions(&round), vec![2, 0, 1]);
+    }
+
+    #[test]
+    fn every_race_has_one_winner() {
+        let report = analyze_seed(5, 2);
+        assert_eq!(report.trials, 2);
+        assert_eq!(
+            report.wins_by_position.len(),
+            RaceConfig::default().n_players
+        );
+        assert_eq!(report.wins_by_position.iter().sum::<usize>(), 2);
+        assert!((0.0..=1.0).contains(&report.dnf_rate));
+        assert!(report.mean_finish_spread >= 0.0);
+    }
+
+    #[test]
+    fn difficulty_needs_dnfs_and_a_spread_of_finishes() {
+        let spec = DifficultySpec {
+            dnf_rate: 0.2..0.5,
+            min_finish_spread: 5.0,
+        };
+        let mut round = RoundState::default();
+        for end_secs in [Some(10.0), Some(16.0), Some(20.0), None] {
+            let mut player = PlayerState::new(String::new(), Color::WHITE, Vec3::ZERO, 0.0);
+            player.end_secs = end_secs.or(Some(30.0));
+            player.finished = end_secs.is_some();
+            round.players.push(player);
+        }
+        assert!(spec.matches(&round));
+        // Nobody dropping out is too easy
+        round.players[3].finished = true;
+        assert!(!spec.matches(&round));
+        // A photo finish is not enough of a spread
+        round.players[3].finished = false;
+        round.players[2].end_secs = Some(12.0);
+        round.players[1].end_secs = Some(11.0);
+        assert!(!spec.matches(&round));
+    }
+
+    #[test]
+    fn any_raced_track_matches_a_trivial_spec() {
+        let spec = DifficultySpec {
+            dnf_rate: 0.0..f32::INFINITY,
+            min_finish_spread: 0.0,
+        };
+        assert_eq!(search_seeds(spec, 2).len(), 2);
+    }
+}
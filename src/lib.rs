@@ -1,2 +1,18 @@
+pub mod audio;
+pub mod cinematic;
+pub mod config;
+pub mod fairness;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod live;
 pub mod paths;
+pub mod race;
+pub mod replay;
+pub mod results;
+pub mod screenshot;
+pub mod settings;
 pub mod shapes;
+pub mod sim;
+pub mod tournament;
+pub mod track;
+pub mod trail;
+pub mod votes;
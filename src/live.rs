@@ -0,0 +1,142 @@
+use std::{
+    io::{self, BufRead, BufReader, Write},
+    net::{TcpListener, TcpStream},
+    thread,
+};
+
+use bevy::{log::warn, prelude::*};
+use crossbeam_channel::{Receiver, Sender};
+use serde::{Deserialize, Serialize};
+
+use crate::race::{rank_players, RaceClock, RoundState};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PlayerSnapshot {
+    pub name: String,
+    pub finished: bool,
+    /// Seconds from the start of the round until the player finished or dropped out.
+    pub time: Option<f32>,
+    pub distance: f32,
+}
+
+/// The state of the race at one moment, as served to overlays.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RaceSnapshot {
+    /// Seconds since the start of the round, negative during the countdown.
+    pub time: f32,
+    /// Players in order of rank, the leader first.
+    pub players: Vec<PlayerSnapshot>,
+}
+
+impl RaceSnapshot {
+    pub fn new(round: &RoundState, clock: &RaceClock) -> Self {
+        Self {
+            time: clock.elapsed - round.start_secs,
+            players: rank_players(round)
+                .into_iter()
+                .map(|index| {
+                    let player = &round.players[index];
+                    PlayerSnapshot {
+                        name: player.name.clone(),
+                        finished: player.finished,
+                        time: player.end_secs.map(|end| end - round.start_secs),
+                        distance: player.distance,
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Where [`publish_snapshots`] sends a snapshot of the race every frame.
+pub struct SnapshotSender(pub Sender<RaceSnapshot>);
+
+pub fn publish_snapshots(
+    sender: Res<SnapshotSender>,
+    round: Res<RoundState>,
+    clock: Res<RaceClock>,
+) {
+    // Snapshots that the server has not yet taken are stale, so this one can be dropped in favor
+    // of the next
+    sender.0.try_send(RaceSnapshot::new(&round, &clock)).ok();
+}
+
+/// Serves snapshots of the race as JSON over HTTP on `port`, on a thread of its own. Each request
+/// is long-polled: it is answered with the next snapshot published after it arrives.
+pub fn serve_snapshots(port: u16) -> io::Result<SnapshotSender> {
+    let listener = TcpListener::bind(("0.0.0.0", port))?;
+    let (sender, receiver) = crossbeam_channel::bounded(1);
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let result = stream.and_then(|stream| respond(stream, &receiver));
+            if let Err(e) = result {
+                warn!("Failed to serve race snapshot: {:?}", e);
+            }
+        }
+    });
+    Ok(SnapshotSender(sender))
+}
+
+fn respond(mut stream: TcpStream, receiver: &Receiver<RaceSnapshot>) -> io::Result<()> {
+    // The request itself does not matter, but it has to be read before answering
+    let mut reader = BufReader::new(&stream);
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 0 && line != "\r\n" {
+        line.clear();
+    }
+    // Skip the snapshot left over from before the request, then wait for a fresh one
+    receiver.try_recv().ok();
+    let snapshot = receiver
+        .recv()
+        .map_err(|e| io::Error::new(io::ErrorKind::BrokenPipe, e))?;
+    let body = serde_json::to_string(&snapshot)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\n\
+         Content-Type: application/json\r\n\
+         Access-Control-Allow-Origin: *\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\
+         \r\n\
+         {}",
+        body.len(),
+        body
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::race::PlayerState;
+
+    #[test]
+    fn snapshots_list_players_by_rank_as_json() {
+        let mut round = RoundState {
+            start_secs: 3.0,
+            players: ["RED", "BLUE"]
+                .into_iter()
+                .map(|name| PlayerState::new(name.to_string(), Color::WHITE, Vec3::ZERO, 3.0))
+                .collect(),
+        };
+        round.players[0].distance = -40.0;
+        round.players[1].distance = -100.0;
+        round.players[1].finished = true;
+        round.players[1].end_secs = Some(13.5);
+        let clock = RaceClock {
+            elapsed: 15.0,
+            delta: 0.0,
+        };
+
+        let snapshot = RaceSnapshot::new(&round, &clock);
+        let json = serde_json::to_string(&snapshot).unwrap();
+        assert_eq!(
+            json,
+            r#"{"time":12.0,"players":[{"name":"BLUE","finished":true,"time":10.5,"distance":-100.0},{"name":"RED","finished":false,"time":null,"distance":-40.0}]}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<RaceSnapshot>(&json).unwrap(),
+            snapshot
+        );
+    }
+}
@@ -1,82 +1,546 @@
-use std::time::Duration;
+use std::{path::Path, time::Duration};
 
-use bavy_balls::shapes::{mesh_to_collider_shape, HalfCylinderPath};
+use bavy_balls::{
+    audio::{play_ball_sounds, BallSounds},
+    cinematic::{load_keyframes, CinematicCamera},
+    config::{load_game_config, GameConfig, CONFIG_PATH, MOON_GRAVITY},
+    race::{
+        apply_boosts, apply_catch_up, apply_gravity, apply_slow_zones, apply_time_scale,
+        apply_wind_zones, despawn_all_balls, despawn_balls, despawn_level, detect_finish,
+        detect_photo_finish, log_race_events, nudge_balls, prevent_tunneling, rank_players,
+        record_placements, record_splits, reset_photo_finish, spawn_balls, spawn_track,
+        spin_hazards, start_round, stream_track, tick_race_clock, track_path, Ball, BallCollision,
+        BallPhysics, CountdownTimer, DeterministicMode, FinishLine, GameLevel, GameState, Obstacle,
+        PhotoFinish, PlayerState, RaceClock, RaceConfig, RaceEvent, RaceSeed, RaceSystem,
+        RoundState, SegmentKind, SeriesState, StreamedTrackMesh, SuddenDeath, TimeScale, Track,
+        TrackInfo, TrackPreview, MAX_NAME_LENGTH, NUDGE_KEYS, SPAWN_POSITION,
+    },
+    replay::{
+        move_ghost, play_replay, record_replay, rewind_race, start_recording, Replay, ReplayPlayer,
+        ReplayRecorder,
+    },
+    results::{load_high_scores, save_high_scores, Bet, HighScores, BET_WINNINGS},
+    screenshot::{ScreenshotPlugin, ScreenshotRequest},
+    settings::{LeaderboardStyle, LightingMode, Settings},
+    shapes::{collider_to_mesh, ColliderKind},
+    tournament::{award_points, reset_finished_tournament, Tournament},
+    track::{load_track, save_track, TrackDescriptor},
+    trail::{ribbon_mesh, update_trails, Trail, TrailConfig},
+    votes::{tally_chat_votes, ChatVotes, VoteFeed},
+};
+#[cfg(not(target_arch = "wasm32"))]
+use bavy_balls::{
+    fairness::{search_seeds, DifficultySpec},
+    live::{publish_snapshots, serve_snapshots},
+    replay::{load_ghost, load_replay, save_ghost, GhostBall, GhostRun},
+    results::write_results,
+    votes::StdinVotes,
+};
 use bevy::{
-    input::system::exit_on_esc_system, math::const_vec3, prelude::*, render::primitives::Aabb,
-    ui::CAMERA_UI, utils::Instant,
+    diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin},
+    input::{
+        mouse::{MouseMotion, MouseWheel},
+        system::exit_on_esc_system,
+    },
+    pbr::wireframe::{Wireframe, WireframePlugin},
+    prelude::*,
+    render::{options::WgpuOptions, render_resource::WgpuFeatures},
+    ui::CAMERA_UI,
+    utils::{HashMap, Instant},
 };
 use bevy_rapier3d::{
-    na::{Isometry3, Vector3},
-    physics::TimestepMode,
+    na::{Point3, Vector3},
+    physics::{
+        QueryPipelineColliderComponentsQuery, QueryPipelineColliderComponentsSet, TimestepMode,
+    },
     prelude::*,
 };
-use rand::rngs::SmallRng;
-use rand::{Rng, SeedableRng};
 use smooth_bevy_cameras::{
-    controllers::fps::{FpsCameraBundle, FpsCameraController, FpsCameraPlugin},
+    controllers::fps::{ControlEvent, FpsCameraBundle, FpsCameraController, FpsCameraPlugin},
     LookTransform, LookTransformPlugin, Smoother,
 };
 
-#[derive(Debug, Clone, Eq, PartialEq, Hash)]
-enum GameState {
-    Menu,
-    Playing,
-    GameOver,
+/// The value following the command line argument `name`, if present.
+#[cfg(not(target_arch = "wasm32"))]
+fn arg_value(name: &str) -> Option<String> {
+    std::env::args().skip_while(|arg| arg != name).nth(1)
+}
+
+/// Whether the command line flag `name` was given.
+#[cfg(not(target_arch = "wasm32"))]
+fn has_flag(name: &str) -> bool {
+    std::env::args().any(|arg| arg == name)
+}
+
+/// The `key=value` pairs of the page URL's query string. On the web there is no command line, so
+/// `--seed 42` is given as `?seed=42` instead.
+#[cfg(target_arch = "wasm32")]
+fn query_params() -> Vec<(String, Option<String>)> {
+    let search = web_sys::window()
+        .and_then(|window| window.location().search().ok())
+        .unwrap_or_default();
+    parse_query(&search)
+}
+
+/// The `key=value` pairs of a URL query string `search`. Keys without a value, as for flags, have
+/// `None` for their value.
+#[cfg(any(target_arch = "wasm32", test))]
+fn parse_query(search: &str) -> Vec<(String, Option<String>)> {
+    search
+        .trim_start_matches('?')
+        .split('&')
+        .filter(|param| !param.is_empty())
+        .map(|param| match param.split_once('=') {
+            Some((key, value)) => (key.to_string(), Some(value.to_string())),
+            None => (param.to_string(), None),
+        })
+        .collect()
+}
+
+/// The value of the query parameter matching the command line argument `name`, if present.
+#[cfg(target_arch = "wasm32")]
+fn arg_value(name: &str) -> Option<String> {
+    let key = name.trim_start_matches("--");
+    query_params()
+        .into_iter()
+        .find(|(param, _)| param == key)
+        .and_then(|(_, value)| value)
+}
+
+/// Whether the query parameter matching the command line flag `name` was given.
+#[cfg(target_arch = "wasm32")]
+fn has_flag(name: &str) -> bool {
+    let key = name.trim_start_matches("--");
+    query_params().iter().any(|(param, _)| param == key)
+}
+
+/// The path following the command line argument `name`, if present. There is no file system on
+/// the web, so file arguments are ignored there.
+fn path_arg(name: &str) -> Option<String> {
+    arg_value(name).filter(|_| !cfg!(target_arch = "wasm32"))
+}
+
+fn parse_seed_arg() -> Option<u64> {
+    arg_value("--seed").map(|seed| {
+        seed.parse()
+            .expect("--seed must be an unsigned 64-bit integer")
+    })
+}
+
+fn parse_rounds_arg() -> Option<usize> {
+    arg_value("--rounds").map(|rounds| {
+        rounds
+            .parse()
+            .expect("--rounds must be an unsigned integer")
+    })
+}
+
+fn parse_track_arg() -> Option<TrackDescriptor> {
+    path_arg("--track").map(|path| {
+        load_track(Path::new(&path))
+            .unwrap_or_else(|e| panic!("Failed to load track from {}: {}", path, e))
+    })
+}
+
+fn parse_cinematic_arg() -> Option<CinematicCamera> {
+    path_arg("--cinematic").map(|path| {
+        let keyframes = load_keyframes(Path::new(&path))
+            .unwrap_or_else(|e| panic!("Failed to load cinematic from {}: {}", path, e));
+        CinematicCamera::new(keyframes, has_flag("--loop-cinematic"))
+    })
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn parse_replay_arg() -> Option<Replay> {
+    arg_value("--replay").map(|path| {
+        load_replay(Path::new(&path))
+            .unwrap_or_else(|e| panic!("Failed to load replay from {}: {}", path, e))
+    })
+}
+
+#[cfg(target_arch = "wasm32")]
+fn parse_replay_arg() -> Option<Replay> {
+    None
+}
+
+/// The port on which to serve the live state of races.
+#[cfg(not(target_arch = "wasm32"))]
+fn parse_serve_arg() -> Option<u16> {
+    arg_value("--serve").map(|port| port.parse().expect("--serve must be a port number"))
+}
+
+/// The number of human players nudging their balls, each with their own keys from [`NUDGE_KEYS`].
+fn parse_nudge_arg() -> Option<usize> {
+    arg_value("--nudge").map(|n_nudgers| {
+        let n_nudgers = n_nudgers
+            .parse()
+            .expect("--nudge must be an unsigned integer");
+        if n_nudgers > NUDGE_KEYS.len() {
+            eprintln!("At most {} players can nudge their balls", NUDGE_KEYS.len());
+        }
+        n_nudgers
+    })
 }
 
 fn main() {
+    let seed_arg = parse_seed_arg();
+    let track_arg = parse_track_arg();
+    let cinematic_arg = parse_cinematic_arg();
+    let replay_arg = parse_replay_arg();
+    let rounds_arg = parse_rounds_arg();
+    let seed = replay_arg
+        .as_ref()
+        .map(|replay| replay.seed)
+        .or(seed_arg)
+        .or_else(|| track_arg.as_ref().map(|track| track.path.seed))
+        .unwrap_or_else(rand::random);
+    let mut game_config = load_game_config(Path::new(CONFIG_PATH)).unwrap_or_else(|e| {
+        eprintln!("Failed to load config from {}: {}", CONFIG_PATH, e);
+        GameConfig::default()
+    });
+    if has_flag("--moon") {
+        game_config.gravity = MOON_GRAVITY;
+    }
+    let mut race_config = RaceConfig::new(game_config.n_players);
+    if let Some(n_nudgers) = parse_nudge_arg() {
+        race_config.nudge_keys = NUDGE_KEYS
+            .iter()
+            .copied()
+            .enumerate()
+            .take(n_nudgers.min(race_config.n_players))
+            .collect();
+    }
+    if let Some(track) = track_arg.as_ref() {
+        track.race.apply(&mut race_config);
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(budget) = arg_value("--search") {
+        let budget = budget
+            .parse()
+            .expect("--search must be an unsigned integer");
+        for seed in search_seeds(DifficultySpec::default(), budget) {
+            println!("{}", seed);
+        }
+        return;
+    }
+    if let Some(path) = path_arg("--save-track") {
+        let track = track_arg.clone().unwrap_or_else(|| {
+            TrackDescriptor::new(track_path(
+                seed,
+                race_config.target_laps.is_some(),
+                &game_config,
+            ))
+        });
+        match save_track(&track, Path::new(&path)) {
+            Ok(()) => println!("Saved track to {}", path),
+            Err(e) => eprintln!("Failed to save track to {}: {}", path, e),
+        }
+    }
     let mut app = App::new();
 
     app.insert_resource(WindowDescriptor {
         title: "Bavy Balls".to_string(),
-        width: 960.0,
-        height: 540.0,
-        resizable: false,
-        ..Default::default()
-    })
-    .insert_resource(ClearColor(Color::BLACK))
-    .add_plugins(DefaultPlugins)
-    .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
-    .insert_resource(RapierConfiguration {
-        timestep_mode: TimestepMode::InterpolatedTimestep,
+        width: game_config.window_width,
+        height: game_config.window_height,
+        resizable: true,
         ..Default::default()
     })
-    .add_plugin(LookTransformPlugin)
-    .add_plugin(FpsCameraPlugin::default())
-    .add_system(exit_on_esc_system);
+    .insert_resource(ClearColor(Color::BLACK));
+    let debug_colliders = has_flag("--debug-colliders");
+    if debug_colliders {
+        app.insert_resource(WgpuOptions {
+            features: WgpuFeatures::POLYGON_MODE_LINE,
+            ..Default::default()
+        });
+    }
+    app.add_plugins(DefaultPlugins)
+        .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
+        .add_plugin(LookTransformPlugin)
+        .add_plugin(FpsCameraPlugin::default())
+        .add_plugin(FrameTimeDiagnosticsPlugin::default())
+        .add_plugin(ScreenshotPlugin);
+    if has_flag("--deterministic") {
+        DeterministicMode::default().configure(&mut app);
+    } else {
+        app.insert_resource(RapierConfiguration {
+            timestep_mode: TimestepMode::InterpolatedTimestep,
+            ..Default::default()
+        });
+    }
 
     app.add_state(GameState::Menu)
-        .insert_resource(RoundState {
-            start: Instant::now(),
-            players: Vec::new(),
-        })
+        .init_resource::<RoundState>()
+        .init_resource::<CountdownTimer>()
+        .init_resource::<RaceClock>()
+        .init_resource::<TimeScale>()
+        .insert_resource(RaceSeed(seed))
         .init_resource::<FollowMode>()
+        .init_resource::<ActiveGamepad>()
+        .init_resource::<UiScale>()
+        .init_resource::<MenuSelection>()
+        .insert_resource(Settings {
+            lighting: if has_flag("--directional-light") {
+                LightingMode::Directional
+            } else {
+                LightingMode::PerBall
+            },
+            ..Default::default()
+        })
+        .init_resource::<TrailConfig>()
+        .init_resource::<BallMaterials>()
+        .init_resource::<NameEntry>()
+        .insert_resource(SuddenDeath {
+            enabled: has_flag("--sudden-death"),
+            ..Default::default()
+        })
+        .insert_resource(race_config)
+        .insert_resource(game_config)
+        .init_resource::<ColliderKind>()
+        .init_resource::<BallPhysics>()
+        .insert_resource(BallCollision {
+            players_collide: !has_flag("--ghost"),
+        })
         .add_startup_system(setup)
         .add_startup_system(setup_audio)
+        .add_startup_system(setup_debug_overlay)
+        .add_event::<RaceEvent>()
+        .init_resource::<PhotoFinish>()
+        .add_system(log_race_events)
         .add_system(restart_audio)
+        .add_system(toggle_debug_overlay)
+        .add_system(update_debug_overlay)
+        .add_system(scale_ui)
+        .add_system(track_gamepad)
         // .add_system(hacks)
         .add_system_set(SystemSet::on_enter(GameState::Menu).with_system(setup_menu))
-        .add_system_set(SystemSet::on_update(GameState::Menu).with_system(button_system))
+        .add_system_set(
+            SystemSet::on_update(GameState::Menu)
+                .with_system(button_system)
+                .with_system(menu_keyboard_input)
+                .with_system(exit_on_esc_system),
+        )
         .add_system_set(SystemSet::on_exit(GameState::Menu).with_system(cleanup_menu))
+        .add_system_set(SystemSet::on_enter(GameState::Settings).with_system(setup_settings_menu))
+        .add_system_set(
+            SystemSet::on_update(GameState::Settings)
+                .with_system(button_system)
+                .with_system(menu_keyboard_input)
+                .with_system(settings_button_system)
+                .with_system(seed_entry_input)
+                .with_system(update_settings_menu),
+        )
+        .add_system_set(SystemSet::on_exit(GameState::Settings).with_system(cleanup_menu))
+        .add_system_set(SystemSet::on_enter(GameState::Betting).with_system(setup_betting_menu))
+        .add_system_set(
+            SystemSet::on_update(GameState::Betting)
+                .with_system(button_system)
+                .with_system(menu_keyboard_input)
+                .with_system(bet_button_system),
+        )
+        .add_system_set(SystemSet::on_exit(GameState::Betting).with_system(cleanup_menu))
+        .add_system_set(SystemSet::on_enter(GameState::NameEntry).with_system(setup_name_entry))
+        .add_system_set(
+            SystemSet::on_update(GameState::NameEntry)
+                .with_system(button_system)
+                .with_system(name_entry_input)
+                .with_system(update_name_entry),
+        )
+        .add_system_set(
+            SystemSet::on_exit(GameState::NameEntry)
+                .with_system(apply_entered_names)
+                .with_system(cleanup_menu),
+        )
         .add_system_set(
             SystemSet::on_enter(GameState::Playing)
                 .with_system(setup_live_scoreboard)
+                .with_system(setup_minimap)
+                .with_system(setup_ball_labels)
                 .with_system(setup_level)
+                .with_system(apply_gravity)
+                .with_system(setup_ball_mesh)
+                .with_system(setup_lighting)
                 .with_system(start_round),
         )
         .add_system_set(
             SystemSet::on_update(GameState::Playing)
                 .with_system(follow_ball)
-                .with_system(spawn_balls)
-                .with_system(despawn_balls)
-                .with_system(update_leaderboard),
+                .with_system(direct_camera)
+                .with_system(orbit_input)
+                .with_system(gamepad_camera_input)
+                .with_system(play_cinematic)
+                .with_system(select_clicked_ball)
+                .with_system(update_cursor_visibility)
+                .with_system(stream_track)
+                .with_system(add_streamed_track_visuals)
+                .with_system(add_obstacle_visuals)
+                .with_system(spin_hazards)
+                .with_system(time_scale_input)
+                .with_system(apply_time_scale)
+                .with_system(detect_photo_finish)
+                .with_system(frame_photo_finish)
+                .with_system(update_countdown)
+                .with_system(add_ball_visuals)
+                .with_system(add_ball_trails)
+                .with_system(update_ball_lods)
+                .with_system(play_ball_sounds)
+                .with_system(update_trails)
+                .with_system(update_leaderboard)
+                .with_system(update_race_timer)
+                .with_system(draw_track_preview)
+                .with_system(update_minimap)
+                .with_system(update_ball_labels)
+                .with_system(pause_input)
+                .with_system(photo_mode_input),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .label(RaceSystem::Clock)
+                .with_system(tick_race_clock),
+        )
+        .add_system_set(
+            SystemSet::on_enter(GameState::Paused)
+                .with_system(pause_round)
+                .with_system(setup_pause_overlay),
+        )
+        .add_system_set(SystemSet::on_update(GameState::Paused).with_system(resume_input))
+        .add_system_set(
+            SystemSet::on_exit(GameState::Paused)
+                .with_system(resume_round)
+                .with_system(cleanup_pause_overlay),
+        )
+        .init_resource::<PhotoMode>()
+        .add_system_set(
+            SystemSet::on_enter(GameState::PhotoMode)
+                .with_system(pause_round)
+                .with_system(enter_photo_mode),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::PhotoMode)
+                .with_system(exit_photo_mode_input)
+                .with_system(screenshot_input),
+        )
+        .add_system_set(
+            SystemSet::on_exit(GameState::PhotoMode)
+                .with_system(resume_round)
+                .with_system(exit_photo_mode),
         )
         .add_system_set(
             SystemSet::on_exit(GameState::Playing)
+                .with_system(reset_photo_finish)
                 .with_system(despawn_level)
                 .with_system(despawn_all_balls),
         )
-        .add_system_set(SystemSet::on_enter(GameState::GameOver).with_system(setup_game_over));
+        .add_system_set(SystemSet::on_enter(GameState::GameOver).with_system(setup_game_over))
+        .add_system_set(
+            SystemSet::on_update(GameState::GameOver)
+                .with_system(button_system)
+                .with_system(menu_keyboard_input)
+                .with_system(exit_on_esc_system),
+        )
+        .add_system_set(SystemSet::on_exit(GameState::GameOver).with_system(cleanup_menu));
+    let fixed_track = seed_arg.is_some() || track_arg.is_some() || replay_arg.is_some();
+    if let Some(replay) = replay_arg {
+        // Balls follow the replay in place of the race being simulated
+        app.insert_resource(ReplayPlayer { replay })
+            .add_system_set(SystemSet::on_update(GameState::Playing).with_system(play_replay));
+    } else {
+        app.init_resource::<ReplayRecorder>()
+            .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(start_recording))
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(move_ghost)
+                    // Rewinding resets the balls before anything else moves them this frame
+                    .with_system(
+                        rewind_race
+                            .after(RaceSystem::Clock)
+                            .before(RaceSystem::Spawn),
+                    )
+                    .with_system(record_replay.after(RaceSystem::Despawn)),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .label(RaceSystem::Spawn)
+                    .after(RaceSystem::Clock)
+                    .with_system(spawn_balls),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .label(RaceSystem::Physics)
+                    .after(RaceSystem::Spawn)
+                    .with_system(apply_boosts)
+                    .with_system(apply_slow_zones)
+                    .with_system(nudge_balls)
+                    .with_system(apply_catch_up)
+                    .with_system(apply_wind_zones)
+                    .with_system(prevent_tunneling),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .label(RaceSystem::Finish)
+                    .after(RaceSystem::Physics)
+                    .with_system(detect_finish)
+                    .with_system(record_splits),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .label(RaceSystem::Despawn)
+                    .after(RaceSystem::Finish)
+                    .with_system(despawn_balls),
+            );
+        #[cfg(not(target_arch = "wasm32"))]
+        app.add_system_set(SystemSet::on_enter(GameState::GameOver).with_system(save_replay))
+            .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(setup_ghost));
+        if let Some(n_rounds) = rounds_arg {
+            app.insert_resource(Tournament::new(n_rounds))
+                .add_system_set(SystemSet::on_exit(GameState::Playing).with_system(award_points))
+                .add_system_set(
+                    SystemSet::on_exit(GameState::GameOver).with_system(reset_finished_tournament),
+                );
+        }
+        if has_flag("--handicap") {
+            // Each round after the first delays the start of players by how well they last placed
+            app.init_resource::<SeriesState>().add_system_set(
+                SystemSet::on_enter(GameState::GameOver).with_system(record_placements),
+            );
+        }
+    }
+    if debug_colliders {
+        app.add_plugin(WireframePlugin)
+            .insert_resource(DebugColliders { visible: true })
+            .add_system(toggle_debug_colliders)
+            .add_system(add_collider_wireframes);
+    }
+    if let Some(track) = track_arg {
+        app.insert_resource(track);
+    }
+    if let Some(cinematic) = cinematic_arg {
+        app.insert_resource(cinematic);
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    if has_flag("--chat-votes") {
+        // Chat messages naming balls, relayed to standard input, vote for the ball to follow
+        app.insert_resource(VoteFeed(Box::new(StdinVotes::spawn())))
+            .init_resource::<ChatVotes>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(tally_chat_votes)
+                    .with_system(follow_chat_votes),
+            );
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(port) = parse_serve_arg() {
+        // Overlays can follow the race live over HTTP
+        let sender = serve_snapshots(port)
+            .unwrap_or_else(|e| panic!("Failed to serve on port {}: {}", port, e));
+        app.insert_resource(sender).add_system_set(
+            SystemSet::on_update(GameState::Playing).with_system(publish_snapshots),
+        );
+    }
+    if !fixed_track {
+        // Without a fixed seed or track, every round is on a new track. The seed changes on leaving
+        // the results screen so that the results are recorded against the track they were set on
+        app.add_system_set(SystemSet::on_exit(GameState::GameOver).with_system(randomize_seed));
+    }
 
     app.run();
 }
@@ -85,26 +549,122 @@ const NORMAL_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
 const HOVERED_BUTTON: Color = Color::rgb(0.25, 0.25, 0.25);
 const PRESSED_BUTTON: Color = Color::rgb(0.35, 0.75, 0.35);
 
+/// What a menu button does when clicked.
+#[derive(Component, Clone, Copy)]
+enum MenuButton {
+    Start,
+    EnterNames,
+    NextRound,
+    Settings,
+    Bet,
+    Back,
+}
+
+impl MenuButton {
+    fn label(self) -> &'static str {
+        match self {
+            MenuButton::Start => "START",
+            MenuButton::EnterNames => "NAMES",
+            MenuButton::NextRound => "NEXT",
+            MenuButton::Settings => "SETTINGS",
+            MenuButton::Bet => "BET",
+            MenuButton::Back => "BACK",
+        }
+    }
+
+    fn next_state(self) -> GameState {
+        match self {
+            MenuButton::Start | MenuButton::NextRound => GameState::Playing,
+            MenuButton::EnterNames => GameState::NameEntry,
+            MenuButton::Settings => GameState::Settings,
+            MenuButton::Bet => GameState::Betting,
+            MenuButton::Back => GameState::Menu,
+        }
+    }
+}
+
+/// The menu button focused for keyboard navigation, counting the buttons on screen from the top.
+#[derive(Default)]
+struct MenuSelection {
+    index: usize,
+}
+
+/// Menu buttons in order from the top of the screen to the bottom.
+fn buttons_top_to_bottom<'a>(
+    buttons: impl Iterator<Item = (Entity, &'a GlobalTransform)>,
+) -> Vec<Entity> {
+    let mut buttons = buttons
+        .map(|(entity, transform)| (entity, transform.translation.y))
+        .collect::<Vec<_>>();
+    // UI coordinates increase upwards
+    buttons.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap_or(std::cmp::Ordering::Equal));
+    buttons.into_iter().map(|(entity, _)| entity).collect()
+}
+
 #[allow(clippy::type_complexity)]
 fn button_system(
     mut interaction_query: Query<
-        (&Interaction, &mut UiColor),
-        (Changed<Interaction>, With<Button>),
+        (
+            Entity,
+            &Interaction,
+            ChangeTrackers<Interaction>,
+            &mut UiColor,
+            &MenuButton,
+        ),
+        With<Button>,
     >,
+    transforms: Query<(Entity, &GlobalTransform), With<MenuButton>>,
+    mut selection: ResMut<MenuSelection>,
     mut state: ResMut<State<GameState>>,
 ) {
-    for (interaction, mut color) in interaction_query.iter_mut() {
-        match *interaction {
+    let order = buttons_top_to_bottom(transforms.iter());
+    let focused = order.get(selection.index).copied();
+    for (entity, interaction, tracker, mut color, button) in interaction_query.iter_mut() {
+        *color = match *interaction {
             Interaction::Clicked => {
-                *color = PRESSED_BUTTON.into();
-                state.set(GameState::Playing).ok();
+                state.set(button.next_state()).ok();
+                PRESSED_BUTTON.into()
             }
             Interaction::Hovered => {
-                *color = HOVERED_BUTTON.into();
-            }
-            Interaction::None => {
-                *color = NORMAL_BUTTON.into();
+                // Hovering over a button focuses it, as the arrow keys do
+                if tracker.is_changed() {
+                    if let Some(index) = order.iter().position(|&button| button == entity) {
+                        selection.index = index;
+                    }
+                }
+                HOVERED_BUTTON.into()
             }
+            Interaction::None if focused == Some(entity) => HOVERED_BUTTON.into(),
+            Interaction::None => NORMAL_BUTTON.into(),
+        };
+    }
+}
+
+/// Moves the focus between menu buttons with the up and down arrow keys, and presses the focused
+/// button with Enter.
+fn menu_keyboard_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    buttons: Query<(Entity, &GlobalTransform), With<MenuButton>>,
+    mut colors: Query<(&MenuButton, &mut UiColor)>,
+    mut selection: ResMut<MenuSelection>,
+    mut state: ResMut<State<GameState>>,
+) {
+    let order = buttons_top_to_bottom(buttons.iter());
+    if order.is_empty() {
+        return;
+    }
+    if keyboard_input.just_pressed(KeyCode::Down) {
+        selection.index = (selection.index + 1) % order.len();
+    } else if keyboard_input.just_pressed(KeyCode::Up) {
+        selection.index = (selection.index + order.len() - 1) % order.len();
+    }
+    selection.index = selection.index.min(order.len() - 1);
+    if keyboard_input.just_pressed(KeyCode::Return)
+        || keyboard_input.just_pressed(KeyCode::NumpadEnter)
+    {
+        if let Ok((button, mut color)) = colors.get_mut(order[selection.index]) {
+            *color = PRESSED_BUTTON.into();
+            state.set(button.next_state()).ok();
         }
     }
 }
@@ -113,10 +673,14 @@ struct FontHandle {
     handle: Handle<Font>,
 }
 
+const HIGH_SCORES_PATH: &str = "highscores.ron";
+
 fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
     commands.insert_resource(FontHandle {
         handle: asset_server.load("fonts/FiraSans-Bold.ttf"),
     });
+    commands.insert_resource(load_high_scores(Path::new(HIGH_SCORES_PATH)));
+    commands.insert_resource(BallSounds::load(&asset_server));
 }
 
 struct MusicHandle {
@@ -144,7 +708,76 @@ fn restart_audio(audio: Res<Audio>, mut music: ResMut<MusicHandle>) {
     }
 }
 
-fn setup_menu(mut commands: Commands, font_handle: Res<FontHandle>, mut windows: ResMut<Windows>) {
+/// Window height at which the UI is laid out at its unscaled size.
+const UI_REFERENCE_HEIGHT: f32 = 540.0;
+
+/// Factor by which fixed sizes in the UI are scaled, so that it fills a resized window as it does
+/// the initial one.
+struct UiScale(f32);
+
+impl Default for UiScale {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+fn ui_scale(window_height: f32) -> f32 {
+    (window_height / UI_REFERENCE_HEIGHT).max(f32::EPSILON)
+}
+
+/// The size of a UI node at a [`UiScale`] of 1. Unset dimensions are left as they are.
+#[derive(Component, Default)]
+struct ScaledSize {
+    width: Option<f32>,
+    height: Option<f32>,
+}
+
+/// The font size of a UI text at a [`UiScale`] of 1.
+#[derive(Component)]
+struct ScaledFontSize(f32);
+
+/// Rescales the UI to the height of the window when it is resized, and scales newly spawned nodes
+/// and texts.
+fn scale_ui(
+    windows: Res<Windows>,
+    mut scale: ResMut<UiScale>,
+    mut nodes: Query<(&ScaledSize, ChangeTrackers<ScaledSize>, &mut Style)>,
+    mut texts: Query<(&ScaledFontSize, ChangeTrackers<ScaledFontSize>, &mut Text)>,
+) {
+    if let Some(window) = windows.get_primary() {
+        let new_scale = ui_scale(window.height());
+        if (scale.0 - new_scale).abs() > f32::EPSILON {
+            scale.0 = new_scale;
+        }
+    }
+    for (size, tracker, mut style) in nodes.iter_mut() {
+        if !scale.is_changed() && !tracker.is_added() {
+            continue;
+        }
+        if let Some(width) = size.width {
+            style.size.width = Val::Px(scale.0 * width);
+        }
+        if let Some(height) = size.height {
+            style.size.height = Val::Px(scale.0 * height);
+        }
+    }
+    for (font_size, tracker, mut text) in texts.iter_mut() {
+        if !scale.is_changed() && !tracker.is_added() {
+            continue;
+        }
+        for section in text.sections.iter_mut() {
+            section.style.font_size = scale.0 * font_size.0;
+        }
+    }
+}
+
+fn setup_menu(
+    mut commands: Commands,
+    font_handle: Res<FontHandle>,
+    mut windows: ResMut<Windows>,
+    high_scores: Res<HighScores>,
+    seed: Res<RaceSeed>,
+) {
     for window in windows.iter_mut() {
         window.set_cursor_visibility(true);
     }
@@ -162,35 +795,22 @@ fn setup_menu(mut commands: Commands, font_handle: Res<FontHandle>, mut windows:
             ..Default::default()
         })
         .with_children(|builder| {
-            builder.spawn_bundle(TextBundle {
-                text: Text::with_section(
-                    "BAVY BALLS",
-                    TextStyle {
-                        font: font_handle.handle.clone(),
-                        font_size: 60.0,
-                        color: Color::rgb(0.9, 0.9, 0.9),
-                    },
-                    TextAlignment {
-                        vertical: VerticalAlign::Center,
-                        horizontal: HorizontalAlign::Center,
-                    },
-                ),
-                style: Style {
-                    size: Size::new(Val::Px(300.0), Val::Px(65.0)),
-                    // center button
-                    margin: Rect::all(Val::Auto),
-                    // horizontally center child text
-                    justify_content: JustifyContent::Center,
-                    // vertically center child text
-                    align_items: AlignItems::Center,
-                    ..Default::default()
-                },
-                ..Default::default()
-            });
             builder
-                .spawn_bundle(ButtonBundle {
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        "BAVY BALLS",
+                        TextStyle {
+                            font: font_handle.handle.clone(),
+                            font_size: 60.0,
+                            color: Color::rgb(0.9, 0.9, 0.9),
+                        },
+                        TextAlignment {
+                            vertical: VerticalAlign::Center,
+                            horizontal: HorizontalAlign::Center,
+                        },
+                    ),
                     style: Style {
-                        size: Size::new(Val::Px(150.0), Val::Px(65.0)),
+                        size: Size::new(Val::Px(300.0), Val::Px(65.0)),
                         // center button
                         margin: Rect::all(Val::Auto),
                         // horizontally center child text
@@ -199,33 +819,101 @@ fn setup_menu(mut commands: Commands, font_handle: Res<FontHandle>, mut windows:
                         align_items: AlignItems::Center,
                         ..Default::default()
                     },
-                    color: NORMAL_BUTTON.into(),
                     ..Default::default()
                 })
-                .with_children(|parent| {
-                    parent.spawn_bundle(TextBundle {
-                        text: Text::with_section(
-                            "START",
-                            TextStyle {
-                                font: font_handle.handle.clone(),
-                                font_size: 40.0,
-                                color: Color::rgb(0.9, 0.9, 0.9),
-                            },
-                            Default::default(),
-                        ),
+                .insert_bundle((
+                    ScaledSize {
+                        width: Some(300.0),
+                        height: Some(65.0),
+                    },
+                    ScaledFontSize(60.0),
+                ));
+            builder
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        match high_scores.best_time(seed.0) {
+                            Some(time) => format!("BEST {:5.3}s", time),
+                            None => "NO BEST TIME YET".to_string(),
+                        },
+                        TextStyle {
+                            font: font_handle.handle.clone(),
+                            font_size: 25.0,
+                            color: Color::rgb(0.9, 0.9, 0.9),
+                        },
+                        Default::default(),
+                    ),
+                    style: Style {
+                        margin: Rect::all(Val::Auto),
                         ..Default::default()
-                    });
-                });
+                    },
+                    ..Default::default()
+                })
+                .insert(ScaledFontSize(25.0));
+            spawn_button(builder, &font_handle, MenuButton::Start);
+            spawn_button(builder, &font_handle, MenuButton::Bet);
+            spawn_button(builder, &font_handle, MenuButton::EnterNames);
+            spawn_button(builder, &font_handle, MenuButton::Settings);
         });
 
     info!("Menu");
 }
 
+fn spawn_button(builder: &mut ChildBuilder, font_handle: &FontHandle, button: MenuButton) {
+    builder
+        .spawn_bundle(ButtonBundle {
+            style: Style {
+                size: Size::new(Val::Undefined, Val::Px(65.0)),
+                // wide enough for the longest label
+                min_size: Size::new(Val::Px(150.0), Val::Undefined),
+                padding: Rect {
+                    left: Val::Px(20.0),
+                    right: Val::Px(20.0),
+                    ..Default::default()
+                },
+                // center button
+                margin: Rect::all(Val::Auto),
+                // horizontally center child text
+                justify_content: JustifyContent::Center,
+                // vertically center child text
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: NORMAL_BUTTON.into(),
+            ..Default::default()
+        })
+        .insert_bundle((
+            button,
+            ScaledSize {
+                height: Some(65.0),
+                ..Default::default()
+            },
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        button.label(),
+                        TextStyle {
+                            font: font_handle.handle.clone(),
+                            font_size: 40.0,
+                            color: Color::rgb(0.9, 0.9, 0.9),
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert(ScaledFontSize(40.0));
+        });
+}
+
 fn cleanup_menu(
     mut commands: Commands,
     cameras: Query<(Entity, &Camera)>,
-    nodes: Query<Entity, With<Node>>,
+    nodes: Query<Entity, (With<Node>, Without<DebugOverlay>)>,
+    mut selection: ResMut<MenuSelection>,
 ) {
+    // The next menu starts with its top button focused
+    selection.index = 0;
     for (entity, camera) in cameras.iter() {
         if camera.name == Some(CAMERA_UI.to_string()) {
             commands.entity(entity).despawn_recursive();
@@ -236,685 +924,3682 @@ fn cleanup_menu(
     }
 }
 
-fn setup_game_over(mut state: ResMut<State<GameState>>) {
-    info!("Game over!");
-    state.set(GameState::Menu).ok();
-}
-
-// fn hacks(keyboard_input: Res<Input<KeyCode>>, mut state: ResMut<State<GameState>>) {
-//     if keyboard_input.just_pressed(KeyCode::M) {
-//         state.set(GameState::Menu).ok();
-//     } else if keyboard_input.just_pressed(KeyCode::P) {
-//         state.set(GameState::Playing).ok();
-//     } else if keyboard_input.just_pressed(KeyCode::O) {
-//         state.set(GameState::GameOver).ok();
-//     }
-// }
-
-const SPAWN_POSITION: Vec3 = Vec3::ZERO;
-const SPAWN_RADIUS: f32 = 75.0;
-
-#[derive(Component)]
-struct GameLevel;
+/// A button on the betting screen that bets on the ball of a player, and starts the race.
+#[derive(Component, Clone, Copy)]
+struct BetButton(usize);
 
-fn setup_level(
+fn setup_betting_menu(
     mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
+    font_handle: Res<FontHandle>,
+    race_config: Res<RaceConfig>,
+    high_scores: Res<HighScores>,
 ) {
-    let half_cylinder_mesh = Mesh::from(HalfCylinderPath {
-        start: SPAWN_POSITION,
-        radius: SPAWN_RADIUS,
-        segment_length: 100.0,
-        n_segments: 10,
-        seed: rand::random(),
-        yaw_range: (-std::f32::consts::FRAC_PI_4)..std::f32::consts::FRAC_PI_4,
-        pitch_range: (-std::f32::consts::FRAC_PI_4)..(-0.1 * std::f32::consts::FRAC_PI_4),
-        ..Default::default()
-    });
-    let half_cylinder_collider = mesh_to_collider_shape(&half_cylinder_mesh)
-        .expect("Failed to convert half cylinder mesh to collider");
-    let half_cylinder_handle = meshes.add(half_cylinder_mesh);
-    let mut half_cylinder_material = StandardMaterial::from(Color::SILVER);
-    half_cylinder_material.perceptual_roughness = 0.5;
-    let half_cylinder_material = materials.add(half_cylinder_material);
-
-    spawn_halfpipe_segment(
-        &mut commands,
-        half_cylinder_handle,
-        half_cylinder_material,
-        half_cylinder_collider,
-        Vec3::ZERO,
-        Quat::IDENTITY,
-    );
-
+    let text_style = |font_size: f32, color: Color| TextStyle {
+        font: font_handle.handle.clone(),
+        font_size,
+        color,
+    };
+    // ui camera
+    commands.spawn_bundle(UiCameraBundle::default());
     commands
-        .spawn_bundle(FpsCameraBundle::new(
-            FpsCameraController {
-                enabled: false,
-                smoothing_weight: 0.99,
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::ColumnReverse,
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
                 ..Default::default()
             },
-            PerspectiveCameraBundle::default(),
-            SPAWN_POSITION + Vec3::new(0.0, 1.0, 1.0),
-            SPAWN_POSITION,
-        ))
-        .insert(GameLevel);
-}
-
-fn spawn_halfpipe_segment(
-    commands: &mut Commands,
-    mesh: Handle<Mesh>,
-    material: Handle<StandardMaterial>,
-    collider_shape: ColliderShape,
-    translation: Vec3,
-    rotation: Quat,
-) {
-    let (axis, angle) = rotation.to_axis_angle();
-    let position = Isometry3::new(
-        Vector3::new(translation.x, translation.y, translation.z),
-        Vector3::new(axis.x, axis.y, axis.z) * angle,
-    );
-    commands
-        .spawn_bundle(RigidBodyBundle {
-            body_type: RigidBodyType::Static.into(),
-            position: RigidBodyPosition {
-                position,
-                next_position: position,
-            }
-            .into(),
+            color: Color::NONE.into(),
             ..Default::default()
         })
-        .insert_bundle((RigidBodyPositionSync::Discrete, GameLevel))
         .with_children(|builder| {
             builder
-                .spawn_bundle(PbrBundle {
-                    mesh,
-                    material,
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        "PICK THE WINNER",
+                        text_style(60.0, Color::rgb(0.9, 0.9, 0.9)),
+                        Default::default(),
+                    ),
+                    style: Style {
+                        margin: Rect::all(Val::Px(20.0)),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .insert(ScaledFontSize(60.0));
+            builder
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        format!(
+                            "{} COINS. A WINNING PICK WINS {}",
+                            high_scores.coins, BET_WINNINGS
+                        ),
+                        text_style(25.0, Color::GOLD),
+                        Default::default(),
+                    ),
+                    style: Style {
+                        margin: Rect::all(Val::Px(10.0)),
+                        ..Default::default()
+                    },
                     ..Default::default()
                 })
-                .insert_bundle(ColliderBundle {
-                    shape: collider_shape.into(),
+                .insert(ScaledFontSize(25.0));
+            builder
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        flex_wrap: FlexWrap::Wrap,
+                        justify_content: JustifyContent::Center,
+                        size: Size::new(Val::Percent(80.0), Val::Undefined),
+                        ..Default::default()
+                    },
+                    color: Color::NONE.into(),
                     ..Default::default()
                 })
-                .insert_bundle((ColliderPositionSync::Discrete, Track));
+                .with_children(|parent| {
+                    for (i, ball_info) in race_config
+                        .balls
+                        .iter()
+                        .take(race_config.n_players)
+                        .enumerate()
+                    {
+                        parent
+                            .spawn_bundle(ButtonBundle {
+                                style: Style {
+                                    size: Size::new(Val::Px(180.0), Val::Px(45.0)),
+                                    margin: Rect::all(Val::Px(5.0)),
+                                    justify_content: JustifyContent::Center,
+                                    align_items: AlignItems::Center,
+                                    ..Default::default()
+                                },
+                                color: row_tint(ball_info.color).into(),
+                                ..Default::default()
+                            })
+                            .insert_bundle((
+                                BetButton(i),
+                                ScaledSize {
+                                    width: Some(180.0),
+                                    height: Some(45.0),
+                                },
+                            ))
+                            .with_children(|parent| {
+                                parent
+                                    .spawn_bundle(TextBundle {
+                                        text: Text::with_section(
+                                            ball_info.name.to_string(),
+                                            text_style(
+                                                25.0,
+                                                contrasting_text_color(ball_info.color),
+                                            ),
+                                            Default::default(),
+                                        ),
+                                        ..Default::default()
+                                    })
+                                    .insert(ScaledFontSize(25.0));
+                            });
+                    }
+                });
+            spawn_button(builder, &font_handle, MenuButton::Back);
         });
-}
-
-#[derive(Component)]
-struct Track;
-
-#[derive(Default)]
-struct Prng {
-    rng: Option<SmallRng>,
-}
-
-#[derive(Component)]
-struct Ball;
-
-const N_PLAYERS: usize = 10;
-
-struct BallInfo {
-    name: &'static str,
-    color: Color,
-}
-
-const BALL_INFO: [BallInfo; N_PLAYERS] = [
-    BallInfo {
-        name: "RED",
-        color: Color::RED,
-    },
-    BallInfo {
-        name: "ORANGE",
-        color: Color::ORANGE_RED,
-    },
-    BallInfo {
-        name: "YELLOW",
-        color: Color::ORANGE,
-    },
-    BallInfo {
-        name: "GREEN",
-        color: Color::GREEN,
-    },
-    BallInfo {
-        name: "BLUE",
-        color: Color::MIDNIGHT_BLUE,
-    },
-    BallInfo {
-        name: "INDIGO",
-        color: Color::BLUE,
-    },
-    BallInfo {
-        name: "VIOLET",
-        color: Color::INDIGO,
-    },
-    BallInfo {
-        name: "WHITE",
-        color: Color::WHITE,
-    },
-    BallInfo {
-        name: "DARK_GRAY",
-        color: Color::DARK_GRAY,
-    },
-    BallInfo {
-        name: "BLACK",
-        color: Color::BLACK,
-    },
-];
 
-struct PlayerState {
-    name: String,
-    color: Color,
-    entity: Option<Entity>,
-    start: Instant,
-    end: Option<Instant>,
-    distance: f32,
-    finished: bool,
+    info!("Betting");
 }
 
-impl PlayerState {
-    fn new(name: String, color: Color, start: Instant) -> Self {
-        Self {
-            name,
-            color,
-            entity: None,
-            start,
-            end: None,
-            distance: 0.0,
-            finished: false,
+#[allow(clippy::type_complexity)]
+fn bet_button_system(
+    mut commands: Commands,
+    interaction_query: Query<(&Interaction, &BetButton), (Changed<Interaction>, With<Button>)>,
+    race_config: Res<RaceConfig>,
+    mut state: ResMut<State<GameState>>,
+) {
+    for (interaction, button) in interaction_query.iter() {
+        if *interaction == Interaction::Clicked {
+            info!("Betting on {}", race_config.balls[button.0].name);
+            commands.insert_resource(Bet { player: button.0 });
+            state.set(GameState::Playing).ok();
         }
     }
 }
 
-struct RoundState {
-    start: Instant,
-    players: Vec<PlayerState>,
-}
-
-const MAX_DISADVANTAGE_MS: u64 = 10000;
+/// Number of entrants whose names can be typed in before a race. The rest keep their default names.
+const NAME_ENTRY_SLOTS: usize = 4;
 
-fn start_round(mut rng: Local<Prng>, mut round: ResMut<RoundState>, mut windows: ResMut<Windows>) {
-    for window in windows.iter_mut() {
-        window.set_cursor_visibility(false);
-    }
-    if rng.rng.is_none() {
-        rng.rng = Some(SmallRng::seed_from_u64(rand::random()));
-    }
-    let rng = rng.rng.as_mut().unwrap();
-    round.start = Instant::now();
-    round.players.clear();
-    round.players = (0..N_PLAYERS)
-        .map(|i| {
-            PlayerState::new(
-                format!("{} ({})", BALL_INFO[i].name, (i + 1) % N_PLAYERS),
-                BALL_INFO[i].color,
-                round.start + Duration::from_millis(rng.gen_range(0u64..MAX_DISADVANTAGE_MS)),
-            )
-        })
-        .collect();
-    info!("Starting the round!");
+/// Names typed in on the name entry screen, and the slot being typed into.
+#[derive(Default)]
+struct NameEntry {
+    names: Vec<String>,
+    current: usize,
 }
 
 #[derive(Component)]
-struct Leaderboard;
-
-#[derive(Component)]
-struct LeaderboardPlayer {
+struct NameEntrySlot {
     index: usize,
 }
 
-#[derive(Component)]
-struct LeaderboardPlayerName {
-    index: usize,
-}
+fn setup_name_entry(
+    mut commands: Commands,
+    font_handle: Res<FontHandle>,
+    race_config: Res<RaceConfig>,
+    mut name_entry: ResMut<NameEntry>,
+) {
+    let n_slots = NAME_ENTRY_SLOTS.min(race_config.n_players);
+    name_entry.names.resize(n_slots, String::new());
+    name_entry.current = 0;
 
-fn setup_live_scoreboard(mut commands: Commands, font_handle: Res<FontHandle>) {
+    let text_style = |font_size: f32, color: Color| TextStyle {
+        font: font_handle.handle.clone(),
+        font_size,
+        color,
+    };
     // ui camera
     commands.spawn_bundle(UiCameraBundle::default());
-
-    // root node
     commands
         .spawn_bundle(NodeBundle {
             style: Style {
+                flex_direction: FlexDirection::ColumnReverse,
                 size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
-                justify_content: JustifyContent::SpaceBetween,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
                 ..Default::default()
             },
             color: Color::NONE.into(),
             ..Default::default()
         })
-        .with_children(|parent| {
-            // right vertical fill
-            parent
-                .spawn_bundle(NodeBundle {
+        .with_children(|builder| {
+            builder
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        "ENTER NAMES",
+                        text_style(60.0, Color::rgb(0.9, 0.9, 0.9)),
+                        Default::default(),
+                    ),
                     style: Style {
-                        flex_direction: FlexDirection::ColumnReverse,
-                        justify_content: JustifyContent::Center,
-                        size: Size::new(Val::Px(200.0), Val::Percent(100.0)),
+                        margin: Rect::all(Val::Px(20.0)),
                         ..Default::default()
                     },
-                    color: Color::rgba(0.5, 0.5, 0.5, 0.15).into(),
                     ..Default::default()
                 })
-                .with_children(|parent| {
-                    // Title
-                    parent.spawn_bundle(TextBundle {
-                        style: Style {
-                            size: Size::new(Val::Undefined, Val::Px(25.)),
-                            margin: Rect {
-                                left: Val::Auto,
-                                right: Val::Auto,
-                                ..Default::default()
-                            },
-                            ..Default::default()
-                        },
+                .insert(ScaledFontSize(60.0));
+            for index in 0..n_slots {
+                builder
+                    .spawn_bundle(TextBundle {
                         text: Text::with_section(
-                            "Leaderboard",
-                            TextStyle {
-                                font: font_handle.handle.clone(),
-                                font_size: 25.,
-                                color: Color::WHITE,
-                            },
+                            "",
+                            text_style(40.0, race_config.balls[index].color),
                             Default::default(),
                         ),
+                        style: Style {
+                            margin: Rect::all(Val::Px(5.0)),
+                            ..Default::default()
+                        },
                         ..Default::default()
-                    });
-                    // List with hidden overflow
-                    parent
-                        .spawn_bundle(NodeBundle {
-                            style: Style {
-                                flex_direction: FlexDirection::ColumnReverse,
-                                align_self: AlignSelf::Center,
-                                size: Size::new(Val::Percent(100.0), Val::Percent(50.0)),
-                                overflow: Overflow::Hidden,
-                                ..Default::default()
-                            },
-                            color: Color::rgba(0.75, 0.75, 0.75, 0.10).into(),
+                    })
+                    .insert_bundle((NameEntrySlot { index }, ScaledFontSize(40.0)));
+            }
+            builder
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        "Type a name and press ENTER for the next",
+                        text_style(25.0, Color::rgb(0.9, 0.9, 0.9)),
+                        Default::default(),
+                    ),
+                    style: Style {
+                        margin: Rect {
+                            top: Val::Px(40.0),
                             ..Default::default()
-                        })
-                        .with_children(|parent| {
-                            // Moving panel
-                            parent
-                                .spawn_bundle(NodeBundle {
-                                    style: Style {
-                                        flex_direction: FlexDirection::ColumnReverse,
-                                        flex_grow: 1.0,
-                                        max_size: Size::new(Val::Undefined, Val::Undefined),
-                                        ..Default::default()
-                                    },
-                                    color: Color::NONE.into(),
-                                    ..Default::default()
-                                })
-                                .insert(Leaderboard)
-                                .with_children(|parent| {
-                                    // List items
-                                    for (i, ball_info) in BALL_INFO.iter().enumerate() {
-                                        parent
-                                            .spawn_bundle(NodeBundle {
-                                                style: Style {
-                                                    justify_content: JustifyContent::FlexEnd,
-                                                    size: Size::new(
-                                                        Val::Px(200.0),
-                                                        Val::Percent(100.0),
-                                                    ),
-                                                    flex_direction: FlexDirection::Row,
-                                                    ..Default::default()
-                                                },
-                                                color: Color::NONE.into(),
-                                                ..Default::default()
-                                            })
-                                            .with_children(|parent| {
-                                                parent
-                                                    .spawn_bundle(TextBundle {
-                                                        style: Style {
-                                                            flex_shrink: 0.,
-                                                            size: Size::new(
-                                                                Val::Undefined,
-                                                                Val::Px(20.),
-                                                            ),
-                                                            margin: Rect {
-                                                                left: Val::Px(10.),
-                                                                right: Val::Auto,
-                                                                ..Default::default()
-                                                            },
-                                                            ..Default::default()
-                                                        },
-                                                        text: Text::with_section(
-                                                            ball_info.name,
-                                                            TextStyle {
-                                                                font: font_handle.handle.clone(),
-                                                                font_size: 20.,
-                                                                color: ball_info.color,
-                                                            },
-                                                            Default::default(),
-                                                        ),
-                                                        ..Default::default()
-                                                    })
-                                                    .insert(LeaderboardPlayerName { index: i });
-                                                parent
-                                                    .spawn_bundle(TextBundle {
-                                                        style: Style {
-                                                            flex_shrink: 0.,
-                                                            size: Size::new(
-                                                                Val::Undefined,
-                                                                Val::Px(20.),
-                                                            ),
-                                                            margin: Rect {
-                                                                right: Val::Px(10.),
-                                                                left: Val::Auto,
-                                                                ..Default::default()
-                                                            },
-                                                            ..Default::default()
-                                                        },
-                                                        text: Text::with_section(
-                                                            ball_info.name,
-                                                            TextStyle {
-                                                                font: font_handle.handle.clone(),
-                                                                font_size: 20.,
-                                                                color: ball_info.color,
-                                                            },
-                                                            Default::default(),
-                                                        ),
-                                                        ..Default::default()
-                                                    })
-                                                    .insert(LeaderboardPlayer { index: i });
-                                            });
-                                    }
-                                });
-                        });
-                });
+                        },
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .insert(ScaledFontSize(25.0));
+            spawn_button(builder, &font_handle, MenuButton::Start);
         });
+
+    info!("Name entry");
 }
 
-fn update_leaderboard(
-    mut names: Query<(&LeaderboardPlayerName, &mut Text), Without<LeaderboardPlayer>>,
-    mut distances: Query<(&LeaderboardPlayer, &mut Text), Without<LeaderboardPlayerName>>,
-    round: Res<RoundState>,
+/// Types characters into the current name entry slot. Enter moves on to the next slot, starting
+/// the race after the last, and Escape goes back to the menu.
+fn name_entry_input(
+    mut received_characters: EventReader<ReceivedCharacter>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut name_entry: ResMut<NameEntry>,
+    mut state: ResMut<State<GameState>>,
 ) {
-    let mut player_order = round
-        .players
-        .iter()
-        .enumerate()
-        .map(|(i, player)| (player.distance, player.end, i))
-        .collect::<Vec<_>>();
-    player_order.sort_unstable_by(|a, b| {
-        a.0.partial_cmp(&b.0)
-            .unwrap()
-            .then_with(|| a.1.unwrap_or(round.start).cmp(&b.1.unwrap_or(round.start)))
-    });
-    for (player, mut text) in distances.iter_mut() {
-        let list_index = player.index;
-        let (distance, end, player_index) = player_order[list_index];
-        text.sections[0].value = if round.players[player_index].finished {
-            format!("{:5.3}s", (end.unwrap() - round.start).as_secs_f64())
+    let current = name_entry.current;
+    if let Some(name) = name_entry.names.get_mut(current) {
+        for event in received_characters.iter() {
+            // Control characters, including those sent for backspace and enter, are handled as
+            // key presses below
+            if !event.char.is_control() && name.chars().count() < MAX_NAME_LENGTH {
+                name.push(event.char);
+            }
+        }
+        if keyboard_input.just_pressed(KeyCode::Back) {
+            name.pop();
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Return) {
+        if name_entry.current + 1 < name_entry.names.len() {
+            name_entry.current += 1;
         } else {
-            format!(
-                "{}{:5.1}m",
-                if end.is_some() && !round.players[player_index].finished {
-                    "DNF "
-                } else {
-                    ""
-                },
-                distance.abs()
-            )
-        };
-        text.sections[0].style.color = round.players[player_index].color;
+            state.set(GameState::Playing).ok();
+        }
     }
-    for (player, mut text) in names.iter_mut() {
-        let list_index = player.index;
-        let (_, _, player_index) = player_order[list_index];
-        text.sections[0].value = round.players[player_index].name.to_string();
-        text.sections[0].style.color = round.players[player_index].color;
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        // Otherwise the menu would see the same press and exit
+        keyboard_input.reset(KeyCode::Escape);
+        state.set(GameState::Menu).ok();
     }
 }
 
-fn spawn_balls(
-    mut commands: Commands,
-    meshes: ResMut<Assets<Mesh>>,
-    materials: ResMut<Assets<StandardMaterial>>,
-    mut rng: Local<Prng>,
-    mut round: ResMut<RoundState>,
+fn update_name_entry(
+    name_entry: Res<NameEntry>,
+    race_config: Res<RaceConfig>,
+    mut slots: Query<(&NameEntrySlot, &mut Text)>,
 ) {
-    let now = Instant::now();
-    if rng.rng.is_none() {
-        rng.rng = Some(SmallRng::seed_from_u64(rand::random()));
-    }
-    let rng = rng.rng.as_mut().unwrap();
-    let meshes = meshes.into_inner();
-    let materials = materials.into_inner();
-    for player in round.players.iter_mut() {
-        if player.entity.is_none() && player.end.is_none() && now > player.start {
-            let spawn_point = SPAWN_POSITION
-                + Vec3::new(
-                    rng.gen_range((-0.9 * SPAWN_RADIUS + 1.0)..(0.9 * SPAWN_RADIUS - 1.0)),
-                    0.0,
-                    -1.0,
-                );
-            player.entity = Some(spawn_ball(
-                &mut commands,
-                meshes,
-                materials,
-                spawn_point,
-                player.color,
-            ));
-        }
+    for (slot, mut text) in slots.iter_mut() {
+        let name = &name_entry.names[slot.index];
+        let cursor = if slot.index == name_entry.current {
+            "_"
+        } else {
+            ""
+        };
+        text.sections[0].value = if name.is_empty() && slot.index != name_entry.current {
+            race_config.balls[slot.index].name.to_string()
+        } else {
+            format!("{}{}", name, cursor)
+        };
     }
 }
 
-fn spawn_ball(
-    commands: &mut Commands,
-    meshes: &mut Assets<Mesh>,
-    materials: &mut Assets<StandardMaterial>,
-    spawn_point: Vec3,
-    ball_color: Color,
-) -> Entity {
-    commands
-        .spawn_bundle(RigidBodyBundle {
-            body_type: RigidBodyType::Dynamic.into(),
-            position: spawn_point.into(),
-            velocity: RigidBodyVelocity {
-                linvel: -1.0f32 * Vector3::z(),
-                ..Default::default()
+fn apply_entered_names(name_entry: Res<NameEntry>, mut race_config: ResMut<RaceConfig>) {
+    race_config.set_names(&name_entry.names);
+}
+
+/// Player counts that the settings menu steps through.
+const PLAYER_COUNTS: [usize; 7] = [2, 4, 6, 8, 10, 15, 20];
+
+/// A setting that a button on the settings screen changes when clicked.
+#[derive(Component, Clone, Copy)]
+enum SettingButton {
+    Lighting,
+    Shadows,
+    Leaderboard,
+    Players,
+    Volume,
+    Seed,
+}
+
+impl SettingButton {
+    const ALL: [SettingButton; 6] = [
+        SettingButton::Lighting,
+        SettingButton::Shadows,
+        SettingButton::Leaderboard,
+        SettingButton::Players,
+        SettingButton::Volume,
+        SettingButton::Seed,
+    ];
+
+    fn label(self, settings: &Settings, race_config: &RaceConfig, seed: &RaceSeed) -> String {
+        match self {
+            SettingButton::Lighting => match settings.lighting {
+                LightingMode::PerBall => "LIGHTS: PER BALL".to_string(),
+                LightingMode::Directional => "LIGHTS: SINGLE".to_string(),
+            },
+            SettingButton::Shadows => {
+                format!("SHADOWS: {}", if settings.shadows { "ON" } else { "OFF" })
             }
-            .into(),
-            ccd: RigidBodyCcd {
-                ccd_enabled: true,
-                ..Default::default()
+            SettingButton::Leaderboard => match settings.leaderboard {
+                LeaderboardStyle::Sorted => "LEADERBOARD: SORTED".to_string(),
+                LeaderboardStyle::Sticky => "LEADERBOARD: STICKY".to_string(),
+            },
+            SettingButton::Players => format!("PLAYERS: {}", race_config.n_players),
+            SettingButton::Volume => format!("VOLUME: {:.0}%", 100.0 * settings.master_volume),
+            SettingButton::Seed => format!("SEED: {}_", seed.0),
+        }
+    }
+
+    /// Changes the setting, which takes effect from the next round.
+    fn apply(self, settings: &mut Settings, race_config: &mut RaceConfig, seed: &mut RaceSeed) {
+        match self {
+            SettingButton::Lighting => settings.lighting = settings.lighting.toggled(),
+            SettingButton::Shadows => settings.shadows = !settings.shadows,
+            SettingButton::Leaderboard => settings.leaderboard = settings.leaderboard.toggled(),
+            SettingButton::Players => {
+                let n_players = PLAYER_COUNTS
+                    .iter()
+                    .copied()
+                    .find(|&n_players| n_players > race_config.n_players)
+                    .unwrap_or(PLAYER_COUNTS[0]);
+                race_config.set_n_players(n_players);
             }
-            .into(),
-            ..Default::default()
-        })
-        .insert_bundle((
-            Ball,
-            RigidBodyPositionSync::Discrete,
-            Transform::from_translation(spawn_point),
-            GlobalTransform::from_translation(spawn_point),
-        ))
-        .with_children(|builder| {
-            builder
-                .spawn_bundle(PbrBundle {
-                    mesh: meshes.add(Mesh::from(bevy::prelude::shape::Icosphere {
-                        radius: 1.0,
+            SettingButton::Volume => settings.step_volume(),
+            SettingButton::Seed => seed.0 = rand::random(),
+        }
+    }
+}
+
+#[derive(Component)]
+struct SettingLabel(SettingButton);
+
+fn setup_settings_menu(mut commands: Commands, font_handle: Res<FontHandle>) {
+    let text_style = |font_size: f32| TextStyle {
+        font: font_handle.handle.clone(),
+        font_size,
+        color: Color::rgb(0.9, 0.9, 0.9),
+    };
+    // ui camera
+    commands.spawn_bundle(UiCameraBundle::default());
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::ColumnReverse,
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .with_children(|builder| {
+            builder
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section("SETTINGS", text_style(60.0), Default::default()),
+                    style: Style {
+                        margin: Rect::all(Val::Px(20.0)),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .insert(ScaledFontSize(60.0));
+            for setting in SettingButton::ALL {
+                builder
+                    .spawn_bundle(ButtonBundle {
+                        style: Style {
+                            size: Size::new(Val::Px(400.0), Val::Px(45.0)),
+                            margin: Rect::all(Val::Px(5.0)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..Default::default()
+                        },
+                        color: NORMAL_BUTTON.into(),
                         ..Default::default()
-                    })),
-                    material: materials.add(StandardMaterial {
-                        base_color: ball_color,
-                        emissive: ball_color,
-                        perceptual_roughness: 0.9,
+                    })
+                    .insert_bundle((
+                        setting,
+                        ScaledSize {
+                            width: Some(400.0),
+                            height: Some(45.0),
+                        },
+                    ))
+                    .with_children(|parent| {
+                        parent
+                            .spawn_bundle(TextBundle {
+                                text: Text::with_section("", text_style(30.0), Default::default()),
+                                ..Default::default()
+                            })
+                            .insert_bundle((SettingLabel(setting), ScaledFontSize(30.0)));
+                    });
+            }
+            builder
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        "Type a seed, or click it for a random one",
+                        text_style(25.0),
+                        Default::default(),
+                    ),
+                    style: Style {
+                        margin: Rect {
+                            top: Val::Px(20.0),
+                            ..Default::default()
+                        },
                         ..Default::default()
-                    }),
+                    },
+                    ..Default::default()
+                })
+                .insert(ScaledFontSize(25.0));
+            spawn_button(builder, &font_handle, MenuButton::Back);
+        });
+
+    info!("Settings");
+}
+
+#[allow(clippy::type_complexity)]
+fn settings_button_system(
+    mut interaction_query: Query<
+        (&Interaction, &mut UiColor, &SettingButton),
+        (Changed<Interaction>, With<Button>),
+    >,
+    mut settings: ResMut<Settings>,
+    mut race_config: ResMut<RaceConfig>,
+    mut seed: ResMut<RaceSeed>,
+) {
+    for (interaction, mut color, setting) in interaction_query.iter_mut() {
+        match *interaction {
+            Interaction::Clicked => {
+                *color = PRESSED_BUTTON.into();
+                setting.apply(&mut settings, &mut race_config, &mut seed);
+            }
+            Interaction::Hovered => {
+                *color = HOVERED_BUTTON.into();
+            }
+            Interaction::None => {
+                *color = NORMAL_BUTTON.into();
+            }
+        }
+    }
+}
+
+/// Types digits into the seed, and goes back to the menu with Escape.
+fn seed_entry_input(
+    mut received_characters: EventReader<ReceivedCharacter>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut seed: ResMut<RaceSeed>,
+    mut state: ResMut<State<GameState>>,
+) {
+    for event in received_characters.iter() {
+        let digit = match event.char.to_digit(10) {
+            Some(digit) => digit as u64,
+            None => continue,
+        };
+        // Digits that would overflow the seed are ignored
+        if let Some(entered) = seed.0.checked_mul(10).and_then(|s| s.checked_add(digit)) {
+            seed.0 = entered;
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Back) {
+        seed.0 /= 10;
+    }
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        // Otherwise the menu would see the same press and exit
+        keyboard_input.reset(KeyCode::Escape);
+        state.set(GameState::Menu).ok();
+    }
+}
+
+fn update_settings_menu(
+    settings: Res<Settings>,
+    race_config: Res<RaceConfig>,
+    seed: Res<RaceSeed>,
+    mut labels: Query<(&SettingLabel, &mut Text)>,
+) {
+    for (label, mut text) in labels.iter_mut() {
+        text.sections[0].value = label.0.label(&settings, &race_config, &seed);
+    }
+}
+
+/// Halves the time scale with [ and doubles it with ], for slow motion and fast forward.
+fn time_scale_input(keyboard_input: Res<Input<KeyCode>>, mut time_scale: ResMut<TimeScale>) {
+    let scale = time_scale.0;
+    if keyboard_input.just_pressed(KeyCode::LBracket) {
+        time_scale.set(0.5 * scale);
+    } else if keyboard_input.just_pressed(KeyCode::RBracket) {
+        time_scale.set(2.0 * scale);
+    }
+    if time_scale.0 != scale {
+        info!("Time scale: {}x", time_scale.0);
+    }
+}
+
+const PAUSE_KEYS: [KeyCode; 2] = [KeyCode::Escape, KeyCode::P];
+
+fn pause_input(mut keyboard_input: ResMut<Input<KeyCode>>, mut state: ResMut<State<GameState>>) {
+    for key in PAUSE_KEYS {
+        if keyboard_input.just_pressed(key) {
+            // Otherwise the paused state would see the same press and immediately resume
+            keyboard_input.reset(key);
+            state.push(GameState::Paused).ok();
+        }
+    }
+}
+
+fn resume_input(mut keyboard_input: ResMut<Input<KeyCode>>, mut state: ResMut<State<GameState>>) {
+    for key in PAUSE_KEYS {
+        if keyboard_input.just_pressed(key) {
+            keyboard_input.reset(key);
+            state.pop().ok();
+        }
+    }
+}
+
+const PHOTO_MODE_KEY: KeyCode = KeyCode::F2;
+const SCREENSHOT_KEY: KeyCode = KeyCode::Return;
+
+/// Photo mode freezes the race and hides the UI, leaving the free camera to fly around for a clean
+/// view of the scene, which [`SCREENSHOT_KEY`] captures.
+#[derive(Default)]
+struct PhotoMode {
+    /// UI nodes hidden by photo mode, to be shown again when it ends.
+    hidden: Vec<Entity>,
+}
+
+fn photo_mode_input(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if keyboard_input.just_pressed(PHOTO_MODE_KEY) {
+        // Otherwise photo mode would see the same press and immediately end
+        keyboard_input.reset(PHOTO_MODE_KEY);
+        state.push(GameState::PhotoMode).ok();
+    }
+}
+
+fn exit_photo_mode_input(
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut state: ResMut<State<GameState>>,
+) {
+    for key in [PHOTO_MODE_KEY, KeyCode::Escape] {
+        if keyboard_input.just_pressed(key) {
+            keyboard_input.reset(key);
+            state.pop().ok();
+        }
+    }
+}
+
+fn screenshot_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut screenshot_request: ResMut<ScreenshotRequest>,
+) {
+    if keyboard_input.just_pressed(SCREENSHOT_KEY) {
+        screenshot_request.0 = true;
+    }
+}
+
+/// Hides the UI and hands the camera to the free camera controller. Following stops while in
+/// photo mode, as the playing systems do not run.
+#[allow(clippy::type_complexity)]
+fn enter_photo_mode(
+    mut photo_mode: ResMut<PhotoMode>,
+    mut ui_roots: Query<(Entity, &mut Style), (With<Node>, Without<Parent>)>,
+    mut cameras: Query<(&mut FpsCameraController, &mut Smoother)>,
+    mut windows: ResMut<Windows>,
+) {
+    photo_mode.hidden.clear();
+    for (entity, mut style) in ui_roots.iter_mut() {
+        if style.display == Display::Flex {
+            style.display = Display::None;
+            photo_mode.hidden.push(entity);
+        }
+    }
+    for (mut controller, mut smoother) in cameras.iter_mut() {
+        controller.enabled = true;
+        smoother.set_lag_weight(controller.smoothing_weight);
+    }
+    for window in windows.iter_mut() {
+        window.set_cursor_visibility(false);
+    }
+}
+
+/// Shows the UI hidden by photo mode again, and gives the camera back to following if it was.
+fn exit_photo_mode(
+    mut photo_mode: ResMut<PhotoMode>,
+    follow_mode: Res<FollowMode>,
+    mut styles: Query<&mut Style>,
+    mut cameras: Query<(&mut FpsCameraController, &mut Smoother)>,
+) {
+    for entity in photo_mode.hidden.drain(..) {
+        if let Ok(mut style) = styles.get_mut(entity) {
+            style.display = Display::Flex;
+        }
+    }
+    for (mut controller, mut smoother) in cameras.iter_mut() {
+        controller.enabled = !follow_mode.following;
+        smoother.set_lag_weight(if follow_mode.following {
+            FOLLOW_LAG_WEIGHT
+        } else {
+            controller.smoothing_weight
+        });
+    }
+}
+
+// The race clock only advances while playing, so pausing it needs nothing more than pausing the
+// physics
+fn pause_round(mut rapier_config: ResMut<RapierConfiguration>) {
+    rapier_config.physics_pipeline_active = false;
+}
+
+fn resume_round(mut rapier_config: ResMut<RapierConfiguration>) {
+    rapier_config.physics_pipeline_active = true;
+}
+
+#[derive(Component)]
+struct PauseOverlay;
+
+fn setup_pause_overlay(mut commands: Commands, font_handle: Res<FontHandle>) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.5).into(),
+            ..Default::default()
+        })
+        .insert(PauseOverlay)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        "PAUSED",
+                        TextStyle {
+                            font: font_handle.handle.clone(),
+                            font_size: 60.0,
+                            color: Color::rgb(0.9, 0.9, 0.9),
+                        },
+                        Default::default(),
+                    ),
                     ..Default::default()
                 })
-                .insert_bundle(ColliderBundle {
-                    shape: ColliderShape::ball(1.0).into(),
+                .insert(ScaledFontSize(60.0));
+        });
+}
+
+fn cleanup_pause_overlay(mut commands: Commands, overlays: Query<Entity, With<PauseOverlay>>) {
+    for entity in overlays.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Whether the colliders of the track are drawn as wireframes, to check them against the track as
+/// rendered. Drawing wireframes needs a GPU feature that not every platform has, so this is only
+/// available with `--debug-colliders`.
+struct DebugColliders {
+    visible: bool,
+}
+
+/// A wireframe of the shape of the collider that is its parent.
+#[derive(Component)]
+struct ColliderWireframe;
+
+const DEBUG_COLLIDERS_KEY: KeyCode = KeyCode::F4;
+
+fn toggle_debug_colliders(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut debug_colliders: ResMut<DebugColliders>,
+    mut wireframes: Query<&mut Visibility, With<ColliderWireframe>>,
+) {
+    if !keyboard_input.just_pressed(DEBUG_COLLIDERS_KEY) {
+        return;
+    }
+    debug_colliders.visible = !debug_colliders.visible;
+    for mut visibility in wireframes.iter_mut() {
+        visibility.is_visible = debug_colliders.visible;
+    }
+}
+
+/// Adds a wireframe to each new track collider. The wireframes are only drawn, and have no effect
+/// on physics.
+#[allow(clippy::type_complexity)]
+fn add_collider_wireframes(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut material: Local<Option<Handle<StandardMaterial>>>,
+    debug_colliders: Res<DebugColliders>,
+    colliders: Query<
+        (Entity, &ColliderShapeComponent),
+        (With<Track>, Added<ColliderShapeComponent>),
+    >,
+) {
+    for (entity, shape) in colliders.iter() {
+        let mesh = match collider_to_mesh(shape) {
+            Some(mesh) => mesh,
+            None => continue,
+        };
+        // Only the wireframe is wanted, so the faces themselves are fully transparent
+        let material = material
+            .get_or_insert_with(|| {
+                materials.add(StandardMaterial {
+                    base_color: Color::NONE,
+                    alpha_mode: AlphaMode::Blend,
+                    unlit: true,
                     ..Default::default()
                 })
-                .insert(ColliderPositionSync::Discrete)
-                .insert_bundle(PointLightBundle {
-                    point_light: PointLight {
-                        color: ball_color,
-                        intensity: 5000.0,
-                        range: 50.0,
-                        radius: 1.0,
-                        shadows_enabled: false,
-                        ..Default::default()
+            })
+            .clone();
+        commands.entity(entity).with_children(|parent| {
+            parent
+                .spawn_bundle(PbrBundle {
+                    mesh: meshes.add(mesh),
+                    material,
+                    visibility: Visibility {
+                        is_visible: debug_colliders.visible,
                     },
                     ..Default::default()
-                });
+                })
+                .insert_bundle((ColliderWireframe, Wireframe));
+        });
+    }
+}
+
+/// Text of the debug overlay, which shows performance and race statistics for tuning. It is built
+/// once and hidden rather than despawned, so that it can be shown in any state.
+#[derive(Component)]
+struct DebugOverlay;
+
+const DEBUG_OVERLAY_KEY: KeyCode = KeyCode::F3;
+
+fn setup_debug_overlay(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                display: Display::None,
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(10.0),
+                    bottom: Val::Px(10.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 20.0,
+                    color: Color::YELLOW,
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert_bundle((DebugOverlay, ScaledFontSize(20.0)));
+}
+
+fn toggle_debug_overlay(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut pipeline: ResMut<PhysicsPipeline>,
+    mut overlays: Query<&mut Style, With<DebugOverlay>>,
+) {
+    if !keyboard_input.just_pressed(DEBUG_OVERLAY_KEY) {
+        return;
+    }
+    for mut style in overlays.iter_mut() {
+        style.display = match style.display {
+            Display::None => {
+                // Physics is only timed while the overlay is showing the timings
+                pipeline.counters.enable();
+                Display::Flex
+            }
+            Display::Flex => {
+                pipeline.counters.disable();
+                Display::None
+            }
+        };
+    }
+}
+
+/// The lines of the debug overlay.
+fn debug_stats_text(
+    fps: Option<f64>,
+    n_balls: usize,
+    leader: Option<&str>,
+    physics_step_ms: f64,
+    n_track_triangles: usize,
+) -> String {
+    format!(
+        "FPS: {}\nBALLS: {}\nLEADER: {}\nPHYSICS: {:.2}ms\nTRACK TRIANGLES: {}",
+        fps.map_or_else(|| "-".to_string(), |fps| format!("{:.0}", fps)),
+        n_balls,
+        leader.unwrap_or("-"),
+        physics_step_ms,
+        n_track_triangles
+    )
+}
+
+fn update_debug_overlay(
+    diagnostics: Res<Diagnostics>,
+    pipeline: Res<PhysicsPipeline>,
+    round: Res<RoundState>,
+    meshes: Res<Assets<Mesh>>,
+    track_meshes: Query<&Handle<Mesh>, With<Track>>,
+    mut overlays: Query<(&Style, &mut Text), With<DebugOverlay>>,
+) {
+    let (style, mut text) = match overlays.get_single_mut() {
+        Ok(overlay) => overlay,
+        Err(_) => return,
+    };
+    if style.display == Display::None {
+        return;
+    }
+    let fps = diagnostics
+        .get(FrameTimeDiagnosticsPlugin::FPS)
+        .and_then(|fps| fps.average());
+    let n_balls = round
+        .players
+        .iter()
+        .filter(|player| player.entity.is_some() && player.end_secs.is_none())
+        .count();
+    let leader = rank_players(&round)
+        .first()
+        .map(|&player| round.players[player].name.as_str());
+    let n_track_triangles = track_meshes
+        .iter()
+        .filter_map(|handle| meshes.get(handle))
+        .filter_map(|mesh| mesh.indices())
+        .map(|indices| indices.len() / 3)
+        .sum();
+    text.sections[0].value = debug_stats_text(
+        fps,
+        n_balls,
+        leader,
+        pipeline.counters.step_time(),
+        n_track_triangles,
+    );
+}
+
+/// The cursor is only needed to click on balls to follow, so it is hidden while flying freely.
+fn update_cursor_visibility(follow_mode: Res<FollowMode>, mut windows: ResMut<Windows>) {
+    for window in windows.iter_mut() {
+        if window.cursor_visible() != follow_mode.following {
+            window.set_cursor_visibility(follow_mode.following);
+        }
+    }
+}
+
+fn randomize_seed(mut seed: ResMut<RaceSeed>) {
+    seed.0 = rand::random();
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+const RESULTS_PATH: &str = "results.json";
+#[cfg(not(target_arch = "wasm32"))]
+const REPLAY_PATH: &str = "replay.ron";
+
+/// Where the ghost of the best run on the track generated from `seed` is kept.
+#[cfg(not(target_arch = "wasm32"))]
+fn ghost_path(seed: u64) -> String {
+    format!("ghost_{}.ron", seed)
+}
+
+/// Spawns a ghost of the best run on the current track, if there has been one, for the balls to
+/// race against.
+#[cfg(not(target_arch = "wasm32"))]
+fn setup_ghost(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    seed: Res<RaceSeed>,
+    ball_physics: Res<BallPhysics>,
+) {
+    let path = ghost_path(seed.0);
+    let ghost = match load_ghost(Path::new(&path)) {
+        Ok(ghost) if ghost.seed == seed.0 => ghost,
+        Ok(_) => {
+            warn!("Ignoring ghost in {} recorded on another track", path);
+            commands.remove_resource::<GhostRun>();
+            return;
+        }
+        Err(e) => {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                warn!("Failed to read ghost from {}: {}", path, e);
+            }
+            commands.remove_resource::<GhostRun>();
+            return;
+        }
+    };
+    commands.insert_resource(ghost);
+    commands
+        .spawn_bundle(PbrBundle {
+            mesh: meshes.add(Mesh::from(bevy::prelude::shape::Icosphere {
+                radius: ball_physics.radius,
+                subdivisions: BALL_LOD_SUBDIVISIONS[0],
+            })),
+            material: materials.add(StandardMaterial {
+                base_color: Color::rgba(0.9, 0.9, 1.0, 0.3),
+                alpha_mode: AlphaMode::Blend,
+                unlit: true,
+                ..Default::default()
+            }),
+            visibility: Visibility { is_visible: false },
+            ..Default::default()
         })
-        .id()
+        .insert_bundle((GhostBall, GameLevel));
+}
+
+/// Saves the run of the winner of `round` as the ghost to race against on the track generated from
+/// `seed` from now on.
+#[cfg(not(target_arch = "wasm32"))]
+fn save_best_run(seed: u64, round: &RoundState, recorder: &ReplayRecorder) {
+    let winner = match rank_players(round).first() {
+        Some(&winner) => winner,
+        None => return,
+    };
+    let path = ghost_path(seed);
+    let ghost = GhostRun::from_replay(&recorder.replay(), winner);
+    if let Err(e) = save_ghost(&ghost, Path::new(&path)) {
+        warn!("Failed to write ghost to {}: {}", path, e);
+    }
+}
+
+/// Ghosts are not kept on the web.
+#[cfg(target_arch = "wasm32")]
+fn save_best_run(_seed: u64, _round: &RoundState, _recorder: &ReplayRecorder) {}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn save_replay(recorder: Res<ReplayRecorder>) {
+    if let Err(e) = recorder.save_replay(Path::new(REPLAY_PATH)) {
+        warn!("Failed to write replay to {}: {}", REPLAY_PATH, e);
+    }
+}
+
+const PODIUM_PLACES: usize = 3;
+
+/// A line of text for each of the top [`PODIUM_PLACES`] players of `round`, with their color.
+fn podium(round: &RoundState) -> Vec<(String, Color)> {
+    rank_players(round)
+        .into_iter()
+        .take(PODIUM_PLACES)
+        .enumerate()
+        .map(|(place, player_index)| {
+            let player = &round.players[player_index];
+            let result = match player.end_secs {
+                Some(end) if player.finished => format!("{:5.3}s", end - round.start_secs),
+                _ => "DNF".to_string(),
+            };
+            (
+                format!("{}. {}  {}", place + 1, player.name, result),
+                player.color,
+            )
+        })
+        .collect()
+}
+
+#[allow(clippy::too_many_arguments)]
+fn setup_game_over(
+    mut commands: Commands,
+    font_handle: Res<FontHandle>,
+    mut windows: ResMut<Windows>,
+    round: Res<RoundState>,
+    seed: Res<RaceSeed>,
+    mut high_scores: ResMut<HighScores>,
+    replay_player: Option<Res<ReplayPlayer>>,
+    tournament: Option<Res<Tournament>>,
+    recorder: Option<Res<ReplayRecorder>>,
+    bet: Option<Res<Bet>>,
+) {
+    info!("Game over!");
+    // A replay has no results of its own to keep
+    let replaying = replay_player.is_some();
+    #[cfg(not(target_arch = "wasm32"))]
+    if !replaying {
+        if let Err(e) = write_results(&round, Path::new(RESULTS_PATH)) {
+            warn!("Failed to write results to {}: {}", RESULTS_PATH, e);
+        }
+    }
+    let new_record = !replaying && high_scores.record(seed.0, &round);
+    // A bet is on one race only
+    let bet_result = bet
+        .filter(|_| !replaying)
+        .map(|bet| (bet.player, high_scores.settle_bet(&bet, &round)));
+    commands.remove_resource::<Bet>();
+    if new_record || matches!(bet_result, Some((_, true))) {
+        if let Err(e) = save_high_scores(&high_scores, Path::new(HIGH_SCORES_PATH)) {
+            warn!("Failed to write high scores to {}: {}", HIGH_SCORES_PATH, e);
+        }
+    }
+    if new_record {
+        if let Some(recorder) = recorder {
+            save_best_run(seed.0, &round, &recorder);
+        }
+    }
+    for window in windows.iter_mut() {
+        window.set_cursor_visibility(true);
+    }
+
+    let text_style = |font_size: f32, color: Color| TextStyle {
+        font: font_handle.handle.clone(),
+        font_size,
+        color,
+    };
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::ColumnReverse,
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: Color::rgba(0.0, 0.0, 0.0, 0.75).into(),
+            ..Default::default()
+        })
+        .with_children(|builder| {
+            builder
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        "RESULTS",
+                        text_style(60.0, Color::rgb(0.9, 0.9, 0.9)),
+                        Default::default(),
+                    ),
+                    style: Style {
+                        margin: Rect::all(Val::Px(20.0)),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .insert(ScaledFontSize(60.0));
+            for (line, color) in podium(&round) {
+                builder
+                    .spawn_bundle(TextBundle {
+                        text: Text::with_section(line, text_style(40.0, color), Default::default()),
+                        style: Style {
+                            margin: Rect::all(Val::Px(5.0)),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    })
+                    .insert(ScaledFontSize(40.0));
+            }
+            if let Some((player, won)) = bet_result {
+                let name = &round.players[player].name;
+                builder
+                    .spawn_bundle(TextBundle {
+                        text: Text::with_section(
+                            if won {
+                                format!(
+                                    "{} WON YOU {} COINS! YOU HAVE {}",
+                                    name, BET_WINNINGS, high_scores.coins
+                                )
+                            } else {
+                                format!("YOUR BET ON {} LOST", name)
+                            },
+                            text_style(30.0, if won { Color::GOLD } else { Color::GRAY }),
+                            Default::default(),
+                        ),
+                        style: Style {
+                            margin: Rect::all(Val::Px(10.0)),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    })
+                    .insert(ScaledFontSize(30.0));
+            }
+            if new_record {
+                builder
+                    .spawn_bundle(TextBundle {
+                        text: Text::with_section(
+                            "NEW BEST TIME!",
+                            text_style(30.0, Color::GOLD),
+                            Default::default(),
+                        ),
+                        style: Style {
+                            margin: Rect::all(Val::Px(10.0)),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    })
+                    .insert(ScaledFontSize(30.0));
+            }
+            if let Some(tournament) = tournament.as_ref().filter(|t| t.finished()) {
+                builder
+                    .spawn_bundle(TextBundle {
+                        text: Text::with_section(
+                            "STANDINGS",
+                            text_style(40.0, Color::rgb(0.9, 0.9, 0.9)),
+                            Default::default(),
+                        ),
+                        style: Style {
+                            margin: Rect {
+                                top: Val::Px(20.0),
+                                ..Default::default()
+                            },
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    })
+                    .insert(ScaledFontSize(40.0));
+                for (place, player_index) in tournament
+                    .standings()
+                    .into_iter()
+                    .filter(|&player_index| player_index < round.players.len())
+                    .take(PODIUM_PLACES)
+                    .enumerate()
+                {
+                    let player = &round.players[player_index];
+                    builder
+                        .spawn_bundle(TextBundle {
+                            text: Text::with_section(
+                                format!(
+                                    "{}. {}  {} PTS",
+                                    place + 1,
+                                    player.name,
+                                    tournament.points[player_index]
+                                ),
+                                text_style(30.0, player.color),
+                                Default::default(),
+                            ),
+                            style: Style {
+                                margin: Rect::all(Val::Px(5.0)),
+                                ..Default::default()
+                            },
+                            ..Default::default()
+                        })
+                        .insert(ScaledFontSize(30.0));
+                }
+            }
+            let (prompt, button) = match tournament.as_ref().filter(|t| !t.finished()) {
+                Some(tournament) => (
+                    format!(
+                        "Round {} of {}. Press NEXT for the next round",
+                        tournament.rounds_played, tournament.n_rounds
+                    ),
+                    MenuButton::NextRound,
+                ),
+                None => ("Press START to play again".to_string(), MenuButton::Start),
+            };
+            builder
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        prompt,
+                        text_style(25.0, Color::rgb(0.9, 0.9, 0.9)),
+                        Default::default(),
+                    ),
+                    style: Style {
+                        margin: Rect {
+                            top: Val::Px(40.0),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .insert(ScaledFontSize(25.0));
+            spawn_button(builder, &font_handle, button);
+        });
+}
+
+// fn hacks(keyboard_input: Res<Input<KeyCode>>, mut state: ResMut<State<GameState>>) {
+//     if keyboard_input.just_pressed(KeyCode::M) {
+//         state.set(GameState::Menu).ok();
+//     } else if keyboard_input.just_pressed(KeyCode::P) {
+//         state.set(GameState::Playing).ok();
+//     } else if keyboard_input.just_pressed(KeyCode::O) {
+//         state.set(GameState::GameOver).ok();
+//     }
+// }
+
+#[allow(clippy::too_many_arguments)]
+fn setup_level(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    collider_kind: Res<ColliderKind>,
+    race_config: Res<RaceConfig>,
+    seed: Res<RaceSeed>,
+    track: Option<Res<TrackDescriptor>>,
+    game_config: Res<GameConfig>,
+) {
+    let closed = race_config.target_laps.is_some();
+    let path = match track {
+        Some(track) => track.path.clone(),
+        None => track_path(seed.0, closed, &game_config),
+    };
+    let segments =
+        spawn_track(&mut commands, path, &collider_kind, &race_config).unwrap_or_else(|e| {
+            warn!(
+                "Failed to spawn the track, using the default instead: {}",
+                e
+            );
+            let path = track_path(seed.0, closed, &GameConfig::default());
+            spawn_track(&mut commands, path, &collider_kind, &race_config)
+                .expect("Failed to spawn the default track")
+        });
+    let mut segment_materials = HashMap::default();
+    for (entity, mesh, kind) in segments {
+        let material = segment_materials
+            .entry(kind)
+            .or_insert_with(|| materials.add(segment_material(kind)))
+            .clone();
+        commands.entity(entity).insert_bundle(PbrBundle {
+            mesh: meshes.add(mesh),
+            material,
+            ..Default::default()
+        });
+    }
+
+    commands
+        .spawn_bundle(FpsCameraBundle::new(
+            FpsCameraController {
+                enabled: false,
+                smoothing_weight: 0.99,
+                ..Default::default()
+            },
+            PerspectiveCameraBundle::default(),
+            SPAWN_POSITION + Vec3::new(0.0, 1.0, 1.0),
+            SPAWN_POSITION,
+        ))
+        .insert(GameLevel);
+}
+
+/// Renders the obstacles in the track as they are spawned.
+fn add_obstacle_visuals(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    obstacles: Query<(Entity, &ColliderShapeComponent), Added<Obstacle>>,
+) {
+    for (entity, shape) in obstacles.iter() {
+        let mesh = match collider_to_mesh(shape) {
+            Some(mesh) => mesh,
+            None => continue,
+        };
+        let mut material = StandardMaterial::from(Color::CRIMSON);
+        material.perceptual_roughness = 0.5;
+        commands.entity(entity).insert_bundle(PbrBundle {
+            mesh: meshes.add(mesh),
+            material: materials.add(material),
+            ..Default::default()
+        });
+    }
+}
+
+fn segment_material(kind: SegmentKind) -> StandardMaterial {
+    let mut material = StandardMaterial::from(match kind {
+        SegmentKind::Normal => Color::SILVER,
+        SegmentKind::Bouncy => Color::LIME_GREEN,
+        SegmentKind::Boost => Color::ORANGE,
+        SegmentKind::SlowZone => Color::PURPLE,
+    });
+    material.perceptual_roughness = 0.5;
+    material
+}
+
+/// Renders the pieces of a streamed track as they are spawned.
+fn add_streamed_track_visuals(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut material: Local<Option<Handle<StandardMaterial>>>,
+    chunks: Query<(Entity, &StreamedTrackMesh)>,
+) {
+    for (entity, mesh) in chunks.iter() {
+        let material = material
+            .get_or_insert_with(|| materials.add(segment_material(SegmentKind::Normal)))
+            .clone();
+        commands
+            .entity(entity)
+            .remove::<StreamedTrackMesh>()
+            .insert_bundle(PbrBundle {
+                mesh: meshes.add(mesh.0.clone()),
+                material,
+                ..Default::default()
+            });
+    }
+}
+
+/// Subdivisions of the ball icosphere at each level of detail, from the nearest to the furthest.
+const BALL_LOD_SUBDIVISIONS: [usize; 3] = [5, 2, 0];
+/// Distances from the camera beyond which balls switch to each coarser level of detail.
+const BALL_LOD_DISTANCES: [f32; 2] = [100.0, 300.0];
+/// How far past a level of detail's distance a ball must go before switching, so that a ball near
+/// the boundary does not flip back and forth between levels.
+const BALL_LOD_HYSTERESIS: f32 = 10.0;
+
+/// The meshes shared by every ball in the level, one for each level of detail.
+struct BallMesh(Vec<Handle<Mesh>>);
+
+/// The level of detail at which a ball's mesh is drawn.
+#[derive(Component)]
+struct BallLod {
+    level: usize,
+}
+
+/// The level of detail for a ball at `distance` from the camera that was at level `current`. A
+/// ball only changes level once it is `hysteresis` beyond the distance between the levels.
+fn select_lod(distance: f32, current: usize, distances: &[f32], hysteresis: f32) -> usize {
+    let mut level = current.min(distances.len());
+    while level < distances.len() && distance > distances[level] + hysteresis {
+        level += 1;
+    }
+    while level > 0 && distance < distances[level - 1] - hysteresis {
+        level -= 1;
+    }
+    level
+}
+
+fn update_ball_lods(
+    cameras: Query<&LookTransform, With<FpsCameraController>>,
+    ball_mesh: Res<BallMesh>,
+    mut visuals: Query<(&GlobalTransform, &mut BallLod, &mut Handle<Mesh>)>,
+) {
+    let eye = match cameras.get_single() {
+        Ok(look_transform) => look_transform.eye,
+        Err(_) => return,
+    };
+    for (transform, mut lod, mut mesh) in visuals.iter_mut() {
+        let distance = transform.translation.distance(eye);
+        let level = select_lod(
+            distance,
+            lod.level,
+            &BALL_LOD_DISTANCES,
+            BALL_LOD_HYSTERESIS,
+        );
+        if level != lod.level {
+            lod.level = level;
+            *mesh = ball_mesh.0[level].clone();
+        }
+    }
+}
+
+/// A material for each color of ball, keyed by the color quantized to 8 bits per channel. These
+/// are kept between rounds, so that spawning a ball of a color seen before adds no new assets.
+#[derive(Default)]
+struct BallMaterials(HashMap<[u8; 4], Handle<StandardMaterial>>);
+
+fn quantize_color(color: Color) -> [u8; 4] {
+    color
+        .as_rgba_f32()
+        .map(|channel| (255.0 * channel.clamp(0.0, 1.0)).round() as u8)
+}
+
+impl BallMaterials {
+    /// The material for balls of `color`, added on first use.
+    fn material(
+        &mut self,
+        color: Color,
+        materials: &mut Assets<StandardMaterial>,
+    ) -> Handle<StandardMaterial> {
+        self.0
+            .entry(quantize_color(color))
+            .or_insert_with(|| {
+                materials.add(StandardMaterial {
+                    base_color: color,
+                    emissive: color,
+                    perceptual_roughness: 0.9,
+                    ..Default::default()
+                })
+            })
+            .clone()
+    }
+}
+
+fn setup_ball_mesh(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    ball_physics: Res<BallPhysics>,
+) {
+    commands.insert_resource(BallMesh(
+        BALL_LOD_SUBDIVISIONS
+            .iter()
+            .map(|&subdivisions| {
+                meshes.add(Mesh::from(bevy::prelude::shape::Icosphere {
+                    radius: ball_physics.radius,
+                    subdivisions,
+                }))
+            })
+            .collect(),
+    ));
+}
+
+fn setup_lighting(mut commands: Commands, settings: Res<Settings>) {
+    if settings.lighting == LightingMode::Directional {
+        commands
+            .spawn_bundle(DirectionalLightBundle {
+                directional_light: DirectionalLight {
+                    shadows_enabled: settings.shadows,
+                    ..Default::default()
+                },
+                transform: Transform::from_xyz(0.0, 1.0, 0.5).looking_at(Vec3::ZERO, Vec3::Y),
+                ..Default::default()
+            })
+            .insert(GameLevel);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn add_ball_visuals(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    ball_mesh: Res<BallMesh>,
+    mut ball_materials: ResMut<BallMaterials>,
+    balls: Query<(Entity, &Ball), Added<Ball>>,
+    round: Res<RoundState>,
+    ball_physics: Res<BallPhysics>,
+    settings: Res<Settings>,
+) {
+    for (entity, ball) in balls.iter() {
+        // The ball may have been despawned in the same frame that it was added
+        if !round.ball_in_play(ball.player, entity) {
+            continue;
+        }
+        let ball_color = round.players[ball.player].color;
+        let ball_material = ball_materials.material(ball_color, &mut materials);
+        commands.entity(entity).with_children(|builder| {
+            let mut ball_visual = builder.spawn_bundle(PbrBundle {
+                mesh: ball_mesh.0[0].clone(),
+                material: ball_material,
+                ..Default::default()
+            });
+            ball_visual.insert(BallLod { level: 0 });
+            if settings.lighting == LightingMode::PerBall {
+                ball_visual.insert_bundle(PointLightBundle {
+                    point_light: PointLight {
+                        color: ball_color,
+                        intensity: 5000.0,
+                        range: 50.0,
+                        radius: ball_physics.radius,
+                        shadows_enabled: settings.shadows,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                });
+            }
+        });
+    }
+}
+
+fn add_ball_trails(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    balls: Query<(Entity, &Ball, &Transform), Added<Ball>>,
+    round: Res<RoundState>,
+) {
+    for (entity, ball, transform) in balls.iter() {
+        if !round.ball_in_play(ball.player, entity) {
+            continue;
+        }
+        let mut trail_color = round.players[ball.player].color;
+        trail_color.set_a(0.5);
+        commands
+            .spawn_bundle(PbrBundle {
+                mesh: meshes.add(ribbon_mesh(
+                    &[transform.translation, transform.translation],
+                    0.0,
+                )),
+                material: materials.add(StandardMaterial {
+                    base_color: trail_color,
+                    alpha_mode: AlphaMode::Blend,
+                    unlit: true,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+            .insert_bundle((Trail::new(entity), GameLevel));
+    }
+}
+
+#[derive(Component)]
+struct Leaderboard;
+
+#[derive(Component)]
+struct LeaderboardPlayer {
+    index: usize,
+}
+
+/// A row of the leaderboard, which is tinted with the color of the player shown in it.
+#[derive(Component)]
+struct LeaderboardRow {
+    index: usize,
+}
+
+/// Opacity of the tint of each leaderboard row.
+const ROW_TINT_ALPHA: f32 = 0.6;
+
+fn row_tint(color: Color) -> Color {
+    let [r, g, b, _] = color.as_rgba_f32();
+    Color::rgba(r, g, b, ROW_TINT_ALPHA)
+}
+
+/// Black or white, whichever is easier to read on a leaderboard row tinted with `color`.
+fn contrasting_text_color(color: Color) -> Color {
+    let [r, g, b, _] = color.as_rgba_f32();
+    // Perceived brightness of the tint, which mostly hides the dark panel behind it
+    let luminance = 0.299 * r + 0.587 * g + 0.114 * b;
+    if ROW_TINT_ALPHA * luminance > 0.3 {
+        Color::BLACK
+    } else {
+        Color::WHITE
+    }
+}
+
+#[derive(Component)]
+struct LeaderboardPlayerName {
+    index: usize,
+}
+
+#[derive(Component)]
+struct LeaderboardPlayerSpeed {
+    index: usize,
+}
+
+/// Whether the leaderboard shows the speed of each ball in play.
+const SHOW_BALL_SPEED: bool = true;
+const LEADERBOARD_WIDTH: f32 = if SHOW_BALL_SPEED { 280.0 } else { 200.0 };
+
+/// Text showing how long the race has been running.
+#[derive(Component)]
+struct RaceTimerText;
+
+/// Formats `secs` as minutes, seconds and milliseconds, like 01:23.456.
+fn format_race_time(secs: f32) -> String {
+    let millis = (secs.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}.{:03}",
+        millis / 60_000,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+/// Updates the race timer. It only runs while playing, so it stops when the race ends.
+fn update_race_timer(
+    round: Res<RoundState>,
+    clock: Res<RaceClock>,
+    mut texts: Query<&mut Text, With<RaceTimerText>>,
+) {
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = format_race_time(clock.elapsed - round.start_secs);
+    }
+}
+
+#[derive(Component)]
+struct CountdownOverlay;
+
+#[derive(Component)]
+struct CountdownText;
+
+fn update_countdown(
+    mut commands: Commands,
+    countdown: Res<CountdownTimer>,
+    overlays: Query<Entity, With<CountdownOverlay>>,
+    mut texts: Query<&mut Text, With<CountdownText>>,
+) {
+    if countdown.0.finished() {
+        for entity in overlays.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    }
+    let remaining = countdown.0.duration().saturating_sub(countdown.0.elapsed());
+    for mut text in texts.iter_mut() {
+        text.sections[0].value = format!("{}", remaining.as_secs_f32().ceil() as u32);
+    }
+}
+
+fn setup_live_scoreboard(
+    mut commands: Commands,
+    font_handle: Res<FontHandle>,
+    race_config: Res<RaceConfig>,
+) {
+    // ui camera
+    commands.spawn_bundle(UiCameraBundle::default());
+
+    // countdown
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .insert(CountdownOverlay)
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        "",
+                        TextStyle {
+                            font: font_handle.handle.clone(),
+                            font_size: 120.0,
+                            color: Color::rgb(0.9, 0.9, 0.9),
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                })
+                .insert_bundle((CountdownText, ScaledFontSize(120.0)));
+        });
+
+    // root node
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                justify_content: JustifyContent::SpaceBetween,
+                ..Default::default()
+            },
+            color: Color::NONE.into(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            // right vertical fill
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::ColumnReverse,
+                        justify_content: JustifyContent::Center,
+                        size: Size::new(Val::Px(LEADERBOARD_WIDTH), Val::Percent(100.0)),
+                        ..Default::default()
+                    },
+                    color: Color::rgba(0.5, 0.5, 0.5, 0.15).into(),
+                    ..Default::default()
+                })
+                .insert(ScaledSize {
+                    width: Some(LEADERBOARD_WIDTH),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    // Title
+                    parent
+                        .spawn_bundle(TextBundle {
+                            style: Style {
+                                margin: Rect {
+                                    left: Val::Auto,
+                                    right: Val::Auto,
+                                    ..Default::default()
+                                },
+                                ..Default::default()
+                            },
+                            text: Text::with_section(
+                                "Leaderboard",
+                                TextStyle {
+                                    font: font_handle.handle.clone(),
+                                    font_size: 25.,
+                                    color: Color::WHITE,
+                                },
+                                Default::default(),
+                            ),
+                            ..Default::default()
+                        })
+                        .insert(ScaledFontSize(25.0));
+                    // Race timer
+                    parent
+                        .spawn_bundle(TextBundle {
+                            style: Style {
+                                margin: Rect {
+                                    left: Val::Auto,
+                                    right: Val::Auto,
+                                    ..Default::default()
+                                },
+                                ..Default::default()
+                            },
+                            text: Text::with_section(
+                                format_race_time(0.0),
+                                TextStyle {
+                                    font: font_handle.handle.clone(),
+                                    font_size: 20.,
+                                    color: Color::GRAY,
+                                },
+                                Default::default(),
+                            ),
+                            ..Default::default()
+                        })
+                        .insert_bundle((RaceTimerText, ScaledFontSize(20.0)));
+                    // List with hidden overflow
+                    parent
+                        .spawn_bundle(NodeBundle {
+                            style: Style {
+                                flex_direction: FlexDirection::ColumnReverse,
+                                align_self: AlignSelf::Center,
+                                size: Size::new(Val::Percent(100.0), Val::Percent(50.0)),
+                                overflow: Overflow::Hidden,
+                                ..Default::default()
+                            },
+                            color: Color::rgba(0.75, 0.75, 0.75, 0.10).into(),
+                            ..Default::default()
+                        })
+                        .with_children(|parent| {
+                            // Moving panel
+                            parent
+                                .spawn_bundle(NodeBundle {
+                                    style: Style {
+                                        flex_direction: FlexDirection::ColumnReverse,
+                                        flex_grow: 1.0,
+                                        max_size: Size::new(Val::Undefined, Val::Undefined),
+                                        ..Default::default()
+                                    },
+                                    color: Color::NONE.into(),
+                                    ..Default::default()
+                                })
+                                .insert(Leaderboard)
+                                .with_children(|parent| {
+                                    // List items
+                                    for (i, ball_info) in race_config
+                                        .balls
+                                        .iter()
+                                        .take(race_config.n_players)
+                                        .enumerate()
+                                    {
+                                        parent
+                                            .spawn_bundle(NodeBundle {
+                                                style: Style {
+                                                    justify_content: JustifyContent::FlexEnd,
+                                                    size: Size::new(
+                                                        Val::Px(LEADERBOARD_WIDTH),
+                                                        Val::Percent(100.0),
+                                                    ),
+                                                    flex_direction: FlexDirection::Row,
+                                                    ..Default::default()
+                                                },
+                                                color: row_tint(ball_info.color).into(),
+                                                ..Default::default()
+                                            })
+                                            .insert_bundle((
+                                                LeaderboardRow { index: i },
+                                                ScaledSize {
+                                                    width: Some(LEADERBOARD_WIDTH),
+                                                    ..Default::default()
+                                                },
+                                            ))
+                                            .with_children(|parent| {
+                                                parent
+                                                    .spawn_bundle(TextBundle {
+                                                        style: Style {
+                                                            flex_shrink: 0.,
+                                                            margin: Rect {
+                                                                left: Val::Px(10.),
+                                                                right: Val::Auto,
+                                                                ..Default::default()
+                                                            },
+                                                            ..Default::default()
+                                                        },
+                                                        text: Text::with_section(
+                                                            ball_info.name.to_string(),
+                                                            TextStyle {
+                                                                font: font_handle.handle.clone(),
+                                                                font_size: 20.,
+                                                                color: contrasting_text_color(
+                                                                    ball_info.color,
+                                                                ),
+                                                            },
+                                                            Default::default(),
+                                                        ),
+                                                        ..Default::default()
+                                                    })
+                                                    .insert_bundle((
+                                                        LeaderboardPlayerName { index: i },
+                                                        ScaledFontSize(20.0),
+                                                    ));
+                                                if SHOW_BALL_SPEED {
+                                                    parent
+                                                        .spawn_bundle(TextBundle {
+                                                            style: Style {
+                                                                flex_shrink: 0.,
+                                                                margin: Rect {
+                                                                    right: Val::Px(10.),
+                                                                    left: Val::Auto,
+                                                                    ..Default::default()
+                                                                },
+                                                                ..Default::default()
+                                                            },
+                                                            text: Text::with_section(
+                                                                "",
+                                                                TextStyle {
+                                                                    font: font_handle
+                                                                        .handle
+                                                                        .clone(),
+                                                                    font_size: 20.,
+                                                                    color: Color::GRAY,
+                                                                },
+                                                                Default::default(),
+                                                            ),
+                                                            ..Default::default()
+                                                        })
+                                                        .insert_bundle((
+                                                            LeaderboardPlayerSpeed { index: i },
+                                                            ScaledFontSize(20.0),
+                                                        ));
+                                                }
+                                                parent
+                                                    .spawn_bundle(TextBundle {
+                                                        style: Style {
+                                                            flex_shrink: 0.,
+                                                            margin: Rect {
+                                                                right: Val::Px(10.),
+                                                                left: Val::Auto,
+                                                                ..Default::default()
+                                                            },
+                                                            ..Default::default()
+                                                        },
+                                                        text: Text::with_section(
+                                                            ball_info.name.to_string(),
+                                                            TextStyle {
+                                                                font: font_handle.handle.clone(),
+                                                                font_size: 20.,
+                                                                color: contrasting_text_color(
+                                                                    ball_info.color,
+                                                                ),
+                                                            },
+                                                            Default::default(),
+                                                        ),
+                                                        ..Default::default()
+                                                    })
+                                                    .insert_bundle((
+                                                        LeaderboardPlayer { index: i },
+                                                        ScaledFontSize(20.0),
+                                                    ));
+                                            });
+                                    }
+                                });
+                        });
+                });
+        });
+}
+
+const MINIMAP_SIZE: f32 = 150.0;
+const MINIMAP_TRACK_DOT_SIZE: f32 = 3.0;
+/// Number of dots drawn along each segment of the track on the minimap.
+const MINIMAP_DOTS_PER_SEGMENT: usize = 4;
+const MINIMAP_MARKER_SIZE: f32 = 8.0;
+
+/// The part of the minimap onto which the track is drawn, under the ball markers.
+#[derive(Component)]
+struct MinimapTrack;
+
+#[derive(Component)]
+struct MinimapMarker {
+    index: usize,
+}
+
+/// Style for a square of `size` pixels centered on `point` of the minimap.
+fn minimap_point_style(point: Vec2, size: f32) -> Style {
+    Style {
+        position_type: PositionType::Absolute,
+        position: Rect {
+            left: Val::Percent(100.0 * point.x),
+            bottom: Val::Percent(100.0 * point.y),
+            ..Default::default()
+        },
+        margin: Rect {
+            left: Val::Px(-0.5 * size),
+            bottom: Val::Px(-0.5 * size),
+            ..Default::default()
+        },
+        size: Size::new(Val::Px(size), Val::Px(size)),
+        ..Default::default()
+    }
+}
+
+fn setup_minimap(mut commands: Commands, race_config: Res<RaceConfig>) {
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Px(10.0),
+                    bottom: Val::Px(10.0),
+                    ..Default::default()
+                },
+                size: Size::new(Val::Px(MINIMAP_SIZE), Val::Px(MINIMAP_SIZE)),
+                ..Default::default()
+            },
+            color: Color::rgba(0.5, 0.5, 0.5, 0.15).into(),
+            ..Default::default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                        ..Default::default()
+                    },
+                    color: Color::NONE.into(),
+                    ..Default::default()
+                })
+                .insert(MinimapTrack);
+            for (index, ball_info) in race_config
+                .balls
+                .iter()
+                .take(race_config.n_players)
+                .enumerate()
+            {
+                parent
+                    .spawn_bundle(NodeBundle {
+                        style: minimap_point_style(Vec2::ZERO, MINIMAP_MARKER_SIZE),
+                        color: ball_info.color.into(),
+                        visibility: Visibility { is_visible: false },
+                        ..Default::default()
+                    })
+                    .insert(MinimapMarker { index });
+            }
+        });
+}
+
+/// Draws the track onto the minimap once the track has been spawned.
+fn draw_track_preview(
+    mut commands: Commands,
+    track_preview: Option<Res<TrackPreview>>,
+    minimap_tracks: Query<Entity, With<MinimapTrack>>,
+) {
+    let track_preview = match track_preview {
+        Some(track_preview) if track_preview.is_changed() => track_preview,
+        _ => return,
+    };
+    for entity in minimap_tracks.iter() {
+        commands.entity(entity).with_children(|parent| {
+            for points in track_preview.points.windows(2) {
+                for i in 0..MINIMAP_DOTS_PER_SEGMENT {
+                    let t = i as f32 / MINIMAP_DOTS_PER_SEGMENT as f32;
+                    parent.spawn_bundle(NodeBundle {
+                        style: minimap_point_style(
+                            points[0].lerp(points[1], t),
+                            MINIMAP_TRACK_DOT_SIZE,
+                        ),
+                        color: Color::SILVER.into(),
+                        ..Default::default()
+                    });
+                }
+            }
+        });
+    }
+}
+
+/// Moves the markers on the minimap to the positions of the balls in play, hiding those of players
+/// who have no ball.
+fn update_minimap(
+    mut markers: Query<(&MinimapMarker, &mut Style, &mut Visibility)>,
+    balls: Query<&GlobalTransform, With<Ball>>,
+    round: Res<RoundState>,
+    track_preview: Option<Res<TrackPreview>>,
+) {
+    let track_preview = match track_preview {
+        Some(track_preview) => track_preview,
+        None => return,
+    };
+    for (marker, mut style, mut visibility) in markers.iter_mut() {
+        let transform = round
+            .players
+            .get(marker.index)
+            .and_then(|player| player.entity)
+            .and_then(|entity| balls.get(entity).ok());
+        visibility.is_visible = transform.is_some();
+        if let Some(transform) = transform {
+            *style = minimap_point_style(
+                track_preview.project(transform.translation),
+                MINIMAP_MARKER_SIZE,
+            );
+        }
+    }
+}
+
+/// The name of a player, floating above their ball.
+#[derive(Component)]
+struct BallLabel {
+    index: usize,
+}
+
+const BALL_LABEL_WIDTH: f32 = 160.0;
+const BALL_LABEL_FONT_SIZE: f32 = 18.0;
+
+fn setup_ball_labels(
+    mut commands: Commands,
+    font_handle: Res<FontHandle>,
+    race_config: Res<RaceConfig>,
+) {
+    for (index, ball_info) in race_config
+        .balls
+        .iter()
+        .take(race_config.n_players)
+        .enumerate()
+    {
+        commands
+            .spawn_bundle(TextBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    size: Size::new(Val::Px(BALL_LABEL_WIDTH), Val::Undefined),
+                    // Centered over the ball
+                    margin: Rect {
+                        left: Val::Px(-0.5 * BALL_LABEL_WIDTH),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                text: Text::with_section(
+                    ball_info.name.to_string(),
+                    TextStyle {
+                        font: font_handle.handle.clone(),
+                        font_size: BALL_LABEL_FONT_SIZE,
+                        color: ball_info.color,
+                    },
+                    TextAlignment {
+                        vertical: VerticalAlign::Bottom,
+                        horizontal: HorizontalAlign::Center,
+                    },
+                ),
+                visibility: Visibility { is_visible: false },
+                ..Default::default()
+            })
+            .insert_bundle((
+                BallLabel { index },
+                ScaledSize {
+                    width: Some(BALL_LABEL_WIDTH),
+                    height: None,
+                },
+                ScaledFontSize(BALL_LABEL_FONT_SIZE),
+            ));
+    }
+}
+
+/// Moves the name labels to just above the balls in play on screen, hiding those of players who
+/// have no ball, or whose ball is out of view.
+fn update_ball_labels(
+    mut labels: Query<(&BallLabel, &mut Style, &mut Visibility)>,
+    balls: Query<&GlobalTransform, With<Ball>>,
+    cameras: Query<(&Camera, &GlobalTransform), With<FpsCameraController>>,
+    round: Res<RoundState>,
+    ball_physics: Res<BallPhysics>,
+    ui_scale: Res<UiScale>,
+) {
+    let (camera, camera_transform) = match cameras.get_single() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    for (label, mut style, mut visibility) in labels.iter_mut() {
+        let point = round
+            .players
+            .get(label.index)
+            .and_then(|player| player.entity)
+            .and_then(|entity| balls.get(entity).ok())
+            .and_then(|transform| {
+                let above = transform.translation + 2.0 * ball_physics.radius * Vec3::Y;
+                world_to_viewport(camera, camera_transform, above)
+            });
+        visibility.is_visible = point.is_some();
+        if let Some(point) = point {
+            style.position = Rect {
+                left: Val::Percent(100.0 * point.x),
+                bottom: Val::Percent(100.0 * point.y),
+                ..Default::default()
+            };
+            style.margin.left = Val::Px(-0.5 * ui_scale.0 * BALL_LABEL_WIDTH);
+        }
+    }
+}
+
+#[allow(clippy::type_complexity, clippy::too_many_arguments)]
+fn update_leaderboard(
+    mut names: Query<
+        (&LeaderboardPlayerName, &mut Text),
+        (Without<LeaderboardPlayer>, Without<LeaderboardPlayerSpeed>),
+    >,
+    mut distances: Query<
+        (&LeaderboardPlayer, &mut Text),
+        (
+            Without<LeaderboardPlayerName>,
+            Without<LeaderboardPlayerSpeed>,
+        ),
+    >,
+    mut speeds: Query<
+        (&LeaderboardPlayerSpeed, &mut Text),
+        (Without<LeaderboardPlayer>, Without<LeaderboardPlayerName>),
+    >,
+    mut row_colors: Query<(&LeaderboardRow, &mut UiColor)>,
+    velocities: Query<&RigidBodyVelocityComponent>,
+    round: Res<RoundState>,
+    track_info: Option<Res<TrackInfo>>,
+    race_config: Res<RaceConfig>,
+    tournament: Option<Res<Tournament>>,
+    settings: Res<Settings>,
+) {
+    let player_order = rank_players(&round);
+    let rows = settings.leaderboard.rows(&player_order);
+    for (row, mut color) in row_colors.iter_mut() {
+        *color = row_tint(round.players[rows[row.index]].color).into();
+    }
+    for (player, mut text) in distances.iter_mut() {
+        let list_index = player.index;
+        let player_index = rows[list_index];
+        text.sections[0].value =
+            round.standing_text(player_index, race_config.target_laps, track_info.as_deref());
+        text.sections[0].style.color = contrasting_text_color(round.players[player_index].color);
+    }
+    for (player, mut text) in names.iter_mut() {
+        let list_index = player.index;
+        let player_index = rows[list_index];
+        let name = &round.players[player_index].name;
+        // In a series, players are shown with the points they have scored in previous rounds
+        let name = match tournament
+            .as_ref()
+            .and_then(|tournament| tournament.points.get(player_index))
+        {
+            Some(points) => format!("{} {}", name, points),
+            None => name.to_string(),
+        };
+        // Rows that do not move with rank show it instead
+        text.sections[0].value = match settings.leaderboard {
+            LeaderboardStyle::Sorted => name,
+            LeaderboardStyle::Sticky => {
+                let rank = player_order
+                    .iter()
+                    .position(|&index| index == player_index)
+                    .unwrap_or(list_index);
+                format!("{}. {}", rank + 1, name)
+            }
+        };
+        text.sections[0].style.color = contrasting_text_color(round.players[player_index].color);
+    }
+    for (player, mut text) in speeds.iter_mut() {
+        let player = &round.players[rows[player.index]];
+        // Balls despawned this frame, and those of players who have finished or dropped out, have
+        // no speed to show
+        let velocity = player
+            .entity
+            .filter(|_| player.end_secs.is_none())
+            .and_then(|entity| velocities.get(entity).ok());
+        text.sections[0].value = format_speed(velocity.map(|velocity| velocity.linvel.norm()));
+    }
+}
+
+/// Formats a ball's `speed` for the leaderboard, which is blank for balls that have none.
+fn format_speed(speed: Option<f32>) -> String {
+    speed.map_or_else(String::new, |speed| format!("{:4.1}m/s", speed))
+}
+
+const FOLLOW_TRANSITION_DURATION: Duration = Duration::from_millis(300);
+
+/// Radians of orbit per pixel of mouse movement.
+const ORBIT_SENSITIVITY: f32 = 0.005;
+/// Fraction by which each line of scrolling moves the camera towards or away from the ball.
+const ORBIT_ZOOM_STEP: f32 = 0.1;
+const MIN_ORBIT_DISTANCE: f32 = 5.0;
+const MAX_ORBIT_DISTANCE: f32 = 500.0;
+/// Pitch is kept short of straight up or down, where the camera would flip over.
+const MAX_ORBIT_PITCH: f32 = std::f32::consts::FRAC_PI_2 - 0.01;
+
+struct FollowMode {
+    following: bool,
+    index: usize,
+    target: Option<Entity>,
+    /// The look transform when the followed ball last changed, and when that happened.
+    transition: Option<(LookTransform, Instant)>,
+    /// Whether the camera orbits the ball at `yaw`, `pitch` and `distance`. Until the mouse is
+    /// used to orbit or zoom, the camera follows from behind and above the ball's direction of
+    /// travel.
+    orbiting: bool,
+    /// Angle of the camera around the ball from +z, towards +x.
+    yaw: f32,
+    /// Angle of the camera above the ball.
+    pitch: f32,
+    distance: f32,
+    /// The velocity of the followed ball in the last frame, from which impacts are detected.
+    last_velocity: Option<Vec3>,
+    /// The initial magnitude of the camera shake from the followed ball's last impact, and when
+    /// the impact happened.
+    shake: Option<(f32, Instant)>,
+    /// Whether the camera frames all the balls in play, in place of following one.
+    directing: bool,
+}
+
+impl Default for FollowMode {
+    fn default() -> Self {
+        Self {
+            following: true,
+            index: 0,
+            target: None,
+            transition: None,
+            orbiting: false,
+            yaw: 0.0,
+            pitch: 0.0,
+            distance: 0.0,
+            last_velocity: None,
+            shake: None,
+            directing: false,
+        }
+    }
+}
+
+/// The look transform `elapsed` into an eased transition from `from` to `to`, or `None` once the
+/// transition has finished.
+fn transition_look(
+    from: &LookTransform,
+    to: &LookTransform,
+    elapsed: Duration,
+) -> Option<LookTransform> {
+    let t = elapsed.as_secs_f32() / FOLLOW_TRANSITION_DURATION.as_secs_f32();
+    if t >= 1.0 {
+        return None;
+    }
+    // Smoothstep easing
+    let t = t * t * (3.0 - 2.0 * t);
+    Some(LookTransform {
+        eye: from.eye.lerp(to.eye, t),
+        target: from.target.lerp(to.target, t),
+    })
+}
+
+const SHAKE_DURATION: Duration = Duration::from_millis(200);
+/// Smallest change in the followed ball's speed in one frame that shakes the camera.
+const SHAKE_MIN_IMPACT: f32 = 5.0;
+/// Shake magnitude per unit of the square of the change in velocity, which is proportional to the
+/// energy of the impact.
+const SHAKE_PER_IMPACT_ENERGY: f32 = 0.05;
+const MAX_SHAKE: f32 = 10.0;
+
+/// Initial magnitude of the camera shake from an `impact` of that change in velocity, or `None` if it
+/// is too small to shake the camera.
+fn impact_shake(impact: f32) -> Option<f32> {
+    (impact >= SHAKE_MIN_IMPACT).then(|| (SHAKE_PER_IMPACT_ENERGY * impact * impact).min(MAX_SHAKE))
+}
+
+/// Magnitude of a camera shake that started at `initial` magnitude, `elapsed` since the impact. It
+/// decays linearly to zero over `SHAKE_DURATION`.
+fn shake_magnitude(initial: f32, elapsed: Duration) -> f32 {
+    initial * (1.0 - elapsed.as_secs_f32() / SHAKE_DURATION.as_secs_f32()).max(0.0)
+}
+
+/// Offset of the camera from the ball it orbits at `yaw`, `pitch` and `distance`.
+fn orbit_offset(yaw: f32, pitch: f32, distance: f32) -> Vec3 {
+    distance
+        * Vec3::new(
+            pitch.cos() * yaw.sin(),
+            pitch.sin(),
+            pitch.cos() * yaw.cos(),
+        )
+}
+
+/// Orbits the camera around the followed ball while the right mouse button is dragged, and zooms
+/// in and out on scrolling.
+fn orbit_input(
+    mut mouse_motion: EventReader<MouseMotion>,
+    mut mouse_wheel: EventReader<MouseWheel>,
+    mouse_input: Res<Input<MouseButton>>,
+    mut follow_mode: ResMut<FollowMode>,
+    cameras: Query<&LookTransform, With<FpsCameraController>>,
+) {
+    let drag = if mouse_input.pressed(MouseButton::Right) {
+        mouse_motion.iter().map(|motion| &motion.delta).sum()
+    } else {
+        Vec2::ZERO
+    };
+    let scroll = mouse_wheel.iter().map(|wheel| wheel.y).sum::<f32>();
+    if !follow_mode.following || (drag == Vec2::ZERO && scroll == 0.0) {
+        return;
+    }
+    if !follow_mode.orbiting {
+        match cameras.get_single() {
+            Ok(look_transform) => start_orbiting(&mut follow_mode, look_transform),
+            Err(_) => return,
+        }
+    }
+    follow_mode.yaw -= ORBIT_SENSITIVITY * drag.x;
+    follow_mode.pitch =
+        (follow_mode.pitch + ORBIT_SENSITIVITY * drag.y).clamp(-MAX_ORBIT_PITCH, MAX_ORBIT_PITCH);
+    follow_mode.distance = (follow_mode.distance * (1.0 - ORBIT_ZOOM_STEP).powf(scroll))
+        .clamp(MIN_ORBIT_DISTANCE, MAX_ORBIT_DISTANCE);
 }
 
-const BOUNDS: Vec3 = const_vec3!([0.0, -1000.0, f32::MIN]);
-const BOUNDS_MARGIN: Vec3 = const_vec3!([0.0, -SPAWN_RADIUS - 10.0, 0.0]);
+/// Starts orbiting from wherever the camera is, so that it does not jump.
+fn start_orbiting(follow_mode: &mut FollowMode, look_transform: &LookTransform) {
+    let offset = look_transform.eye - look_transform.target;
+    follow_mode.distance = offset
+        .length()
+        .clamp(MIN_ORBIT_DISTANCE, MAX_ORBIT_DISTANCE);
+    follow_mode.yaw = offset.x.atan2(offset.z);
+    follow_mode.pitch = (offset.y / offset.length().max(f32::EPSILON)).asin();
+    follow_mode.orbiting = true;
+}
 
-fn despawn_balls(
-    mut commands: Commands,
-    track: Query<&Aabb, With<Track>>,
-    balls: Query<&GlobalTransform, With<Ball>>,
-    mut bounds: Local<Option<Vec3>>,
-    mut round: ResMut<RoundState>,
-    mut state: ResMut<State<GameState>>,
+/// The gamepad that controls the camera. Only the first gamepad connected is used, until it is
+/// disconnected.
+#[derive(Default)]
+struct ActiveGamepad(Option<Gamepad>);
+
+fn track_gamepad(
+    mut gamepad_events: EventReader<GamepadEvent>,
+    mut active_gamepad: ResMut<ActiveGamepad>,
 ) {
-    *bounds = track
-        .iter()
-        .next()
-        .map_or(Some(BOUNDS), |aabb| Some(aabb.min() + BOUNDS_MARGIN));
-    let bounds = bounds.unwrap();
-    let now = Instant::now();
-    let round_start = round.start;
-    let mut finished_count = 0;
-    for player in round.players.iter_mut() {
-        if let Some(entity) = player.entity {
-            if let Ok(transform) = balls.get(entity) {
-                player.distance = transform.translation.z.max(bounds.z);
-                if transform.translation.y < bounds.y || transform.translation.z <= bounds.z {
-                    player.end = Some(now);
-                    let result = if transform.translation.z <= bounds.z {
-                        player.finished = true;
-                        "finished".to_string()
-                    } else {
-                        format!(
-                            "did not finish ({:2.1}% complete)",
-                            100.0 * player.distance / bounds.z
-                        )
-                    };
-                    info!(
-                        "{} {} in {:3.2}s ({:3.2}s)",
-                        player.name,
-                        result,
-                        (now - round_start).as_secs_f32(),
-                        (now - player.start).as_secs_f32()
-                    );
-                    commands.entity(entity).despawn_recursive();
-                    player.entity = None;
-                }
+    for GamepadEvent(gamepad, event_type) in gamepad_events.iter() {
+        match event_type {
+            GamepadEventType::Connected if active_gamepad.0.is_none() => {
+                info!("Using gamepad {:?}", gamepad);
+                active_gamepad.0 = Some(*gamepad);
+            }
+            GamepadEventType::Disconnected if active_gamepad.0 == Some(*gamepad) => {
+                active_gamepad.0 = None;
             }
+            _ => {}
+        }
+    }
+}
+
+/// Radians per second of orbit with the left stick fully tilted.
+const GAMEPAD_ORBIT_SPEED: f32 = 2.0;
+/// Mouse pixels per second that looking around with the right stick fully tilted is equivalent to.
+const GAMEPAD_LOOK_SPEED: f32 = 500.0;
+
+/// The position of `gamepad`'s stick from its x and y axes.
+fn stick(
+    axes: &Axis<GamepadAxis>,
+    gamepad: Gamepad,
+    x: GamepadAxisType,
+    y: GamepadAxisType,
+) -> Vec2 {
+    Vec2::new(
+        axes.get(GamepadAxis(gamepad, x)).unwrap_or(0.0),
+        axes.get(GamepadAxis(gamepad, y)).unwrap_or(0.0),
+    )
+}
+
+/// Orbits the follow camera around the followed ball with the gamepad's left stick, and looks
+/// around the free camera with its right stick.
+fn gamepad_camera_input(
+    time: Res<Time>,
+    axes: Res<Axis<GamepadAxis>>,
+    active_gamepad: Res<ActiveGamepad>,
+    mut follow_mode: ResMut<FollowMode>,
+    mut control_events: EventWriter<ControlEvent>,
+    cameras: Query<(&FpsCameraController, &LookTransform)>,
+) {
+    let gamepad = match active_gamepad.0 {
+        Some(gamepad) => gamepad,
+        None => return,
+    };
+    let (controller, look_transform) = match cameras.get_single() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    let dt = time.delta_seconds();
+    if follow_mode.following {
+        let orbit = stick(
+            &axes,
+            gamepad,
+            GamepadAxisType::LeftStickX,
+            GamepadAxisType::LeftStickY,
+        );
+        if orbit == Vec2::ZERO {
+            return;
+        }
+        if !follow_mode.orbiting {
+            start_orbiting(&mut follow_mode, look_transform);
         }
-        if player.end.is_some() {
-            finished_count += 1;
+        follow_mode.yaw -= GAMEPAD_ORBIT_SPEED * dt * orbit.x;
+        follow_mode.pitch = (follow_mode.pitch - GAMEPAD_ORBIT_SPEED * dt * orbit.y)
+            .clamp(-MAX_ORBIT_PITCH, MAX_ORBIT_PITCH);
+    } else if controller.enabled {
+        let look = stick(
+            &axes,
+            gamepad,
+            GamepadAxisType::RightStickX,
+            GamepadAxisType::RightStickY,
+        );
+        if look != Vec2::ZERO {
+            // Mouse motion is positive downwards, but the stick is positive upwards
+            control_events.send(ControlEvent::Rotate(
+                controller.mouse_rotate_sensitivity
+                    * GAMEPAD_LOOK_SPEED
+                    * dt
+                    * Vec2::new(look.x, -look.y),
+            ));
         }
     }
-    if finished_count >= N_PLAYERS {
-        state.set(GameState::GameOver).ok();
+}
+
+/// Key that toggles the director camera, which frames all the balls in play.
+const DIRECTOR_KEY: KeyCode = KeyCode::V;
+/// Radius of the smallest view that the director camera frames.
+const MIN_DIRECTOR_RADIUS: f32 = 5.0;
+/// Progress of the leader along the track after which the director camera starts to favor them,
+/// framing only the leader by the finish.
+const DIRECTOR_FAVOR_LEADER_PROGRESS: f32 = 0.8;
+
+/// The centroid of `positions`, and the radius of the sphere around it that contains them all.
+fn bounding_sphere(positions: &[Vec3]) -> Option<(Vec3, f32)> {
+    if positions.is_empty() {
+        return None;
     }
+    let centroid = positions.iter().sum::<Vec3>() / positions.len() as f32;
+    let radius = positions
+        .iter()
+        .map(|position| position.distance(centroid))
+        .fold(0.0, f32::max);
+    Some((centroid, radius))
 }
 
-fn despawn_level(mut commands: Commands, level_entities: Query<Entity, With<GameLevel>>) {
-    for entity in level_entities.iter() {
-        commands.entity(entity).despawn_recursive();
+/// The eye and target of a camera with a vertical field of view of `fov` radians that frames a
+/// sphere at `center` of `radius`, looking along `-view`.
+fn frame_sphere(center: Vec3, radius: f32, fov: f32, view: Vec3) -> (Vec3, Vec3) {
+    let distance = radius.max(MIN_DIRECTOR_RADIUS) / (0.5 * fov).sin();
+    (center + distance * view.normalize(), center)
+}
+
+/// Frames all the balls in play from above while directing, which V toggles. The view zooms out
+/// as the pack spreads, and closes in on the leader as they near the finish on their last lap.
+#[allow(clippy::too_many_arguments)]
+fn direct_camera(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut follow_mode: ResMut<FollowMode>,
+    balls: Query<&GlobalTransform, With<Ball>>,
+    mut cameras: Query<(
+        &mut FpsCameraController,
+        &mut LookTransform,
+        &mut Smoother,
+        &PerspectiveProjection,
+    )>,
+    round: Res<RoundState>,
+    race_config: Res<RaceConfig>,
+    track_info: Option<Res<TrackInfo>>,
+    cinematic: Option<Res<CinematicCamera>>,
+    photo_finish: Res<PhotoFinish>,
+) {
+    if cinematic.map_or(false, |cinematic| cinematic.playing) || photo_finish.active {
+        return;
+    }
+    let (mut controller, mut look_transform, mut smoother, projection) =
+        match cameras.get_single_mut() {
+            Ok(camera) => camera,
+            Err(_) => return,
+        };
+    if keyboard_input.just_pressed(DIRECTOR_KEY) {
+        follow_mode.directing = !follow_mode.directing;
+        follow_mode.following = true;
+        controller.enabled = false;
+        smoother.set_lag_weight(FOLLOW_LAG_WEIGHT);
+        info!(
+            "{}",
+            if follow_mode.directing {
+                "Directing"
+            } else {
+                "Following"
+            }
+        );
+    }
+    if !follow_mode.following || !follow_mode.directing {
+        return;
+    }
+    let live_balls = round
+        .players
+        .iter()
+        .filter(|player| player.end_secs.is_none())
+        .filter_map(|player| player.entity.and_then(|entity| balls.get(entity).ok()))
+        .map(|transform| transform.translation)
+        .collect::<Vec<_>>();
+    // If nobody is live, the camera holds its last position
+    let (mut center, mut radius) = match bounding_sphere(&live_balls) {
+        Some(sphere) => sphere,
+        None => return,
+    };
+    // In lap races, only once the leader is on their last lap
+    let leader = rank_players(&round)
+        .into_iter()
+        .map(|index| &round.players[index])
+        .find(|player| player.end_secs.is_none())
+        .filter(|player| {
+            race_config
+                .target_laps
+                .map_or(true, |target_laps| player.laps + 1 >= target_laps)
+        })
+        .and_then(|player| player.entity.and_then(|entity| balls.get(entity).ok()));
+    if let (Some(leader), Some(track_info)) = (leader, track_info) {
+        let progress = track_info.progress(leader.translation);
+        let t = ((progress - DIRECTOR_FAVOR_LEADER_PROGRESS)
+            / (1.0 - DIRECTOR_FAVOR_LEADER_PROGRESS))
+            .clamp(0.0, 1.0);
+        center = center.lerp(leader.translation, t);
+        radius *= 1.0 - t;
     }
+    // From above and behind the balls, which travel towards -z
+    let view = Vec3::new(0.0, 2.0, 1.0);
+    let (eye, target) = frame_sphere(center, radius, projection.fov, view);
+    look_transform.eye = eye;
+    look_transform.target = target;
 }
 
-fn despawn_all_balls(mut commands: Commands, mut round: ResMut<RoundState>) {
-    for player in round.players.iter_mut() {
-        if let Some(entity) = player.entity {
-            commands.entity(entity).despawn_recursive();
-            player.entity = None;
+/// Seconds between switches of the followed ball to the one chat has voted for most.
+const CHAT_VOTE_SECS: f32 = 5.0;
+
+/// Every few seconds, follows the ball in play that has had the most chat votes since the last
+/// switch. Votes for balls that have left the race are lost.
+fn follow_chat_votes(
+    mut votes: ResMut<ChatVotes>,
+    mut follow_mode: ResMut<FollowMode>,
+    clock: Res<RaceClock>,
+    round: Res<RoundState>,
+) {
+    let now = clock.elapsed;
+    // The clock starts again from zero every round
+    if now >= votes.since && now - votes.since < CHAT_VOTE_SECS {
+        return;
+    }
+    let in_play = |index: usize| {
+        round.players.get(index).map_or(false, |player| {
+            player.entity.is_some() && player.end_secs.is_none()
+        })
+    };
+    if let Some(index) = votes.most_voted(in_play) {
+        if index != follow_mode.index {
+            // follow_ball picks up the change of target, transitioning the camera to it
+            follow_mode.index = index;
+            info!("Now following: {}", round.players[index].name);
         }
     }
+    votes.reset(now);
 }
 
-struct FollowMode {
-    following: bool,
-    index: usize,
-    target: Option<Entity>,
+/// Frames the finish line from the side while there is a photo finish, taking over from following.
+/// The free camera is left alone.
+fn frame_photo_finish(
+    photo_finish: Res<PhotoFinish>,
+    follow_mode: Res<FollowMode>,
+    finish_lines: Query<&GlobalTransform, With<FinishLine>>,
+    mut cameras: Query<(&mut LookTransform, &mut Smoother), With<FpsCameraController>>,
+) {
+    if !follow_mode.following || !(photo_finish.active || photo_finish.is_changed()) {
+        return;
+    }
+    let (mut look_transform, mut smoother) = match cameras.get_single_mut() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    if !photo_finish.active {
+        // The photo finish is over, so follow_ball takes over again
+        smoother.set_lag_weight(FOLLOW_LAG_WEIGHT);
+        return;
+    }
+    let finish_line = match finish_lines.get_single() {
+        Ok(finish_line) => finish_line.translation,
+        Err(_) => return,
+    };
+    // Cut straight to the finish, rather than panning over the track to it. The camera is off to
+    // the side of the track and a little above it.
+    smoother.set_lag_weight(0.0);
+    look_transform.target = finish_line;
+    look_transform.eye = finish_line + Vec3::new(8.0, 2.0, 0.0);
 }
 
-impl Default for FollowMode {
-    fn default() -> Self {
-        Self {
-            following: true,
-            index: 0,
-            target: None,
+/// Whether the followed ball is cycled forward or backward this frame, if at all. Tab and the right
+/// bumper cycle forward, and Shift+Tab and the left bumper backward.
+fn cycle_direction(
+    keyboard_input: &Input<KeyCode>,
+    gamepad_buttons: &Input<GamepadButton>,
+    gamepad: Option<Gamepad>,
+) -> Option<bool> {
+    let gamepad_pressed = |button_type| {
+        gamepad.map_or(false, |gamepad| {
+            gamepad_buttons.just_pressed(GamepadButton(gamepad, button_type))
+        })
+    };
+    if keyboard_input.just_pressed(KeyCode::Tab) {
+        Some(!(keyboard_input.pressed(KeyCode::LShift) || keyboard_input.pressed(KeyCode::RShift)))
+    } else if gamepad_pressed(GamepadButtonType::RightTrigger) {
+        Some(true)
+    } else if gamepad_pressed(GamepadButtonType::LeftTrigger) {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Finds the next player after `index`, cycling forward or backward through the players, whose
+/// ball is currently in play.
+fn next_live_player(players: &[PlayerState], index: usize, forward: bool) -> Option<usize> {
+    let n_players = players.len();
+    (1..=n_players)
+        .map(|offset| {
+            if forward {
+                (index + offset) % n_players
+            } else {
+                (index + n_players - offset % n_players) % n_players
+            }
+        })
+        .find(|&i| players[i].entity.is_some())
+}
+
+/// Keys that follow the balls of the first ten players.
+const FOLLOW_KEYS: [KeyCode; 10] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+    KeyCode::Key0,
+];
+
+/// The lag weight of the camera smoother while following a ball.
+const FOLLOW_LAG_WEIGHT: f32 = 0.99;
+
+/// Toggles playing the cinematic camera path, if one was loaded, with C. While it plays, neither
+/// the follow camera nor the free camera move the camera.
+fn play_cinematic(
+    keyboard_input: Res<Input<KeyCode>>,
+    time: Res<Time>,
+    cinematic: Option<ResMut<CinematicCamera>>,
+    follow_mode: Res<FollowMode>,
+    mut cameras: Query<(&mut FpsCameraController, &mut LookTransform, &mut Smoother)>,
+) {
+    let mut cinematic = match cinematic {
+        Some(cinematic) => cinematic,
+        None => return,
+    };
+    let (mut controller, mut look_transform, mut smoother) = match cameras.get_single_mut() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    let mut stop = false;
+    if keyboard_input.just_pressed(KeyCode::C) {
+        if cinematic.playing {
+            stop = true;
+        } else {
+            cinematic.playing = true;
+            cinematic.elapsed = 0.0;
+            controller.enabled = false;
+            // The keyframes already describe smooth motion
+            smoother.set_lag_weight(0.0);
         }
     }
+    if cinematic.playing {
+        cinematic.elapsed += time.delta_seconds();
+        if let Some(sampled) = cinematic.sample(cinematic.elapsed) {
+            *look_transform = sampled;
+        }
+        stop |= cinematic.finished();
+    }
+    if stop {
+        // Hand the camera back to whichever of the follow and free cameras was in use
+        cinematic.playing = false;
+        controller.enabled = !follow_mode.following;
+        smoother.set_lag_weight(if follow_mode.following {
+            FOLLOW_LAG_WEIGHT
+        } else {
+            controller.smoothing_weight
+        });
+    }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn follow_ball(
     keyboard_input: Res<Input<KeyCode>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    active_gamepad: Res<ActiveGamepad>,
     mut follow_mode: ResMut<FollowMode>,
     balls: Query<(Entity, &GlobalTransform, &RigidBodyVelocityComponent), With<Ball>>,
     mut cameras: Query<(&mut FpsCameraController, &mut LookTransform, &mut Smoother)>,
     round: Res<RoundState>,
+    cinematic: Option<Res<CinematicCamera>>,
+    photo_finish: Res<PhotoFinish>,
 ) {
-    let (mut controller, mut look_transform, mut smoother) = cameras.single_mut();
-    if keyboard_input.just_pressed(KeyCode::F) {
+    if cinematic.map_or(false, |cinematic| cinematic.playing) || photo_finish.active {
+        return;
+    }
+    // There may briefly be no camera, or more than one, while changing state
+    let (mut controller, mut look_transform, mut smoother) = match cameras.get_single_mut() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    let gamepad_pressed = |button_type| {
+        active_gamepad.0.map_or(false, |gamepad| {
+            gamepad_buttons.just_pressed(GamepadButton(gamepad, button_type))
+        })
+    };
+    if keyboard_input.just_pressed(KeyCode::F) || gamepad_pressed(GamepadButtonType::South) {
         follow_mode.following = !follow_mode.following;
         controller.enabled = !follow_mode.following;
         smoother.set_lag_weight(if follow_mode.following {
-            0.99
+            FOLLOW_LAG_WEIGHT
         } else {
             controller.smoothing_weight
         });
     }
-    if !follow_mode.following {
+    if !follow_mode.following || follow_mode.directing {
         return;
     }
-    let mut updated = false;
-    if keyboard_input.just_pressed(KeyCode::Key1) {
+    // The number of players may have changed since the followed player was chosen
+    if follow_mode.index >= round.players.len() {
         follow_mode.index = 0;
-        updated = true;
-    } else if keyboard_input.just_pressed(KeyCode::Key2) {
-        follow_mode.index = 1;
-        updated = true;
-    } else if keyboard_input.just_pressed(KeyCode::Key3) {
-        follow_mode.index = 2;
-        updated = true;
-    } else if keyboard_input.just_pressed(KeyCode::Key4) {
-        follow_mode.index = 3;
-        updated = true;
-    } else if keyboard_input.just_pressed(KeyCode::Key5) {
-        follow_mode.index = 4;
-        updated = true;
-    } else if keyboard_input.just_pressed(KeyCode::Key6) {
-        follow_mode.index = 5;
-        updated = true;
-    } else if keyboard_input.just_pressed(KeyCode::Key7) {
-        follow_mode.index = 6;
-        updated = true;
-    } else if keyboard_input.just_pressed(KeyCode::Key8) {
-        follow_mode.index = 7;
-        updated = true;
-    } else if keyboard_input.just_pressed(KeyCode::Key9) {
-        follow_mode.index = 8;
-        updated = true;
-    } else if keyboard_input.just_pressed(KeyCode::Key0) {
-        follow_mode.index = 9;
-        updated = true;
-    }
-    follow_mode.target = round.players[follow_mode.index].entity;
-    if updated {
-        info!("Now following: {}", round.players[follow_mode.index].name);
+        follow_mode.target = None;
+    }
+    // The followed ball has finished or dropped out since the last frame
+    let target_lost = follow_mode.target.is_some()
+        && round.players.get(follow_mode.index).and_then(|p| p.entity) != follow_mode.target;
+    let mut updated = false;
+    if let Some(forward) = cycle_direction(&keyboard_input, &gamepad_buttons, active_gamepad.0) {
+        if let Some(index) = next_live_player(&round.players, follow_mode.index, forward) {
+            follow_mode.index = index;
+            updated = true;
+        }
+    } else if let Some(index) = FOLLOW_KEYS
+        .iter()
+        .position(|&key| keyboard_input.just_pressed(key))
+    {
+        // With fewer players than number keys, some keys select nobody
+        if index < round.players.len() {
+            follow_mode.index = index;
+            updated = true;
+        }
+    }
+    if !updated && target_lost {
+        if let Some(index) = next_live_player(&round.players, follow_mode.index, true) {
+            follow_mode.index = index;
+            updated = true;
+        }
+    }
+    // If nobody is live, the camera holds its last position
+    if let Some(player) = round.players.get(follow_mode.index) {
+        if player.entity.is_some() || updated {
+            if follow_mode.target.is_some() && player.entity != follow_mode.target {
+                follow_mode.transition = Some((
+                    LookTransform {
+                        eye: look_transform.eye,
+                        target: look_transform.target,
+                    },
+                    Instant::now(),
+                ));
+            }
+            if player.entity != follow_mode.target {
+                // The new ball's velocity is not an impact of the old one
+                follow_mode.last_velocity = None;
+                follow_mode.shake = None;
+            }
+            follow_mode.target = player.entity;
+        }
+        if updated {
+            info!("Now following: {}", player.name);
+        }
     }
     if let Some(ball) = follow_mode.target {
         if let Ok((_, transform, velocity)) = balls.get(ball) {
-            let linvel = Vec3::from_slice(velocity.linvel.as_slice()).normalize_or_zero();
-            let right = linvel.cross(Vec3::Y);
-            let up = right.cross(linvel);
-            let offset = 100.0 * ((up - linvel) + 0.02 * Vec3::ONE);
-            look_transform.target = transform.translation;
-            look_transform.eye = transform.translation + offset;
+            let offset = if follow_mode.orbiting {
+                orbit_offset(follow_mode.yaw, follow_mode.pitch, follow_mode.distance)
+            } else {
+                let linvel = Vec3::from_slice(velocity.linvel.as_slice()).normalize_or_zero();
+                let right = linvel.cross(Vec3::Y);
+                let up = right.cross(linvel);
+                100.0 * ((up - linvel) + 0.02 * Vec3::ONE)
+            };
+            let mut look = LookTransform {
+                eye: transform.translation + offset,
+                target: transform.translation,
+            };
+            if let Some((from, start)) = &follow_mode.transition {
+                match transition_look(from, &look, start.elapsed()) {
+                    Some(transitioning) => look = transitioning,
+                    None => follow_mode.transition = None,
+                }
+            }
+            // A large change in velocity in one frame is an impact
+            let velocity = Vec3::from_slice(velocity.linvel.as_slice());
+            if let Some(last_velocity) = follow_mode.last_velocity {
+                if let Some(magnitude) = impact_shake((velocity - last_velocity).length()) {
+                    follow_mode.shake = Some((magnitude, Instant::now()));
+                }
+            }
+            follow_mode.last_velocity = Some(velocity);
+            // The shake is an offset on top of the computed eye, so it leaves nothing behind once
+            // it has decayed
+            if let Some((initial, start)) = follow_mode.shake {
+                let elapsed = start.elapsed();
+                if elapsed < SHAKE_DURATION {
+                    let t = 60.0 * elapsed.as_secs_f32();
+                    let direction = Vec3::new((1.3 * t).sin(), (1.7 * t).sin(), (1.1 * t).sin());
+                    look.eye += shake_magnitude(initial, elapsed) * direction;
+                } else {
+                    follow_mode.shake = None;
+                }
+            }
+            look_transform.target = look.target;
+            look_transform.eye = look.eye;
+        }
+    }
+}
+
+/// Where `world` appears in the view of `camera`, as a fraction of the window from its bottom left
+/// corner, or `None` if it is out of view, including if it is behind the camera.
+fn world_to_viewport(
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+    world: Vec3,
+) -> Option<Vec2> {
+    let world_to_clip = camera.projection_matrix * camera_transform.compute_matrix().inverse();
+    let clip = world_to_clip * world.extend(1.0);
+    // Points behind the camera would otherwise be projected through it, upside down
+    if clip.w <= 0.0 {
+        return None;
+    }
+    let ndc = clip.truncate() / clip.w;
+    if ndc.x.abs() > 1.0 || ndc.y.abs() > 1.0 {
+        return None;
+    }
+    Some(0.5 * (ndc.truncate() + Vec2::ONE))
+}
+
+/// The ray from `camera` through the point `cursor`, in window coordinates, of a window of size
+/// `window_size`.
+fn cursor_ray(
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+    window_size: Vec2,
+    cursor: Vec2,
+) -> Option<Ray> {
+    let ndc = 2.0 * cursor / window_size - Vec2::ONE;
+    let ndc_to_world = camera_transform.compute_matrix() * camera.projection_matrix.inverse();
+    // Depth is reversed, so the near plane is at 1 and depths towards 0 are further away
+    let near = ndc_to_world.project_point3(ndc.extend(1.0));
+    let far = ndc_to_world.project_point3(ndc.extend(0.5));
+    let direction = (far - near).try_normalize()?;
+    Some(Ray::new(
+        Point3::new(near.x, near.y, near.z),
+        Vector3::new(direction.x, direction.y, direction.z),
+    ))
+}
+
+/// The first ball entity hit by `ray`, of those for which `is_ball_collider` is true of one of its
+/// colliders.
+fn ball_hit_by_ray(
+    query_pipeline: &QueryPipeline,
+    colliders: &QueryPipelineColliderComponentsQuery,
+    ray: &Ray,
+    is_ball_collider: impl Fn(Entity) -> bool,
+) -> Option<Entity> {
+    let collider_set = QueryPipelineColliderComponentsSet(colliders);
+    let filter = |handle: ColliderHandle| is_ball_collider(handle.entity());
+    query_pipeline
+        .cast_ray(
+            &collider_set,
+            ray,
+            f32::MAX,
+            true,
+            InteractionGroups::all(),
+            Some(&filter),
+        )
+        .map(|(handle, _)| handle.entity())
+}
+
+/// Follows the ball under the cursor when the left mouse button is clicked. Clicks that miss every
+/// ball, and clicks while flying freely, are ignored.
+#[allow(clippy::too_many_arguments)]
+fn select_clicked_ball(
+    mouse_input: Res<Input<MouseButton>>,
+    windows: Res<Windows>,
+    mut follow_mode: ResMut<FollowMode>,
+    cameras: Query<(&Camera, &GlobalTransform), With<FpsCameraController>>,
+    query_pipeline: Res<QueryPipeline>,
+    colliders: QueryPipelineColliderComponentsQuery,
+    parents: Query<&Parent>,
+    balls: Query<&Ball>,
+    round: Res<RoundState>,
+) {
+    if !follow_mode.following || !mouse_input.just_pressed(MouseButton::Left) {
+        return;
+    }
+    let (camera, camera_transform) = match cameras.get_single() {
+        Ok(camera) => camera,
+        Err(_) => return,
+    };
+    let window = match windows.get(camera.window) {
+        Some(window) => window,
+        None => return,
+    };
+    let ray = match window.cursor_position().and_then(|cursor| {
+        cursor_ray(
+            camera,
+            camera_transform,
+            Vec2::new(window.width(), window.height()),
+            cursor,
+        )
+    }) {
+        Some(ray) => ray,
+        None => return,
+    };
+    // Ball colliders are children of the ball's rigid body
+    let ball_of_collider = |collider| {
+        let ball_entity = parents.get(collider).ok()?.0;
+        Some((ball_entity, balls.get(ball_entity).ok()?))
+    };
+    let hit = ball_hit_by_ray(&query_pipeline, &colliders, &ray, |collider| {
+        ball_of_collider(collider).is_some()
+    });
+    if let Some((ball_entity, ball)) = hit.and_then(ball_of_collider) {
+        if round.ball_in_play(ball.player, ball_entity) {
+            // follow_ball picks up the change of target, transitioning the camera to it
+            follow_mode.index = ball.player;
+            info!("Now following: {}", round.players[ball.player].name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::camera::{CameraProjection, PerspectiveProjection};
+    use bevy_rapier3d::{na::Isometry3, rapier::parry::query::RayCast};
+
+    use super::*;
+
+    /// Players of whom only those in `live` have a ball in play.
+    fn players(n: usize, live: &[usize]) -> Vec<PlayerState> {
+        (0..n)
+            .map(|i| {
+                let mut player = PlayerState::new(i.to_string(), Color::WHITE, Vec3::ZERO, 0.0);
+                if live.contains(&i) {
+                    player.entity = Some(Entity::from_raw(i as u32));
+                }
+                player
+            })
+            .collect()
+    }
+
+    #[test]
+    fn next_live_player_skips_finished_players() {
+        let players = players(5, &[0, 2, 3]);
+        assert_eq!(next_live_player(&players, 0, true), Some(2));
+        assert_eq!(next_live_player(&players, 3, true), Some(0));
+        assert_eq!(next_live_player(&players, 2, false), Some(0));
+        assert_eq!(next_live_player(&players, 0, false), Some(3));
+        // A finished player being followed moves on to the next live one
+        assert_eq!(next_live_player(&players, 4, true), Some(0));
+        // The only live player is next after themselves
+        assert_eq!(next_live_player(&players[..1], 0, true), Some(0));
+    }
+
+    #[test]
+    fn next_live_player_is_nobody_when_no_balls_are_live() {
+        assert_eq!(next_live_player(&players(5, &[]), 1, true), None);
+        assert_eq!(next_live_player(&players(5, &[]), 1, false), None);
+        assert_eq!(next_live_player(&[], 0, true), None);
+    }
+
+    #[test]
+    fn follow_transition_eases_between_balls() {
+        let from = LookTransform {
+            eye: Vec3::new(0.0, 10.0, 0.0),
+            target: Vec3::ZERO,
+        };
+        let to = LookTransform {
+            eye: Vec3::new(100.0, 10.0, -50.0),
+            target: Vec3::new(100.0, 0.0, -50.0),
+        };
+        let look = transition_look(&from, &to, FOLLOW_TRANSITION_DURATION / 2).unwrap();
+        // Smoothstep easing is symmetric, so it is half way at the midpoint
+        assert!(look.eye.abs_diff_eq(from.eye.lerp(to.eye, 0.5), 1e-4));
+        assert!(look
+            .target
+            .abs_diff_eq(from.target.lerp(to.target, 0.5), 1e-4));
+
+        let early = transition_look(&from, &to, FOLLOW_TRANSITION_DURATION / 10).unwrap();
+        assert!(early.eye.distance(from.eye) < early.eye.distance(to.eye));
+        assert!(transition_look(&from, &to, FOLLOW_TRANSITION_DURATION).is_none());
+    }
+
+    #[test]
+    fn podium_shows_the_top_three_with_their_times() {
+        let mut round = RoundState {
+            start_secs: 2.0,
+            players: players(4, &[2]),
+        };
+        for (player, end_secs) in [(0, 14.5), (3, 12.25)] {
+            round.players[player].finished = true;
+            round.players[player].end_secs = Some(end_secs);
+        }
+        round.players[0].color = Color::RED;
+        round.players[1].distance = -500.0;
+        round.players[1].end_secs = Some(9.0);
+        round.players[2].distance = -300.0;
+
+        assert_eq!(
+            podium(&round),
+            vec![
+                ("1. 3  10.250s".to_string(), Color::WHITE),
+                ("2. 0  12.500s".to_string(), Color::RED),
+                ("3. 1  DNF".to_string(), Color::WHITE),
+            ]
+        );
+    }
+
+    #[test]
+    fn speeds_are_shown_in_metres_per_second() {
+        let velocity = Vector3::new(3.0f32, 0.0, -4.0);
+        assert_eq!(format_speed(Some(velocity.norm())), " 5.0m/s");
+        assert_eq!(format_speed(Some(12.34)), "12.3m/s");
+        assert_eq!(format_speed(None), "");
+    }
+
+    #[test]
+    fn clicks_cast_rays_through_the_cursor() {
+        let projection = PerspectiveProjection {
+            aspect_ratio: 2.0,
+            ..Default::default()
+        };
+        let camera = Camera {
+            projection_matrix: projection.get_projection_matrix(),
+            ..Default::default()
+        };
+        // Looking down -z from the origin
+        let camera_transform = GlobalTransform::identity();
+        let window_size = Vec2::new(800.0, 400.0);
+        let ray = |cursor| cursor_ray(&camera, &camera_transform, window_size, cursor).unwrap();
+        let hits = |ray: &Ray, x| {
+            bevy_rapier3d::rapier::parry::shape::Ball::new(1.0).intersects_ray(
+                &Isometry3::translation(x, 0.0, -10.0),
+                ray,
+                f32::MAX,
+            )
+        };
+
+        let center = ray(0.5 * window_size);
+        assert!((center.dir - -Vector3::z()).norm() < 1e-5);
+        assert!(hits(&center, 0.0));
+        assert!(!hits(&center, 3.0));
+        // A quarter of the way across from the center is half way to the edge of the view
+        let right = ray(Vec2::new(600.0, 200.0));
+        let x = 10.0 * 0.5 * (0.5 * projection.fov).tan() * projection.aspect_ratio;
+        assert!(hits(&right, x));
+        assert!(!hits(&right, 0.0));
+    }
+
+    #[test]
+    fn orbit_offsets_are_on_a_sphere_around_the_ball() {
+        let close = |a: Vec3, b: Vec3| a.distance(b) < 1e-4;
+        assert!(close(
+            orbit_offset(0.0, 0.0, 10.0),
+            Vec3::new(0.0, 0.0, 10.0)
+        ));
+        assert!(close(
+            orbit_offset(std::f32::consts::FRAC_PI_2, 0.0, 10.0),
+            Vec3::new(10.0, 0.0, 0.0)
+        ));
+        assert!(close(
+            orbit_offset(0.0, std::f32::consts::FRAC_PI_4, 2.0f32.sqrt()),
+            Vec3::new(0.0, 1.0, 1.0)
+        ));
+        assert!((orbit_offset(1.2, -0.7, 25.0).length() - 25.0).abs() < 1e-4);
+
+        // Orbiting starts from wherever the camera is
+        let look_transform = LookTransform {
+            eye: Vec3::new(13.0, 4.0, -2.0),
+            target: Vec3::new(10.0, 0.0, -14.0),
+        };
+        let mut follow_mode = FollowMode::default();
+        start_orbiting(&mut follow_mode, &look_transform);
+        assert!(follow_mode.orbiting);
+        assert!(close(
+            look_transform.target
+                + orbit_offset(follow_mode.yaw, follow_mode.pitch, follow_mode.distance),
+            look_transform.eye
+        ));
+    }
+
+    #[test]
+    fn shakes_scale_with_impact_energy_and_decay() {
+        assert_eq!(impact_shake(0.5 * SHAKE_MIN_IMPACT), None);
+        let shake = impact_shake(SHAKE_MIN_IMPACT).unwrap();
+        assert_eq!(impact_shake(2.0 * SHAKE_MIN_IMPACT), Some(4.0 * shake));
+        assert_eq!(impact_shake(1000.0), Some(MAX_SHAKE));
+
+        assert_eq!(shake_magnitude(4.0, Duration::ZERO), 4.0);
+        assert_eq!(shake_magnitude(4.0, SHAKE_DURATION / 2), 2.0);
+        assert_eq!(shake_magnitude(4.0, SHAKE_DURATION), 0.0);
+        assert_eq!(shake_magnitude(4.0, 2 * SHAKE_DURATION), 0.0);
+    }
+
+    /// An app that has added the visuals of a ball for each of `colors`, with `settings`.
+    fn ball_visuals_app(colors: &[Color], settings: Settings) -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(bevy::asset::AssetPlugin)
+            .add_asset::<Mesh>()
+            .add_asset::<StandardMaterial>()
+            .insert_resource(settings)
+            .init_resource::<BallPhysics>()
+            .init_resource::<BallMaterials>()
+            .add_startup_system(setup_ball_mesh)
+            .add_startup_system(setup_lighting)
+            .add_system(add_ball_visuals);
+        let players = colors
+            .iter()
+            .enumerate()
+            .map(|(i, &color)| {
+                let mut player = PlayerState::new(i.to_string(), color, Vec3::ZERO, 0.0);
+                player.entity = Some(app.world.spawn().insert(Ball { player: i }).id());
+                player
+            })
+            .collect();
+        app.insert_resource(RoundState {
+            start_secs: 0.0,
+            players,
+        });
+        app.update();
+        app
+    }
+
+    #[test]
+    fn balls_of_a_color_share_a_mesh_and_material() {
+        let mut app = ball_visuals_app(&[Color::RED; 10], Settings::default());
+        let visuals = app
+            .world
+            .query::<(&Handle<Mesh>, &Handle<StandardMaterial>)>()
+            .iter(&app.world)
+            .map(|(mesh, material)| (mesh.clone(), material.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(visuals.len(), 10);
+        assert!(visuals.iter().all(|visual| *visual == visuals[0]));
+        let materials = app
+            .world
+            .get_resource::<Assets<StandardMaterial>>()
+            .unwrap();
+        assert_eq!(materials.len(), 1);
+        let meshes = app.world.get_resource::<Assets<Mesh>>().unwrap();
+        assert_eq!(meshes.len(), BALL_LOD_SUBDIVISIONS.len());
+    }
+
+    #[test]
+    fn ball_materials_are_cached_by_color() {
+        let mut app = ball_visuals_app(&[], Settings::default());
+        let world = app.world.cell();
+        let mut ball_materials = world.get_resource_mut::<BallMaterials>().unwrap();
+        let mut materials = world
+            .get_resource_mut::<Assets<StandardMaterial>>()
+            .unwrap();
+        let red = ball_materials.material(Color::RED, &mut materials);
+        let blue = ball_materials.material(Color::BLUE, &mut materials);
+        assert_ne!(red, blue);
+        assert_eq!(ball_materials.material(Color::RED, &mut materials), red);
+        // Colors that differ by less than 8 bits can show are the same color
+        let almost_red = Color::rgb(1.0, 0.001, 0.0);
+        assert_eq!(ball_materials.material(almost_red, &mut materials), red);
+        assert_eq!(materials.len(), 2);
+    }
+
+    #[test]
+    fn directional_lighting_gives_balls_no_lights() {
+        let lights = |lighting| {
+            let settings = Settings {
+                lighting,
+                ..Default::default()
+            };
+            let mut app = ball_visuals_app(&[Color::RED, Color::BLUE], settings);
+            let point_lights = app.world.query::<&PointLight>().iter(&app.world).count();
+            let directional_lights = app
+                .world
+                .query::<&DirectionalLight>()
+                .iter(&app.world)
+                .count();
+            (point_lights, directional_lights)
+        };
+        assert_eq!(lights(LightingMode::PerBall), (2, 0));
+        assert_eq!(lights(LightingMode::Directional), (0, 1));
+    }
+
+    #[test]
+    fn ball_lod_changes_only_past_the_hysteresis() {
+        let distances = [100.0, 300.0];
+        let lod = |distance, current| select_lod(distance, current, &distances, 10.0);
+        assert_eq!(lod(50.0, 0), 0);
+        assert_eq!(lod(105.0, 0), 0);
+        assert_eq!(lod(115.0, 0), 1);
+        assert_eq!(lod(95.0, 1), 1);
+        assert_eq!(lod(85.0, 1), 0);
+        // Jumping straight across several levels
+        assert_eq!(lod(1000.0, 0), 2);
+        assert_eq!(lod(0.0, 2), 0);
+        assert_eq!(lod(295.0, 2), 2);
+        assert_eq!(lod(200.0, 2), 1);
+    }
+
+    #[test]
+    fn query_strings_give_args_and_flags() {
+        assert_eq!(
+            parse_query("?seed=42&chat-votes&rounds=3"),
+            vec![
+                ("seed".to_string(), Some("42".to_string())),
+                ("chat-votes".to_string(), None),
+                ("rounds".to_string(), Some("3".to_string())),
+            ]
+        );
+        assert_eq!(parse_query(""), vec![]);
+        assert_eq!(parse_query("?"), vec![]);
+    }
+
+    #[test]
+    fn clicking_a_setting_changes_it() {
+        let mut app = App::new();
+        app.init_resource::<Settings>()
+            .insert_resource(RaceConfig::new(8))
+            .insert_resource(RaceSeed(7))
+            .add_system(settings_button_system);
+        for setting in [SettingButton::Lighting, SettingButton::Players] {
+            app.world.spawn().insert_bundle((
+                Interaction::Clicked,
+                UiColor::from(NORMAL_BUTTON),
+                setting,
+                Button,
+            ));
+        }
+        app.update();
+
+        let settings = app.world.get_resource::<Settings>().unwrap();
+        assert_eq!(settings.lighting, LightingMode::Directional);
+        assert!(!settings.shadows);
+        let race_config = app.world.get_resource::<RaceConfig>().unwrap();
+        assert_eq!(race_config.n_players, 10);
+        assert_eq!(race_config.balls.len(), 10);
+
+        // Player counts wrap around
+        let (mut settings, mut race_config, mut seed) =
+            (Settings::default(), RaceConfig::new(20), RaceSeed(7));
+        SettingButton::Players.apply(&mut settings, &mut race_config, &mut seed);
+        assert_eq!(race_config.n_players, PLAYER_COUNTS[0]);
+        SettingButton::Volume.apply(&mut settings, &mut race_config, &mut seed);
+        assert_eq!(settings.master_volume, 0.0);
+    }
+
+    #[test]
+    fn ui_scales_with_the_window_height() {
+        assert_eq!(ui_scale(UI_REFERENCE_HEIGHT), 1.0);
+        assert_eq!(ui_scale(1080.0), 2.0);
+
+        let mut app = App::new();
+        app.init_resource::<Windows>()
+            .init_resource::<UiScale>()
+            .add_system(scale_ui);
+        let panel = app
+            .world
+            .spawn()
+            .insert_bundle((
+                Style::default(),
+                ScaledSize {
+                    width: Some(LEADERBOARD_WIDTH),
+                    height: None,
+                },
+            ))
+            .id();
+        let text = app
+            .world
+            .spawn()
+            .insert_bundle((
+                Text::with_section("", TextStyle::default(), Default::default()),
+                ScaledFontSize(BALL_LABEL_FONT_SIZE),
+            ))
+            .id();
+        app.update();
+        let style = app.world.get::<Style>(panel).unwrap();
+        assert_eq!(style.size.width, Val::Px(LEADERBOARD_WIDTH));
+        assert_eq!(style.size.height, Style::default().size.height);
+
+        app.world.get_resource_mut::<UiScale>().unwrap().0 = 2.0;
+        app.update();
+        let style = app.world.get::<Style>(panel).unwrap();
+        assert_eq!(style.size.width, Val::Px(2.0 * LEADERBOARD_WIDTH));
+        let text = app.world.get::<Text>(text).unwrap();
+        assert_eq!(text.sections[0].style.font_size, 2.0 * BALL_LABEL_FONT_SIZE);
+    }
+
+    #[test]
+    fn enter_presses_the_focused_button() {
+        let mut app = App::new();
+        app.init_resource::<MenuSelection>()
+            .init_resource::<Input<KeyCode>>()
+            .add_state(GameState::Menu)
+            .add_system(menu_keyboard_input);
+        for (button, y) in [(MenuButton::Settings, 100.0), (MenuButton::Start, 0.0)] {
+            app.world.spawn().insert_bundle((
+                button,
+                UiColor::from(NORMAL_BUTTON),
+                GlobalTransform::from_xyz(0.0, y, 0.0),
+            ));
+        }
+        app.update();
+        assert_eq!(app.world.get_resource::<MenuSelection>().unwrap().index, 0);
+
+        let mut keyboard_input = app.world.get_resource_mut::<Input<KeyCode>>().unwrap();
+        keyboard_input.press(KeyCode::Down);
+        keyboard_input.press(KeyCode::Return);
+        app.update();
+        assert_eq!(app.world.get_resource::<MenuSelection>().unwrap().index, 1);
+        assert_eq!(
+            app.world
+                .get_resource::<State<GameState>>()
+                .unwrap()
+                .current(),
+            &GameState::Playing
+        );
+    }
+
+    #[test]
+    fn bumpers_cycle_the_followed_ball() {
+        let keyboard_input = Input::<KeyCode>::default();
+        let mut gamepad_buttons = Input::<GamepadButton>::default();
+        let (gamepad, other) = (Gamepad(0), Gamepad(1));
+        assert_eq!(
+            cycle_direction(&keyboard_input, &gamepad_buttons, Some(gamepad)),
+            None
+        );
+
+        gamepad_buttons.press(GamepadButton(gamepad, GamepadButtonType::RightTrigger));
+        let forward = cycle_direction(&keyboard_input, &gamepad_buttons, Some(gamepad)).unwrap();
+        let players = players(5, &[0, 2, 3]);
+        assert_eq!(next_live_player(&players, 0, forward), Some(2));
+
+        gamepad_buttons.clear();
+        gamepad_buttons.press(GamepadButton(gamepad, GamepadButtonType::LeftTrigger));
+        let forward = cycle_direction(&keyboard_input, &gamepad_buttons, Some(gamepad)).unwrap();
+        assert_eq!(next_live_player(&players, 0, forward), Some(3));
+
+        // Gamepads other than the active one are ignored, as are buttons with no gamepad active
+        gamepad_buttons.clear();
+        gamepad_buttons.press(GamepadButton(other, GamepadButtonType::RightTrigger));
+        assert_eq!(
+            cycle_direction(&keyboard_input, &gamepad_buttons, Some(gamepad)),
+            None
+        );
+        assert_eq!(
+            cycle_direction(&keyboard_input, &gamepad_buttons, None),
+            None
+        );
+    }
+
+    #[test]
+    fn following_survives_missing_cameras_and_players() {
+        let mut app = App::new();
+        app.init_resource::<Input<KeyCode>>()
+            .init_resource::<Input<GamepadButton>>()
+            .init_resource::<ActiveGamepad>()
+            .init_resource::<PhotoFinish>()
+            .insert_resource(FollowMode {
+                index: 7,
+                ..Default::default()
+            })
+            .insert_resource(RoundState {
+                start_secs: 0.0,
+                players: players(3, &[1]),
+            })
+            .add_system(follow_ball);
+        let spawn_camera = |app: &mut App| {
+            app.world.spawn().insert_bundle((
+                FpsCameraController::default(),
+                LookTransform {
+                    eye: Vec3::ONE,
+                    target: Vec3::ZERO,
+                },
+                Smoother::new(0.9),
+            ));
+        };
+        // Between states there may be no camera, or two
+        app.update();
+        spawn_camera(&mut app);
+        spawn_camera(&mut app);
+        app.update();
+        assert_eq!(app.world.get_resource::<FollowMode>().unwrap().index, 7);
+
+        // With one camera, following starts over from a player who is no longer in the round
+        let mut cameras = app
+            .world
+            .query_filtered::<Entity, With<FpsCameraController>>();
+        let camera = cameras.iter(&app.world).next().unwrap();
+        app.world.despawn(camera);
+        app.update();
+        let follow_mode = app.world.get_resource::<FollowMode>().unwrap();
+        assert_eq!(follow_mode.index, 0);
+        assert_eq!(follow_mode.target, None);
+    }
+
+    #[test]
+    fn debug_stats_are_one_per_line() {
+        assert_eq!(
+            debug_stats_text(Some(59.7), 3, Some("RED"), 1.234, 1200),
+            "FPS: 60\nBALLS: 3\nLEADER: RED\nPHYSICS: 1.23ms\nTRACK TRIANGLES: 1200"
+        );
+        assert_eq!(
+            debug_stats_text(None, 0, None, 0.0, 0),
+            "FPS: -\nBALLS: 0\nLEADER: -\nPHYSICS: 0.00ms\nTRACK TRIANGLES: 0"
+        );
+    }
+
+    #[test]
+    fn leaving_a_menu_keeps_the_debug_overlay() {
+        let mut app = App::new();
+        app.init_resource::<MenuSelection>()
+            .add_system(cleanup_menu);
+        let menu = app.world.spawn().insert(Node::default()).id();
+        let overlay = app
+            .world
+            .spawn()
+            .insert_bundle((Node::default(), DebugOverlay))
+            .id();
+        app.update();
+        assert!(app.world.get_entity(menu).is_none());
+        assert!(app.world.get_entity(overlay).is_some());
+    }
+
+    #[test]
+    fn debug_colliders_add_wireframes_that_toggle() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(bevy::asset::AssetPlugin)
+            .add_asset::<Mesh>()
+            .add_asset::<StandardMaterial>()
+            .init_resource::<Input<KeyCode>>()
+            .insert_resource(DebugColliders { visible: true })
+            .add_system(toggle_debug_colliders)
+            .add_system(add_collider_wireframes);
+        let shape: ColliderShapeComponent = ColliderShape::cuboid(1.0, 1.0, 1.0).into();
+        let collider = app.world.spawn().insert_bundle((Track, shape)).id();
+        app.update();
+
+        let mut wireframes = app
+            .world
+            .query_filtered::<(&Parent, &Visibility), With<ColliderWireframe>>();
+        let (parent, visibility) = wireframes.iter(&app.world).next().unwrap();
+        assert_eq!(parent.0, collider);
+        assert!(visibility.is_visible);
+
+        app.world
+            .get_resource_mut::<Input<KeyCode>>()
+            .unwrap()
+            .press(DEBUG_COLLIDERS_KEY);
+        app.update();
+        assert!(!app.world.get_resource::<DebugColliders>().unwrap().visible);
+        let (_, visibility) = wireframes.iter(&app.world).next().unwrap();
+        assert!(!visibility.is_visible);
+        // Each collider only ever gets one wireframe
+        assert_eq!(wireframes.iter(&app.world).count(), 1);
+    }
+
+    #[test]
+    fn race_times_are_minutes_seconds_and_milliseconds() {
+        assert_eq!(format_race_time(0.0), "00:00.000");
+        assert_eq!(format_race_time(-1.0), "00:00.000");
+        assert_eq!(format_race_time(0.25), "00:00.250");
+        assert_eq!(format_race_time(59.9996), "01:00.000");
+        assert_eq!(format_race_time(83.456), "01:23.456");
+        assert_eq!(format_race_time(754.5), "12:34.500");
+    }
+
+    #[test]
+    fn leaderboard_text_contrasts_with_its_row() {
+        for color in [Color::BLACK, Color::DARK_GRAY, Color::BLUE, Color::PURPLE] {
+            assert_eq!(contrasting_text_color(color), Color::WHITE, "{:?}", color);
+        }
+        for color in [Color::WHITE, Color::YELLOW, Color::CYAN, Color::LIME_GREEN] {
+            assert_eq!(contrasting_text_color(color), Color::BLACK, "{:?}", color);
         }
+        assert_eq!(
+            row_tint(Color::RED),
+            Color::rgba(1.0, 0.0, 0.0, ROW_TINT_ALPHA)
+        );
+    }
+
+    #[test]
+    fn photo_mode_hides_the_ui_frees_the_camera_and_takes_screenshots() {
+        let mut app = App::new();
+        app.init_resource::<PhotoMode>()
+            .init_resource::<FollowMode>()
+            .init_resource::<Windows>()
+            .init_resource::<Input<KeyCode>>()
+            .insert_resource(RapierConfiguration::default())
+            .add_plugin(ScreenshotPlugin)
+            .add_state(GameState::Playing)
+            .add_system_set(SystemSet::on_update(GameState::Playing).with_system(photo_mode_input))
+            .add_system_set(
+                SystemSet::on_enter(GameState::PhotoMode)
+                    .with_system(pause_round)
+                    .with_system(enter_photo_mode),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::PhotoMode)
+                    .with_system(exit_photo_mode_input)
+                    .with_system(screenshot_input),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::PhotoMode)
+                    .with_system(resume_round)
+                    .with_system(exit_photo_mode),
+            );
+        let leaderboard = app
+            .world
+            .spawn()
+            .insert_bundle((Node::default(), Style::default(), Leaderboard))
+            .id();
+        let camera = app
+            .world
+            .spawn()
+            .insert_bundle((
+                FpsCameraController {
+                    enabled: false,
+                    ..Default::default()
+                },
+                Smoother::new(FOLLOW_LAG_WEIGHT),
+            ))
+            .id();
+        let press = |app: &mut App, key| {
+            let mut keyboard_input = app.world.get_resource_mut::<Input<KeyCode>>().unwrap();
+            keyboard_input.clear();
+            keyboard_input.press(key);
+            app.update();
+            app.world
+                .get_resource_mut::<Input<KeyCode>>()
+                .unwrap()
+                .release(key);
+        };
+
+        press(&mut app, PHOTO_MODE_KEY);
+        let state = app.world.get_resource::<State<GameState>>().unwrap();
+        // Following only happens while playing
+        assert_eq!(*state.current(), GameState::PhotoMode);
+        assert_eq!(
+            app.world.get::<Style>(leaderboard).unwrap().display,
+            Display::None
+        );
+        assert!(
+            app.world
+                .get::<FpsCameraController>(camera)
+                .unwrap()
+                .enabled
+        );
+        let rapier_config = app.world.get_resource::<RapierConfiguration>().unwrap();
+        assert!(!rapier_config.physics_pipeline_active);
+
+        press(&mut app, SCREENSHOT_KEY);
+        assert!(app.world.get_resource::<ScreenshotRequest>().unwrap().0);
+        let state = app.world.get_resource::<State<GameState>>().unwrap();
+        assert_eq!(*state.current(), GameState::PhotoMode);
+
+        press(&mut app, KeyCode::Escape);
+        let state = app.world.get_resource::<State<GameState>>().unwrap();
+        assert_eq!(*state.current(), GameState::Playing);
+        assert_eq!(
+            app.world.get::<Style>(leaderboard).unwrap().display,
+            Display::Flex
+        );
+        assert!(
+            !app.world
+                .get::<FpsCameraController>(camera)
+                .unwrap()
+                .enabled
+        );
+        let rapier_config = app.world.get_resource::<RapierConfiguration>().unwrap();
+        assert!(rapier_config.physics_pipeline_active);
+    }
+
+    #[test]
+    fn director_frames_the_whole_pack() {
+        assert_eq!(bounding_sphere(&[]), None);
+        let ball = Vec3::new(1.0, 2.0, 3.0);
+        assert_eq!(bounding_sphere(&[ball]), Some((ball, 0.0)));
+        let pack = [
+            Vec3::new(-3.0, 1.0, -10.0),
+            Vec3::new(3.0, 1.0, -10.0),
+            Vec3::new(0.0, 1.0, -16.0),
+            Vec3::new(0.0, 1.0, -4.0),
+        ];
+        let (center, radius) = bounding_sphere(&pack).unwrap();
+        assert!(center.abs_diff_eq(Vec3::new(0.0, 1.0, -10.0), 1e-6));
+        assert!((radius - 6.0).abs() < 1e-6);
+
+        // The sphere just fits the field of view, seen from along the view direction
+        let fov = std::f32::consts::FRAC_PI_2;
+        let view = Vec3::new(0.0, 2.0, 1.0);
+        let (eye, target) = frame_sphere(center, radius, fov, view);
+        assert_eq!(target, center);
+        assert!((eye - center)
+            .normalize()
+            .abs_diff_eq(view.normalize(), 1e-6));
+        let half_angle = (radius / eye.distance(center)).asin();
+        assert!((half_angle - 0.5 * fov).abs() < 1e-5);
+        // Spreading out zooms out, but a single ball is framed no closer than the smallest view
+        let (wide_eye, _) = frame_sphere(center, 2.0 * radius, fov, view);
+        assert!(wide_eye.distance(center) > eye.distance(center));
+        let (close_eye, _) = frame_sphere(ball, 0.0, fov, view);
+        let closest = MIN_DIRECTOR_RADIUS / (0.5 * fov).sin();
+        assert!((close_eye.distance(ball) - closest).abs() < 1e-4);
+    }
+
+    #[test]
+    fn the_most_voted_live_ball_is_followed() {
+        let mut app = App::new();
+        let mut votes = ChatVotes::default();
+        // Player 2 has the most votes, but has left the race
+        votes.counts.extend([(1, 4), (2, 6), (3, 1)]);
+        app.insert_resource(votes)
+            .init_resource::<FollowMode>()
+            .init_resource::<RaceClock>()
+            .insert_resource(RoundState {
+                start_secs: 0.0,
+                players: players(4, &[0, 1, 3]),
+            })
+            .add_system(follow_chat_votes);
+        let followed = |app: &mut App, secs| {
+            app.world.get_resource_mut::<RaceClock>().unwrap().elapsed = secs;
+            app.update();
+            app.world.get_resource::<FollowMode>().unwrap().index
+        };
+
+        assert_eq!(followed(&mut app, 0.5 * CHAT_VOTE_SECS), 0);
+        assert_eq!(followed(&mut app, CHAT_VOTE_SECS), 1);
+        let votes = app.world.get_resource::<ChatVotes>().unwrap();
+        assert!(votes.counts.is_empty());
+        assert_eq!(votes.since, CHAT_VOTE_SECS);
+    }
+
+    #[test]
+    fn labels_are_projected_onto_the_view() {
+        let projection = PerspectiveProjection {
+            aspect_ratio: 2.0,
+            ..Default::default()
+        };
+        let camera = Camera {
+            projection_matrix: projection.get_projection_matrix(),
+            ..Default::default()
+        };
+        // Looking down -z from a little above the origin
+        let camera_transform = GlobalTransform::from_translation(Vec3::Y);
+        let viewport = |world| world_to_viewport(&camera, &camera_transform, world);
+
+        let center = viewport(Vec3::new(0.0, 1.0, -10.0)).unwrap();
+        assert!(center.abs_diff_eq(Vec2::splat(0.5), 1e-5));
+        // Half way to the right and top edges of the view
+        let half_height = 10.0 * (0.5 * projection.fov).tan();
+        let x = 0.5 * half_height * projection.aspect_ratio;
+        let point = viewport(Vec3::new(x, 1.0 + 0.5 * half_height, -10.0)).unwrap();
+        assert!(point.abs_diff_eq(Vec2::new(0.75, 0.75), 1e-5));
+        // Out of the sides of the view, and behind the camera
+        assert_eq!(viewport(Vec3::new(3.0 * x, 1.0, -10.0)), None);
+        assert_eq!(viewport(Vec3::new(0.0, 1.0, 10.0)), None);
     }
 }
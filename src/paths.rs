@@ -3,19 +3,163 @@ use std::ops::Range;
 use bevy::math::Quat;
 use rand::{prelude::SmallRng, Rng};
 
+/// Number of times a yaw or pitch step that would leave the cumulative envelope is re-sampled
+/// before it is clamped to the envelope edge instead.
+const MAX_RESAMPLES: usize = 8;
+
 pub struct WormPathIterator {
     pub rng: SmallRng,
     pub yaw_range: Range<f32>,
     pub pitch_range: Range<f32>,
+    /// Maximum absolute heading yaw, relative to the initial forward direction, that the path may
+    /// accumulate. When set, each sampled yaw is treated as a change of heading and steps that
+    /// would take the heading outside the envelope are re-sampled. `None` preserves the original
+    /// behavior where every step samples an independent yaw relative to the initial forward
+    /// direction.
+    pub max_cumulative_yaw: Option<f32>,
+    /// Maximum absolute pitch that the path may accumulate, in the same way as
+    /// `max_cumulative_yaw`. `None` preserves the original behavior where every step samples an
+    /// independent pitch from `pitch_range`.
+    pub max_cumulative_pitch: Option<f32>,
+    /// Multiplier applied to the change in heading yaw between consecutive steps to produce a roll
+    /// around the forward axis, banking the path into turns. `0.0` disables banking.
+    pub bank_factor: f32,
+    yaw: f32,
+    pitch: f32,
+}
+
+impl WormPathIterator {
+    pub fn new(rng: SmallRng, yaw_range: Range<f32>, pitch_range: Range<f32>) -> Self {
+        Self {
+            rng,
+            yaw_range,
+            pitch_range,
+            max_cumulative_yaw: None,
+            max_cumulative_pitch: None,
+            bank_factor: 0.0,
+            yaw: 0.0,
+            pitch: 0.0,
+        }
+    }
+
+    pub fn with_max_cumulative_yaw(mut self, max_cumulative_yaw: Option<f32>) -> Self {
+        self.max_cumulative_yaw = max_cumulative_yaw;
+        self
+    }
+
+    pub fn with_max_cumulative_pitch(mut self, max_cumulative_pitch: Option<f32>) -> Self {
+        self.max_cumulative_pitch = max_cumulative_pitch;
+        self
+    }
+
+    pub fn with_bank_factor(mut self, bank_factor: f32) -> Self {
+        self.bank_factor = bank_factor;
+        self
+    }
+}
+
+/// The angle following `angle`, sampled from `range`. If `max_angle` is set, the sample is a
+/// change of `angle` instead, re-sampled if it would take the angle outside `max_angle`.
+fn next_angle(rng: &mut SmallRng, angle: f32, range: &Range<f32>, max_angle: Option<f32>) -> f32 {
+    let max_angle = match max_angle {
+        Some(max_angle) => max_angle.abs(),
+        None => return rng.gen_range(range.clone()),
+    };
+    for _ in 0..MAX_RESAMPLES {
+        let next = angle + rng.gen_range(range.clone());
+        if next.abs() <= max_angle {
+            return next;
+        }
+    }
+    (angle + rng.gen_range(range.clone())).clamp(-max_angle, max_angle)
 }
 
 impl Iterator for WormPathIterator {
     type Item = Quat;
 
     fn next(&mut self) -> Option<Self::Item> {
+        let yaw = next_angle(
+            &mut self.rng,
+            self.yaw,
+            &self.yaw_range,
+            self.max_cumulative_yaw,
+        );
+        let pitch = next_angle(
+            &mut self.rng,
+            self.pitch,
+            &self.pitch_range,
+            self.max_cumulative_pitch,
+        );
+        // Turning left (positive yaw) raises the right-hand, outer edge of the path
+        let roll = self.bank_factor * (yaw - self.yaw);
+        self.yaw = yaw;
+        self.pitch = pitch;
         Some(
-            Quat::from_rotation_y(self.rng.gen_range(self.yaw_range.clone()))
-                * Quat::from_rotation_x(self.rng.gen_range(self.pitch_range.clone())),
+            Quat::from_rotation_y(yaw) * Quat::from_rotation_x(pitch) * Quat::from_rotation_z(roll),
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::math::Vec3;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    /// Heading yaw and pitch of the forward direction, `-Z`, after `rotation`.
+    fn heading(rotation: Quat) -> (f32, f32) {
+        let forward = rotation * -Vec3::Z;
+        ((-forward.x).atan2(-forward.z), forward.y.asin())
+    }
+
+    #[test]
+    fn cumulative_heading_stays_within_envelope() {
+        let max_yaw = 0.5;
+        let max_pitch = 0.3;
+        let iter = WormPathIterator::new(SmallRng::seed_from_u64(1234), -0.4..0.4, -0.2..0.1)
+            .with_max_cumulative_yaw(Some(max_yaw))
+            .with_max_cumulative_pitch(Some(max_pitch));
+        for rotation in iter.take(500) {
+            let (yaw, pitch) = heading(rotation);
+            assert!(yaw.abs() <= max_yaw + 1e-4, "yaw {} left the envelope", yaw);
+            assert!(
+                pitch.abs() <= max_pitch + 1e-4,
+                "pitch {} left the envelope",
+                pitch
+            );
+        }
+    }
+
+    /// The sine of how far the path is rolled about its forward direction by `rotation`.
+    fn roll(rotation: Quat) -> f32 {
+        let (yaw, pitch) = heading(rotation);
+        let level_up = Quat::from_rotation_y(yaw) * Quat::from_rotation_x(pitch) * Vec3::Y;
+        (rotation * Vec3::Y).cross(level_up).length()
+    }
+
+    #[test]
+    fn pure_yaw_path_banks_into_turns() {
+        let path = |bank_factor| {
+            WormPathIterator::new(SmallRng::seed_from_u64(1234), -0.4..0.4, 0.0..1e-6)
+                .with_max_cumulative_yaw(Some(1.0))
+                .with_bank_factor(bank_factor)
+                .take(50)
+                .map(roll)
+                .collect::<Vec<_>>()
+        };
+        assert!(path(0.5).into_iter().any(|roll| roll > 1e-3));
+        assert!(path(0.0).into_iter().all(|roll| roll < 1e-3));
+    }
+
+    #[test]
+    fn same_seed_gives_same_path() {
+        let path = |seed| {
+            WormPathIterator::new(SmallRng::seed_from_u64(seed), -0.4..0.4, -0.2..0.1)
+                .with_max_cumulative_yaw(Some(0.5))
+                .take(100)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(path(42), path(42));
+    }
+}
@@ -0,0 +1,3341 @@
+use std::{borrow::Cow, cmp::Ordering, collections::VecDeque, ops::Range, time::Duration};
+
+use bevy::{math::const_vec3, prelude::*, utils::HashMap};
+use bevy_rapier3d::{
+    na::{Isometry3, Point3, Vector3},
+    physics::{
+        QueryPipelineColliderComponentsQuery, QueryPipelineColliderComponentsSet,
+        SimulationToRenderTime, TimestepMode,
+    },
+    prelude::*,
+};
+use rand::{rngs::SmallRng, seq::SliceRandom, Rng, SeedableRng};
+
+use crate::{
+    config::{GameConfig, StartMode},
+    shapes::{
+        index_list, mesh_to_collider, ColliderKind, HalfCylinderPath, MeshColliderError, PathError,
+        PathSection,
+    },
+    trail::Trail,
+};
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum GameState {
+    Menu,
+    NameEntry,
+    Settings,
+    Betting,
+    Playing,
+    Paused,
+    PhotoMode,
+    GameOver,
+}
+
+/// The stages of a frame of a race, in the order they run, so that every frame of a race does the
+/// same things in the same order.
+#[derive(SystemLabel, Debug, Clone, Eq, PartialEq, Hash)]
+pub enum RaceSystem {
+    /// Advancing the race clock, which the rest of the frame reads.
+    Clock,
+    /// Spawning the balls whose start delay is over.
+    Spawn,
+    /// Forces and corrections applied to balls in play.
+    Physics,
+    /// Checking for players who have finished or passed checkpoints.
+    Finish,
+    /// Removing the balls of players who have finished or dropped out.
+    Despawn,
+}
+
+/// The seed from which the track, start delays, and spawn positions of a round are generated.
+pub struct RaceSeed(pub u64);
+
+/// Replaces the [`RaceSeed`] as the seed from which start delays and spawn positions are generated,
+/// so that the same track can be raced from different starts.
+pub struct StartSeed(pub u64);
+
+/// Physics settings under which a seed always gives the same race, however fast the machine
+/// running it is. Physics advances by a fixed `dt` every frame, rather than by how long the frame
+/// took.
+#[derive(Clone, Copy, Debug)]
+pub struct DeterministicMode {
+    /// Seconds of simulated time per physics step.
+    pub dt: f32,
+    /// Maximum number of substeps that continuous collision detection takes within each step.
+    pub substeps: usize,
+}
+
+impl Default for DeterministicMode {
+    fn default() -> Self {
+        Self {
+            dt: 1.0 / 60.0,
+            substeps: 1,
+        }
+    }
+}
+
+impl DeterministicMode {
+    /// Configures the physics of `app`, which must already have the Rapier plugin, to step
+    /// deterministically.
+    pub fn configure(&self, app: &mut App) {
+        app.insert_resource(*self)
+            .insert_resource(RapierConfiguration {
+                timestep_mode: TimestepMode::FixedTimestep,
+                ..Default::default()
+            })
+            .insert_resource(IntegrationParameters {
+                dt: self.dt,
+                max_ccd_substeps: self.substeps,
+                ..Default::default()
+            });
+    }
+}
+
+pub const SPAWN_POSITION: Vec3 = Vec3::ZERO;
+pub const SPAWN_RADIUS: f32 = 75.0;
+
+#[derive(Component)]
+pub struct GameLevel;
+
+#[derive(Component)]
+pub struct Track;
+
+/// An obstacle in the track for balls to dodge, either a pillar or the bar of a
+/// [`RotatingHazard`].
+#[derive(Component)]
+pub struct Obstacle;
+
+/// A kinematic body spinning about the middle of the track at `speed` radians per second, about
+/// its local z axis, which runs along the track.
+#[derive(Component)]
+pub struct RotatingHazard {
+    pub speed: f32,
+}
+
+/// Speed at which rotating hazards spin, in radians per second.
+const HAZARD_SPEED: f32 = 1.0;
+/// Half the length of the bar of a rotating hazard, as a fraction of the radius of the track where
+/// it spins. The bar sweeps close enough to the floor of the track that balls cannot roll under it.
+const HAZARD_REACH: f32 = 0.98;
+/// Half the thickness of the bar of a rotating hazard, as a fraction of the radius of the track.
+const HAZARD_THICKNESS: f32 = 0.02;
+
+/// Radius of obstacle pillars, as a fraction of the radius of the track.
+const OBSTACLE_RADIUS: f32 = 0.04;
+/// Height of obstacle pillars, as a fraction of the radius of the track.
+const OBSTACLE_HEIGHT: f32 = 0.3;
+/// Furthest that an obstacle stands from the middle of the track, as a fraction of its radius
+/// there, so that there is always room to pass on either side.
+const OBSTACLE_MAX_OFFSET: f32 = 0.6;
+
+/// The position and rotation of each of the `path.obstacles` pillars along the path with
+/// `sections`, standing upright on the floor of the tube at random segments and offsets from the
+/// middle. The pillars are placed from the path's seed, so a track always has the same ones.
+///
+/// None stand in the first tenth of the path, where the balls spawn, nor in gaps.
+pub fn obstacle_placements(path: &HalfCylinderPath, sections: &[PathSection]) -> Vec<(Vec3, Quat)> {
+    let n_segments = sections.len().saturating_sub(1);
+    let segments = (n_segments / 10..n_segments)
+        .filter(|index| !path.gap_segments.contains(index))
+        .collect::<Vec<_>>();
+    if segments.is_empty() {
+        return Vec::new();
+    }
+    // Offset from the seed so that the placements do not follow the track's own random turns
+    let mut rng = SmallRng::seed_from_u64(path.seed.wrapping_add(1));
+    (0..path.obstacles)
+        .map(|_| {
+            let segment = segments[rng.gen_range(0..segments.len())];
+            let (start, end) = (sections[segment], sections[segment + 1]);
+            let t = rng.gen_range(0.0..1.0);
+            let center = start.position.lerp(end.position, t);
+            let up = start.up.lerp(end.up, t).normalize_or_zero();
+            let forward = start.forward.lerp(end.forward, t).normalize_or_zero();
+            let radius = start.radius + (end.radius - start.radius) * t;
+            let right = up.cross(-forward).normalize_or_zero();
+            let offset = rng.gen_range(-OBSTACLE_MAX_OFFSET..OBSTACLE_MAX_OFFSET) * radius;
+            // The floor of the tube at this offset from the middle, with the pillar standing on it
+            let floor = center + right * offset - up * (radius * radius - offset * offset).sqrt();
+            let position = floor + up * (0.5 * OBSTACLE_HEIGHT * path.radius);
+            (position, Quat::from_rotation_arc(Vec3::Y, up))
+        })
+        .collect()
+}
+
+/// Spawns the pillars standing in the track along `path`. They are static, like the track.
+fn spawn_obstacles(commands: &mut Commands, path: &HalfCylinderPath, sections: &[PathSection]) {
+    for (position, rotation) in obstacle_placements(path, sections) {
+        let radius = OBSTACLE_RADIUS * path.radius;
+        let shape = ColliderShape::cylinder(0.5 * OBSTACLE_HEIGHT * path.radius, radius);
+        let entity = spawn_halfpipe_segment(
+            commands,
+            shape,
+            ColliderMaterial::default(),
+            position,
+            rotation,
+        );
+        commands.entity(entity).insert(Obstacle);
+    }
+}
+
+/// The position, rotation, track radius, and spin speed of each of the `path.hazards` rotating
+/// hazards along the path with `sections`, spinning about the middle of the track at random
+/// segments. As with [`obstacle_placements`], the hazards are placed from the path's seed, and
+/// none are near the spawn or in gaps.
+pub fn hazard_placements(
+    path: &HalfCylinderPath,
+    sections: &[PathSection],
+) -> Vec<(Vec3, Quat, f32, f32)> {
+    let n_segments = sections.len().saturating_sub(1);
+    let segments = (n_segments / 10..n_segments)
+        .filter(|index| !path.gap_segments.contains(index))
+        .collect::<Vec<_>>();
+    if segments.is_empty() {
+        return Vec::new();
+    }
+    let mut rng = SmallRng::seed_from_u64(path.seed.wrapping_add(2));
+    (0..path.hazards)
+        .map(|_| {
+            let segment = segments[rng.gen_range(0..segments.len())];
+            let (start, end) = (sections[segment], sections[segment + 1]);
+            let t = rng.gen_range(0.0..1.0);
+            let position = start.position.lerp(end.position, t);
+            let forward = start.forward.lerp(end.forward, t).normalize_or_zero();
+            let up = start.up.lerp(end.up, t).normalize_or_zero();
+            let radius = start.radius + (end.radius - start.radius) * t;
+            let right = up.cross(-forward).normalize_or_zero();
+            let up = (-forward).cross(right);
+            let rotation = Quat::from_mat3(&Mat3::from_cols(right, up, -forward));
+            let speed = if rng.gen() {
+                HAZARD_SPEED
+            } else {
+                -HAZARD_SPEED
+            };
+            (position, rotation, radius, speed)
+        })
+        .collect()
+}
+
+/// Spawns the rotating hazards in the track along `path`.
+fn spawn_hazards(commands: &mut Commands, path: &HalfCylinderPath, sections: &[PathSection]) {
+    for (translation, rotation, radius, speed) in hazard_placements(path, sections) {
+        let position = isometry(translation, rotation);
+        let transform = Transform {
+            translation,
+            rotation,
+            ..Default::default()
+        };
+        let thickness = HAZARD_THICKNESS * path.radius;
+        commands
+            .spawn_bundle(RigidBodyBundle {
+                body_type: RigidBodyType::KinematicVelocityBased.into(),
+                position: RigidBodyPosition {
+                    position,
+                    next_position: position,
+                }
+                .into(),
+                ..Default::default()
+            })
+            .insert_bundle((
+                RotatingHazard { speed },
+                RigidBodyPositionSync::Discrete,
+                GameLevel,
+                transform,
+                GlobalTransform::from(transform),
+            ))
+            .with_children(|builder| {
+                builder
+                    .spawn_bundle(ColliderBundle {
+                        shape: ColliderShape::cuboid(HAZARD_REACH * radius, thickness, thickness)
+                            .into(),
+                        ..Default::default()
+                    })
+                    .insert_bundle((
+                        ColliderPositionSync::Discrete,
+                        Obstacle,
+                        Transform::default(),
+                        GlobalTransform::default(),
+                    ));
+            });
+    }
+}
+
+/// Spins rotating hazards about the track at their speed.
+pub fn spin_hazards(
+    mut hazards: Query<(
+        &RotatingHazard,
+        &RigidBodyPositionComponent,
+        &mut RigidBodyVelocityComponent,
+    )>,
+) {
+    for (hazard, position, mut velocity) in hazards.iter_mut() {
+        // Spinning about the local z axis leaves it where it is
+        let axis = position.position.rotation * Vector3::z();
+        velocity.angvel = axis * hazard.speed;
+    }
+}
+
+/// The kind of surface a segment of the track has.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum SegmentKind {
+    Normal,
+    /// A bounce pad, off which balls rebound.
+    Bouncy,
+    /// A boost pad, which speeds up balls that touch it.
+    Boost,
+    /// A slow zone, which damps the motion of balls while they touch it.
+    SlowZone,
+}
+
+impl SegmentKind {
+    pub fn collider_material(&self) -> ColliderMaterial {
+        match self {
+            SegmentKind::Normal => ColliderMaterial::default(),
+            SegmentKind::Bouncy => ColliderMaterial {
+                restitution: 1.5,
+                restitution_combine_rule: CoefficientCombineRule::Max,
+                ..Default::default()
+            },
+            SegmentKind::Boost | SegmentKind::SlowZone => ColliderMaterial::default(),
+        }
+    }
+
+    /// Physics events that colliders of this kind must report for their effects on balls.
+    pub fn active_events(&self) -> ActiveEvents {
+        match self {
+            SegmentKind::Normal | SegmentKind::Bouncy => ActiveEvents::empty(),
+            SegmentKind::Boost | SegmentKind::SlowZone => ActiveEvents::CONTACT_EVENTS,
+        }
+    }
+}
+
+/// A separately-spawned segment of the track, for tracks with special segments.
+#[derive(Component)]
+pub struct TrackSegment {
+    pub index: usize,
+    pub kind: SegmentKind,
+}
+
+/// Sensor spanning the end of the track. Balls that pass through it have finished, or in lap races,
+/// completed a lap.
+#[derive(Component)]
+pub struct FinishLine {
+    /// The direction in which balls must cross the line for it to count.
+    pub normal: Vec3,
+}
+
+/// Sensor part way along the track at which players' split times are recorded.
+#[derive(Component)]
+pub struct Checkpoint {
+    pub index: usize,
+}
+
+/// Number of checkpoints evenly spaced along the track between the start and the finish line.
+pub const N_CHECKPOINTS: usize = 4;
+
+#[derive(Component)]
+pub struct Ball {
+    pub player: usize,
+}
+
+/// Physical properties of every ball spawned in a round.
+#[derive(Clone, Debug)]
+pub struct BallPhysics {
+    pub radius: f32,
+    pub density: f32,
+    pub restitution: f32,
+    pub friction: f32,
+}
+
+impl Default for BallPhysics {
+    fn default() -> Self {
+        Self {
+            radius: 1.0,
+            density: 1.0,
+            restitution: 0.0,
+            friction: 0.5,
+        }
+    }
+}
+
+/// The collision group of balls, which they leave out of the groups they collide with when they
+/// pass through each other.
+pub const BALL_COLLISION_GROUP: u32 = 1 << 1;
+
+/// Whether balls collide with each other. In a ghost race they do not, and only collide with the
+/// track, so that where each ball places depends purely on its own run.
+#[derive(Clone, Copy, Debug)]
+pub struct BallCollision {
+    pub players_collide: bool,
+}
+
+impl Default for BallCollision {
+    fn default() -> Self {
+        Self {
+            players_collide: true,
+        }
+    }
+}
+
+impl BallCollision {
+    pub fn interaction_groups(&self) -> InteractionGroups {
+        if self.players_collide {
+            InteractionGroups::all()
+        } else {
+            InteractionGroups::new(BALL_COLLISION_GROUP, u32::MAX ^ BALL_COLLISION_GROUP)
+        }
+    }
+}
+
+/// The path of the track generated from `seed`, shaped by `config`. Lap races need a `closed`
+/// track.
+pub fn track_path(seed: u64, closed: bool, config: &GameConfig) -> HalfCylinderPath {
+    HalfCylinderPath {
+        start: SPAWN_POSITION,
+        radius: config.spawn_radius,
+        segment_length: config.segment_length,
+        n_segments: config.n_segments,
+        seed,
+        yaw_range: config.yaw_range.clone(),
+        pitch_range: config.pitch_range.clone(),
+        closed,
+        obstacles: config.obstacles,
+        hazards: config.hazards,
+        ..Default::default()
+    }
+}
+
+/// Bounds of all of the current track's geometry, from which out of bounds is decided.
+#[derive(Clone, Copy, Debug)]
+pub struct TrackBounds {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl TrackBounds {
+    pub fn union(self, other: Self) -> Self {
+        Self {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+}
+
+/// Information about the current track for display.
+pub struct TrackInfo {
+    /// Length of the track along its centerline.
+    pub length: f32,
+    /// The z coordinate of the finish line.
+    pub finish_z: f32,
+    /// The centers of the track's sections from start to finish.
+    centers: Vec<Vec3>,
+    /// Distance along the centerline from the start to each of `centers`.
+    distances: Vec<f32>,
+}
+
+impl TrackInfo {
+    /// Information about a track of `length` whose sections are centered on `centers`.
+    pub fn new(length: f32, centers: Vec<Vec3>) -> Self {
+        let distances = std::iter::once(0.0)
+            .chain(centers.windows(2).scan(0.0, |distance, pair| {
+                *distance += pair[0].distance(pair[1]);
+                Some(*distance)
+            }))
+            .collect();
+        Self {
+            length,
+            finish_z: centers.last().map_or(SPAWN_POSITION.z, |center| center.z),
+            centers,
+            distances,
+        }
+    }
+
+    /// How far through the race a ball at `position` is, from 0 at the start to 1 at the finish.
+    ///
+    /// Progress is measured along the centerline, up to the point on it nearest to the ball, so
+    /// that it holds on tracks that curve back on themselves, such as closed loops.
+    pub fn progress(&self, position: Vec3) -> f32 {
+        let total = self.distances.last().copied().unwrap_or(0.0);
+        if total <= 0.0 {
+            return 0.0;
+        }
+        let mut nearest = (f32::INFINITY, 0.0);
+        for (index, pair) in self.centers.windows(2).enumerate() {
+            let along = pair[1] - pair[0];
+            let t = ((position - pair[0]).dot(along) / along.length_squared().max(f32::EPSILON))
+                .clamp(0.0, 1.0);
+            let distance_squared = position.distance_squared(pair[0] + along * t);
+            if distance_squared < nearest.0 {
+                nearest = (distance_squared, self.distances[index] + along.length() * t);
+            }
+        }
+        (nearest.1 / total).clamp(0.0, 1.0)
+    }
+}
+
+/// A top-down view of the current track for the minimap.
+pub struct TrackPreview {
+    /// The centers of the track's sections, projected onto the minimap.
+    pub points: Vec<Vec2>,
+    /// The top-down position, in x and -z, of the minimap's origin.
+    origin: Vec2,
+    /// The top-down extent that the minimap covers along both of its axes.
+    extent: f32,
+}
+
+impl TrackPreview {
+    /// Fits the top-down view of `centers` to the minimap, keeping its aspect ratio and centering
+    /// it along its shorter axis.
+    pub fn new(centers: &[Vec3]) -> Self {
+        let top_down = centers
+            .iter()
+            .map(|center| Vec2::new(center.x, -center.z))
+            .collect::<Vec<_>>();
+        let min = top_down
+            .iter()
+            .copied()
+            .reduce(Vec2::min)
+            .unwrap_or(Vec2::ZERO);
+        let max = top_down
+            .iter()
+            .copied()
+            .reduce(Vec2::max)
+            .unwrap_or(Vec2::ZERO);
+        let extent = (max - min).max_element().max(f32::EPSILON);
+        let mut preview = Self {
+            points: Vec::new(),
+            origin: 0.5 * (min + max) - Vec2::splat(0.5 * extent),
+            extent,
+        };
+        preview.points = centers
+            .iter()
+            .map(|&center| preview.project(center))
+            .collect();
+        preview
+    }
+
+    /// Where `position` appears on the minimap, from (0, 0) at its bottom left to (1, 1) at its
+    /// top right. Balls travel towards -z, which is up the minimap.
+    pub fn project(&self, position: Vec3) -> Vec2 {
+        (Vec2::new(position.x, -position.z) - self.origin) / self.extent
+    }
+}
+
+/// Tracks with more segments than this are streamed in by a [`TrackStreamer`] rather than built
+/// all at once, as building the mesh and collider of a very long track takes a noticeable while.
+pub const STREAM_MIN_SEGMENTS: usize = 500;
+/// Number of segments in each piece of a streamed track.
+const STREAM_CHUNK_SEGMENTS: usize = 50;
+/// How many segments of track a streamed track keeps ahead of the leading ball.
+const STREAM_AHEAD_SEGMENTS: usize = 200;
+/// How many segments of track a streamed track keeps behind the last ball before despawning it.
+const STREAM_BEHIND_SEGMENTS: usize = 100;
+
+/// The mesh of a streamed piece of track, left on its collider entity for anything that renders
+/// the track.
+#[derive(Component)]
+pub struct StreamedTrackMesh(pub Mesh);
+
+/// Builds a long track a piece at a time as the balls progress along it, spawning the pieces
+/// ahead of the leading ball and despawning those far behind the last, so that neither startup
+/// nor memory grows with the length of the track.
+///
+/// The sections of the whole path are generated up front, which is cheap, so that the pieces line
+/// up exactly with the track that [`Mesh::from`] would build.
+pub struct TrackStreamer {
+    path: HalfCylinderPath,
+    /// See [`HalfCylinderPath::collider_path`].
+    collider_path: Option<HalfCylinderPath>,
+    sections: Vec<PathSection>,
+    /// The first segment not yet spawned.
+    next_segment: usize,
+    /// The segments and collider entities of each spawned piece still in the world, in order.
+    chunks: VecDeque<(Range<usize>, Entity)>,
+}
+
+impl TrackStreamer {
+    pub fn new(path: HalfCylinderPath) -> Self {
+        let sections = path.sections();
+        Self {
+            collider_path: path.collider_path(),
+            path,
+            sections,
+            next_segment: 0,
+            chunks: VecDeque::new(),
+        }
+    }
+
+    /// The segments and mesh of the next piece of the track, with the coarser mesh to build its
+    /// collider from if there is one, or `None` if the whole track has been built.
+    pub fn next_chunk(&mut self) -> Option<(Range<usize>, Mesh, Option<Mesh>)> {
+        let n_segments = self.sections.len().saturating_sub(1);
+        if self.next_segment >= n_segments {
+            return None;
+        }
+        let segments =
+            self.next_segment..(self.next_segment + STREAM_CHUNK_SEGMENTS).min(n_segments);
+        self.next_segment = segments.end;
+        let mesh = self.path.segments_mesh(&self.sections, segments.clone());
+        let collider_mesh = self
+            .collider_path
+            .as_ref()
+            .map(|path| path.segments_mesh(&self.sections, segments.clone()));
+        Some((segments, mesh, collider_mesh))
+    }
+
+    /// The segment of the track nearest to `position`.
+    pub fn nearest_segment(&self, position: Vec3) -> usize {
+        nearest_segment(&self.sections, position)
+    }
+}
+
+/// The segment of the track with `sections` that starts or ends nearest to `position`.
+pub fn nearest_segment(sections: &[PathSection], position: Vec3) -> usize {
+    sections
+        .iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| {
+            a.position
+                .distance_squared(position)
+                .partial_cmp(&b.position.distance_squared(position))
+                .unwrap_or(Ordering::Equal)
+        })
+        .map_or(0, |(index, _)| index.min(sections.len().saturating_sub(2)))
+}
+
+/// The sections of the current track, for finding where balls are relative to it.
+pub struct TrackSections(pub Vec<PathSection>);
+
+/// Spawns `collider` for a piece of track of `kind`, returning the entity holding it.
+pub fn spawn_track_collider(
+    commands: &mut Commands,
+    collider: ColliderShape,
+    kind: SegmentKind,
+) -> Entity {
+    let entity = spawn_halfpipe_segment(
+        commands,
+        collider,
+        kind.collider_material(),
+        Vec3::ZERO,
+        Quat::IDENTITY,
+    );
+    let active_events = kind.active_events();
+    if !active_events.is_empty() {
+        commands
+            .entity(entity)
+            .insert(ColliderFlagsComponent::from(ColliderFlags {
+                active_events,
+                ..Default::default()
+            }));
+    }
+    entity
+}
+
+/// Spawns the next piece of a streamed track once the leading ball gets near its end, and
+/// despawns pieces that every ball has left far behind.
+pub fn stream_track(
+    mut commands: Commands,
+    streamer: Option<ResMut<TrackStreamer>>,
+    collider_kind: Res<ColliderKind>,
+    balls: Query<&GlobalTransform, With<Ball>>,
+    parents: Query<&Parent>,
+) {
+    let mut streamer = match streamer {
+        Some(streamer) => streamer,
+        None => return,
+    };
+    let (rearmost, foremost) = balls
+        .iter()
+        .map(|transform| streamer.nearest_segment(transform.translation))
+        .fold(None, |range: Option<(usize, usize)>, segment| {
+            Some(range.map_or((segment, segment), |(rearmost, foremost)| {
+                (rearmost.min(segment), foremost.max(segment))
+            }))
+        })
+        .unwrap_or((0, 0));
+
+    // One piece per frame at most, to spread the cost of building them
+    if streamer.next_segment < foremost + STREAM_AHEAD_SEGMENTS {
+        if let Some((segments, mesh, collider_mesh)) = streamer.next_chunk() {
+            // The first piece was built the same way, so this is not expected to fail
+            match mesh_to_collider(collider_mesh.as_ref().unwrap_or(&mesh), &collider_kind) {
+                Ok(collider) => {
+                    let entity = spawn_track_collider(&mut commands, collider, SegmentKind::Normal);
+                    commands.entity(entity).insert(StreamedTrackMesh(mesh));
+                    streamer.chunks.push_back((segments, entity));
+                }
+                Err(e) => warn!(
+                    "Failed to build collider for segments {:?}: {}",
+                    segments, e
+                ),
+            }
+        }
+    }
+
+    while let Some((segments, entity)) = streamer.chunks.front() {
+        if segments.end + STREAM_BEHIND_SEGMENTS >= rearmost {
+            break;
+        }
+        // Despawn the rigid body along with the collider that is its child
+        let body = parents.get(*entity).map_or(*entity, |parent| parent.0);
+        commands.entity(body).despawn_recursive();
+        streamer.chunks.pop_front();
+    }
+}
+
+/// Why [`spawn_track`] could not spawn a track.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TrackError {
+    Path(PathError),
+    Collider(MeshColliderError),
+}
+
+impl std::fmt::Display for TrackError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Path(e) => write!(f, "invalid track path: {}", e),
+            Self::Collider(e) => write!(f, "failed to build track collider: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for TrackError {}
+
+impl From<PathError> for TrackError {
+    fn from(e: PathError) -> Self {
+        Self::Path(e)
+    }
+}
+
+impl From<MeshColliderError> for TrackError {
+    fn from(e: MeshColliderError) -> Self {
+        Self::Collider(e)
+    }
+}
+
+/// What a piece of track built by [`spawn_track`] is of the whole.
+enum TrackPiece {
+    Whole,
+    Segment(usize),
+    /// The first piece of a streamed track, made of these segments.
+    Chunk(Range<usize>),
+}
+
+/// Spawns the physics for the track along `path`, returning the entities holding its colliders,
+/// with their meshes and kinds, so that the caller can attach anything needed to render them.
+///
+/// Colliders are built from [`HalfCylinderPath::collider_path`] if it differs, while the returned
+/// meshes are always those of `path`.
+///
+/// If `race_config` gives any segments a special kind, the track is split into one collider per
+/// segment so that each can have its own material. Otherwise the whole track is one collider,
+/// unless it is an open track longer than [`STREAM_MIN_SEGMENTS`]. Then only its first piece is
+/// spawned, and a [`TrackStreamer`] is inserted to spawn the rest with [`stream_track`].
+///
+/// Nothing is spawned for a path that fails [`HalfCylinderPath::validate`], or if any of the
+/// colliders cannot be built.
+pub fn spawn_track(
+    commands: &mut Commands,
+    path: HalfCylinderPath,
+    collider_kind: &ColliderKind,
+    race_config: &RaceConfig,
+) -> Result<Vec<(Entity, Mesh, SegmentKind)>, TrackError> {
+    path.validate()?;
+    let uniform = race_config.bouncy_segments.is_empty()
+        && race_config.boost_segments.is_empty()
+        && race_config.slow_segments.is_empty();
+    let to_collider = |mesh: &Mesh, collider_mesh: Option<&Mesh>| {
+        mesh_to_collider(collider_mesh.unwrap_or(mesh), collider_kind)
+    };
+
+    // The colliders are all built before anything is spawned, so that nothing is left behind if
+    // one of them fails
+    let collider_path = path.collider_path();
+    let mut streamer = None;
+    let pieces = if uniform && !path.closed && path.n_segments > STREAM_MIN_SEGMENTS {
+        // The first piece is spawned straight away so that there is track under the balls
+        let mut track_streamer = TrackStreamer::new(path.clone());
+        let mut pieces = Vec::new();
+        if let Some((range, mesh, collider_mesh)) = track_streamer.next_chunk() {
+            let collider = to_collider(&mesh, collider_mesh.as_ref())?;
+            pieces.push((
+                TrackPiece::Chunk(range),
+                mesh,
+                collider,
+                SegmentKind::Normal,
+            ));
+        }
+        streamer = Some(track_streamer);
+        pieces
+    } else if uniform {
+        let collider_mesh = collider_path.map(Mesh::from);
+        let mesh = Mesh::from(path.clone());
+        let collider = to_collider(&mesh, collider_mesh.as_ref())?;
+        vec![(TrackPiece::Whole, mesh, collider, SegmentKind::Normal)]
+    } else {
+        let mut collider_meshes = collider_path.map(|path| path.into_segment_meshes().into_iter());
+        path.clone()
+            .into_segment_meshes()
+            .into_iter()
+            .map(|mesh| (mesh, collider_meshes.as_mut().and_then(Iterator::next)))
+            .enumerate()
+            // Gap segments have no triangles, and so nothing to spawn
+            .filter(|(_, (mesh, _))| {
+                mesh.indices()
+                    .map_or(false, |indices| !index_list(indices).is_empty())
+            })
+            .map(|(index, (mesh, collider_mesh))| {
+                let collider = to_collider(&mesh, collider_mesh.as_ref())?;
+                let kind = race_config.segment_kind(index);
+                Ok((TrackPiece::Segment(index), mesh, collider, kind))
+            })
+            .collect::<Result<Vec<_>, MeshColliderError>>()?
+    };
+
+    let length = path.length();
+    let sections = path.sections();
+    let finish = *sections
+        .last()
+        .expect("Track path must have at least one section");
+    let centers = sections
+        .iter()
+        .map(|section| section.position)
+        .collect::<Vec<_>>();
+    commands.insert_resource(TrackPreview::new(&centers));
+    commands.insert_resource(TrackInfo::new(length, centers));
+    commands.insert_resource(TrackSections(sections.clone()));
+    let finish_line = spawn_track_sensor(commands, finish);
+    commands.entity(finish_line).insert(FinishLine {
+        normal: finish.forward,
+    });
+    for index in 0..N_CHECKPOINTS {
+        let section = sections[(index + 1) * (sections.len() - 1) / (N_CHECKPOINTS + 1)];
+        let checkpoint = spawn_track_sensor(commands, section);
+        commands.entity(checkpoint).insert(Checkpoint { index });
+    }
+    for &(index, acceleration) in &race_config.wind_zones {
+        if index + 1 < sections.len() {
+            let zone = spawn_segment_sensor(commands, sections[index], sections[index + 1]);
+            commands.entity(zone).insert(WindZone { acceleration });
+        }
+    }
+    spawn_obstacles(commands, &path, &sections);
+    spawn_hazards(commands, &path, &sections);
+
+    let mut segments = Vec::with_capacity(pieces.len());
+    for (piece, mesh, collider, kind) in pieces {
+        let entity = spawn_track_collider(commands, collider, kind);
+        match piece {
+            TrackPiece::Whole => {}
+            TrackPiece::Segment(index) => {
+                commands.entity(entity).insert(TrackSegment { index, kind });
+            }
+            TrackPiece::Chunk(range) => {
+                if let Some(streamer) = streamer.as_mut() {
+                    streamer.chunks.push_back((range, entity));
+                }
+            }
+        }
+        segments.push((entity, mesh, kind));
+    }
+    let bounds = if let Some(streamer) = streamer {
+        commands.insert_resource(streamer);
+        // The bounds of the pieces of track are not all known up front, so bound the tube
+        // around every section instead
+        let margin = Vec3::splat(
+            sections
+                .iter()
+                .map(|section| section.radius + path.rail_height)
+                .fold(0.0, f32::max),
+        );
+        sections
+            .iter()
+            .map(|section| TrackBounds {
+                min: section.position - margin,
+                max: section.position + margin,
+            })
+            .reduce(TrackBounds::union)
+    } else {
+        commands.remove_resource::<TrackStreamer>();
+        segments
+            .iter()
+            .filter_map(|(_, mesh, _)| mesh.compute_aabb())
+            .map(|aabb| TrackBounds {
+                min: aabb.min().into(),
+                max: aabb.max().into(),
+            })
+            .reduce(TrackBounds::union)
+    };
+    if let Some(bounds) = bounds {
+        commands.insert_resource(bounds);
+    }
+    Ok(segments)
+}
+
+fn isometry(translation: Vec3, rotation: Quat) -> Isometry3<f32> {
+    let (axis, angle) = rotation.to_axis_angle();
+    Isometry3::new(
+        Vector3::new(translation.x, translation.y, translation.z),
+        Vector3::new(axis.x, axis.y, axis.z) * angle,
+    )
+}
+
+/// Spawns a thin sensor across the opening of the track at `section`, large enough to cover the
+/// whole tube.
+pub fn spawn_track_sensor(commands: &mut Commands, section: PathSection) -> Entity {
+    spawn_sensor(commands, section, 1.0)
+}
+
+/// A volume of the track that pushes the balls within it with `acceleration`.
+#[derive(Component)]
+pub struct WindZone {
+    pub acceleration: Vec3,
+}
+
+/// Spawns a sensor filling the tube between the `start` and `end` sections of a segment.
+fn spawn_segment_sensor(commands: &mut Commands, start: PathSection, end: PathSection) -> Entity {
+    let middle = PathSection {
+        position: start.position.lerp(end.position, 0.5),
+        forward: (end.position - start.position).normalize_or_zero(),
+        up: start.up.lerp(end.up, 0.5).normalize_or_zero(),
+        radius: start.radius.max(end.radius),
+    };
+    spawn_sensor(
+        commands,
+        middle,
+        0.5 * start.position.distance(end.position),
+    )
+}
+
+/// Spawns a sensor box across the track at `section`, large enough to cover the whole tube, and
+/// extending `half_depth` along the track either side of it.
+fn spawn_sensor(commands: &mut Commands, section: PathSection, half_depth: f32) -> Entity {
+    let radius = section.radius;
+    let right = section.up.cross(-section.forward).normalize_or_zero();
+    let up = (-section.forward).cross(right);
+    let rotation = Quat::from_mat3(&Mat3::from_cols(right, up, -section.forward));
+    let transform = Transform {
+        translation: section.position,
+        rotation,
+        ..Default::default()
+    };
+    commands
+        .spawn_bundle(ColliderBundle {
+            collider_type: ColliderType::Sensor.into(),
+            shape: ColliderShape::cuboid(radius, radius, half_depth).into(),
+            position: ColliderPosition(isometry(section.position, rotation)).into(),
+            flags: ColliderFlags {
+                active_events: ActiveEvents::INTERSECTION_EVENTS,
+                ..Default::default()
+            }
+            .into(),
+            ..Default::default()
+        })
+        .insert_bundle((GameLevel, transform, GlobalTransform::from(transform)))
+        .id()
+}
+
+pub fn spawn_halfpipe_segment(
+    commands: &mut Commands,
+    collider_shape: ColliderShape,
+    material: ColliderMaterial,
+    translation: Vec3,
+    rotation: Quat,
+) -> Entity {
+    let position = isometry(translation, rotation);
+    let transform = Transform {
+        translation,
+        rotation,
+        ..Default::default()
+    };
+    let mut collider = None;
+    commands
+        .spawn_bundle(RigidBodyBundle {
+            body_type: RigidBodyType::Static.into(),
+            position: RigidBodyPosition {
+                position,
+                next_position: position,
+            }
+            .into(),
+            ..Default::default()
+        })
+        .insert_bundle((
+            RigidBodyPositionSync::Discrete,
+            GameLevel,
+            transform,
+            GlobalTransform::from(transform),
+        ))
+        .with_children(|builder| {
+            let mut entity = builder.spawn_bundle(ColliderBundle {
+                shape: collider_shape.into(),
+                material: material.into(),
+                ..Default::default()
+            });
+            entity.insert_bundle((
+                ColliderPositionSync::Discrete,
+                Track,
+                Transform::default(),
+                GlobalTransform::default(),
+            ));
+            collider = Some(entity.id());
+        });
+    collider.unwrap()
+}
+
+pub const N_PLAYERS: usize = 10;
+
+#[derive(Clone)]
+pub struct BallInfo {
+    pub name: Cow<'static, str>,
+    pub color: Color,
+}
+
+pub const BALL_INFO: [BallInfo; N_PLAYERS] = [
+    BallInfo {
+        name: Cow::Borrowed("RED"),
+        color: Color::RED,
+    },
+    BallInfo {
+        name: Cow::Borrowed("ORANGE"),
+        color: Color::ORANGE,
+    },
+    BallInfo {
+        name: Cow::Borrowed("YELLOW"),
+        color: Color::YELLOW,
+    },
+    BallInfo {
+        name: Cow::Borrowed("GREEN"),
+        color: Color::GREEN,
+    },
+    BallInfo {
+        name: Cow::Borrowed("BLUE"),
+        color: Color::BLUE,
+    },
+    BallInfo {
+        name: Cow::Borrowed("INDIGO"),
+        color: Color::INDIGO,
+    },
+    BallInfo {
+        name: Cow::Borrowed("VIOLET"),
+        color: Color::VIOLET,
+    },
+    BallInfo {
+        name: Cow::Borrowed("WHITE"),
+        color: Color::WHITE,
+    },
+    BallInfo {
+        name: Cow::Borrowed("DARK_GRAY"),
+        color: Color::DARK_GRAY,
+    },
+    BallInfo {
+        name: Cow::Borrowed("BLACK"),
+        color: Color::BLACK,
+    },
+];
+
+/// Names of the hues of generated ball colors, each covering 30 degrees centered on its own hue.
+const HUE_NAMES: [&str; 12] = [
+    "RED", "ORANGE", "YELLOW", "LIME", "GREEN", "SPRING", "CYAN", "AZURE", "BLUE", "PURPLE",
+    "MAGENTA", "ROSE",
+];
+/// Lightnesses of successive bands of generated ball colors. Each band holds up to
+/// [`HUE_NAMES`]`.len()` colors, so that hues in a band stay far enough apart to tell apart. Most
+/// of [`BALL_INFO`] is of lightness 0.5, so the bands are darker or lighter than that.
+const PALETTE_LIGHTNESS: [f32; 3] = [0.35, 0.6, 0.7];
+
+/// Names and colors for `n` balls: the named set of [`BALL_INFO`] first, then generated colors.
+/// Generated hues are evenly spaced in bands of differing lightness, with each band's hues offset
+/// from those of the bands before it. Generated names are those of their hue, numbered by player.
+pub fn generate_ball_palette(n: usize) -> Vec<BallInfo> {
+    let n_generated = n.saturating_sub(BALL_INFO.len());
+    let n_bands = ((n_generated + HUE_NAMES.len() - 1) / HUE_NAMES.len()).max(1);
+    let hues_per_band = ((n_generated + n_bands - 1) / n_bands).max(1);
+    BALL_INFO
+        .iter()
+        .cloned()
+        .chain((0..n_generated).map(|i| {
+            let band = i / hues_per_band;
+            let hue = 360.0 * ((i % hues_per_band) as f32 + band as f32 / n_bands as f32)
+                / hues_per_band as f32;
+            let hue_name = HUE_NAMES[((hue + 15.0) / 30.0) as usize % HUE_NAMES.len()];
+            BallInfo {
+                name: Cow::Owned(format!("{} {}", hue_name, BALL_INFO.len() + i + 1)),
+                color: Color::hsl(hue, 1.0, PALETTE_LIGHTNESS[band % PALETTE_LIGHTNESS.len()]),
+            }
+        }))
+        .take(n)
+        .collect()
+}
+
+/// Maximum length, in characters, of a player-entered name.
+pub const MAX_NAME_LENGTH: usize = 12;
+
+/// Strips control characters and surrounding whitespace from a player-entered name, and cuts it
+/// down to [`MAX_NAME_LENGTH`] characters, so that it cannot break the layout of the UI text it is
+/// shown in.
+pub fn sanitize_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| !c.is_control())
+        .collect::<String>()
+        .trim()
+        .chars()
+        .take(MAX_NAME_LENGTH)
+        .collect::<String>()
+        .trim_end()
+        .to_string()
+}
+
+/// Keys with which a human player nudges their ball, relative to the direction of the race, which
+/// runs towards -z.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NudgeKeys {
+    pub left: KeyCode,
+    pub right: KeyCode,
+    pub forward: KeyCode,
+    pub back: KeyCode,
+}
+
+/// The keys of each human player, in order, chosen so as not to clash with the camera controls.
+pub const NUDGE_KEYS: [NudgeKeys; 3] = [
+    NudgeKeys {
+        left: KeyCode::Left,
+        right: KeyCode::Right,
+        forward: KeyCode::Up,
+        back: KeyCode::Down,
+    },
+    NudgeKeys {
+        left: KeyCode::J,
+        right: KeyCode::L,
+        forward: KeyCode::I,
+        back: KeyCode::K,
+    },
+    NudgeKeys {
+        left: KeyCode::Numpad4,
+        right: KeyCode::Numpad6,
+        forward: KeyCode::Numpad8,
+        back: KeyCode::Numpad5,
+    },
+];
+
+pub struct RaceConfig {
+    pub n_players: usize,
+    pub balls: Vec<BallInfo>,
+    /// Indices of the track segments that are bounce pads.
+    pub bouncy_segments: Vec<usize>,
+    /// Indices of the track segments that are boost pads.
+    pub boost_segments: Vec<usize>,
+    /// Speed added to a ball, in its direction of travel, when it touches a boost pad.
+    pub boost_strength: f32,
+    /// Indices of the track segments that are slow zones.
+    pub slow_segments: Vec<usize>,
+    /// Linear damping of balls while they touch a slow zone.
+    pub slow_linear_damping: f32,
+    /// Angular damping of balls while they touch a slow zone.
+    pub slow_angular_damping: f32,
+    /// Number of laps of a closed track to race, or `None` to race once from start to finish.
+    pub target_laps: Option<u32>,
+    /// The keys of the players whose balls are nudged by humans, by player index. Every other ball
+    /// is left to physics alone.
+    pub nudge_keys: HashMap<usize, NudgeKeys>,
+    /// Acceleration of a ball being nudged, kept well below gravity so that a nudge can steer a
+    /// ball but not lift it.
+    pub nudge_acceleration: f32,
+    /// Indices of the track segments that are wind zones, each with the acceleration it adds to
+    /// the balls within it, as if the wind blew or gravity pulled another way.
+    pub wind_zones: Vec<(usize, Vec3)>,
+}
+
+impl RaceConfig {
+    pub fn new(n_players: usize) -> Self {
+        Self {
+            n_players,
+            balls: generate_ball_palette(n_players),
+            bouncy_segments: Vec::new(),
+            boost_segments: Vec::new(),
+            boost_strength: 20.0,
+            slow_segments: Vec::new(),
+            slow_linear_damping: 1.0,
+            slow_angular_damping: 1.0,
+            target_laps: None,
+            nudge_keys: HashMap::default(),
+            nudge_acceleration: 2.0,
+            wind_zones: Vec::new(),
+        }
+    }
+
+    /// Names the balls of the first entrants after `names`, in order. Names are sanitized, and
+    /// entrants whose name is empty once sanitized keep the default name from [`BALL_INFO`].
+    pub fn set_names(&mut self, names: &[String]) {
+        let defaults = Self::new(self.n_players).balls;
+        for (i, (ball, default)) in self.balls.iter_mut().zip(defaults).enumerate() {
+            let name = names
+                .get(i)
+                .map(|name| sanitize_name(name))
+                .unwrap_or_default();
+            ball.name = if name.is_empty() {
+                default.name
+            } else {
+                Cow::Owned(name)
+            };
+        }
+    }
+
+    /// Changes the number of players to `n_players`, keeping the names of those that remain.
+    pub fn set_n_players(&mut self, n_players: usize) {
+        let mut balls = Self::new(n_players).balls;
+        for (ball, old) in balls.iter_mut().zip(&self.balls) {
+            ball.name = old.name.clone();
+        }
+        self.n_players = n_players;
+        self.balls = balls;
+    }
+
+    pub fn segment_kind(&self, index: usize) -> SegmentKind {
+        if self.bouncy_segments.contains(&index) {
+            SegmentKind::Bouncy
+        } else if self.boost_segments.contains(&index) {
+            SegmentKind::Boost
+        } else if self.slow_segments.contains(&index) {
+            SegmentKind::SlowZone
+        } else {
+            SegmentKind::Normal
+        }
+    }
+}
+
+impl Default for RaceConfig {
+    fn default() -> Self {
+        Self::new(N_PLAYERS)
+    }
+}
+
+pub struct PlayerState {
+    pub name: String,
+    pub color: Color,
+    pub entity: Option<Entity>,
+    pub spawn_point: Vec3,
+    /// Race clock time at which the player's ball is spawned, after their start delay.
+    pub start_secs: f32,
+    /// Race clock time at which the player finished or dropped out.
+    pub end_secs: Option<f32>,
+    /// The z coordinate the player's ball has reached. Balls travel towards -z, so this becomes
+    /// more negative as they progress, and the most negative distance is the furthest along.
+    pub distance: f32,
+    /// How far along the track the player's ball is, on its current lap in lap races. See
+    /// [`TrackInfo::progress`].
+    pub progress: f32,
+    pub finished: bool,
+    /// When the player first passed through each checkpoint, or `None` if they have not, including
+    /// if they flew over it.
+    pub splits: Vec<Option<f32>>,
+    /// Number of laps completed, in lap races.
+    pub laps: u32,
+    /// Whether the player has passed the last checkpoint since they last crossed the finish line.
+    /// In lap races, a crossing only counts as a lap when this is set, so that the crossing of a
+    /// ball spawned across the line does not count, nor does cutting back over it.
+    pub passed_last_checkpoint: bool,
+}
+
+impl PlayerState {
+    pub fn new(name: String, color: Color, spawn_point: Vec3, start_secs: f32) -> Self {
+        Self {
+            name,
+            color,
+            entity: None,
+            spawn_point,
+            start_secs,
+            end_secs: None,
+            distance: 0.0,
+            progress: 0.0,
+            finished: false,
+            splits: vec![None; N_CHECKPOINTS],
+            laps: 0,
+            passed_last_checkpoint: false,
+        }
+    }
+}
+
+/// Seconds of race time since the current round began, including the countdown. It only advances
+/// while the round is being played, so paused time counts neither towards race times nor start
+/// delays.
+#[derive(Default)]
+pub struct RaceClock {
+    pub elapsed: f32,
+    /// Seconds by which the clock advanced in the current frame.
+    pub delta: f32,
+}
+
+impl RaceClock {
+    pub fn reset(&mut self) {
+        self.elapsed = 0.0;
+        self.delta = 0.0;
+    }
+
+    pub fn tick(&mut self, delta: f32) {
+        self.delta = delta;
+        self.elapsed += delta;
+    }
+}
+
+pub const MIN_TIME_SCALE: f32 = 0.1;
+pub const MAX_TIME_SCALE: f32 = 4.0;
+
+/// How fast race time passes relative to real time, for slow motion and fast forward. Physics and
+/// the race clock are scaled alike, so race times are the same whatever the scale.
+pub struct TimeScale(pub f32);
+
+impl Default for TimeScale {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+impl TimeScale {
+    pub fn set(&mut self, scale: f32) {
+        self.0 = scale.clamp(MIN_TIME_SCALE, MAX_TIME_SCALE);
+    }
+}
+
+/// Distance from the finish line within which a ball is about to finish.
+pub const PHOTO_FINISH_DISTANCE: f32 = 3.0;
+pub const PHOTO_FINISH_TIME_SCALE: f32 = 0.25;
+
+/// Slow motion for a close finish, while two or more balls are about to cross the finish line.
+#[derive(Default)]
+pub struct PhotoFinish {
+    pub active: bool,
+    /// The time scale from before the slow motion, to go back to afterwards.
+    pub previous_scale: f32,
+}
+
+/// Whether two or more balls still in the race are within [`PHOTO_FINISH_DISTANCE`] of the finish
+/// line at `finish_z`.
+pub fn is_photo_finish(round: &RoundState, finish_z: f32) -> bool {
+    round
+        .players
+        .iter()
+        .filter(|player| {
+            player.entity.is_some()
+                && player.end_secs.is_none()
+                && (player.distance - finish_z).abs() <= PHOTO_FINISH_DISTANCE
+        })
+        .count()
+        >= 2
+}
+
+/// Slows time down for a photo finish, and back to the previous speed once the balls have crossed
+/// the line. In lap races, balls pass the line on every lap, so there are no photo finishes.
+pub fn detect_photo_finish(
+    track_info: Option<Res<TrackInfo>>,
+    race_config: Res<RaceConfig>,
+    round: Res<RoundState>,
+    mut photo_finish: ResMut<PhotoFinish>,
+    mut time_scale: ResMut<TimeScale>,
+) {
+    let close = match track_info {
+        Some(track_info) if race_config.target_laps.is_none() => {
+            is_photo_finish(&round, track_info.finish_z)
+        }
+        _ => false,
+    };
+    if close && !photo_finish.active {
+        photo_finish.active = true;
+        photo_finish.previous_scale = time_scale.0;
+        time_scale.set(PHOTO_FINISH_TIME_SCALE);
+        info!("Photo finish!");
+    } else if !close && photo_finish.active {
+        end_photo_finish(&mut photo_finish, &mut time_scale);
+    }
+}
+
+fn end_photo_finish(photo_finish: &mut PhotoFinish, time_scale: &mut TimeScale) {
+    photo_finish.active = false;
+    time_scale.set(photo_finish.previous_scale);
+}
+
+/// Ends any photo finish still going when the round ends, so that the next is not in slow motion.
+pub fn reset_photo_finish(
+    mut photo_finish: ResMut<PhotoFinish>,
+    mut time_scale: ResMut<TimeScale>,
+) {
+    if photo_finish.active {
+        end_photo_finish(&mut photo_finish, &mut time_scale);
+    }
+}
+
+/// Sets the gravity of the physics to that of the [`GameConfig`], so that a change to it takes
+/// effect from the next round.
+pub fn apply_gravity(game_config: Res<GameConfig>, mut rapier_config: ResMut<RapierConfiguration>) {
+    rapier_config.gravity = -game_config.gravity * Vector3::y();
+}
+
+/// Scales the time that physics simulates each frame by the [`TimeScale`].
+pub fn apply_time_scale(
+    time: Res<Time>,
+    time_scale: Res<TimeScale>,
+    deterministic_mode: Option<Res<DeterministicMode>>,
+    mut integration_parameters: ResMut<IntegrationParameters>,
+    mut sim_to_render_time: ResMut<SimulationToRenderTime>,
+) {
+    match deterministic_mode {
+        // One step is taken per frame, so each step simulates a scaled time
+        Some(mode) => integration_parameters.dt = mode.dt * time_scale.0,
+        // Steps are taken to catch up with the time that has passed, so hold back or bring forward
+        // the time that they need to catch up with
+        None => sim_to_render_time.diff -= (1.0 - time_scale.0) * time.delta_seconds(),
+    }
+}
+
+/// Advances the race clock and the countdown together, by the scaled time. In
+/// [`DeterministicMode`], the clock advances by one physics step per frame, however long the frame
+/// took.
+pub fn tick_race_clock(
+    time: Res<Time>,
+    time_scale: Option<Res<TimeScale>>,
+    deterministic_mode: Option<Res<DeterministicMode>>,
+    mut clock: ResMut<RaceClock>,
+    mut countdown: ResMut<CountdownTimer>,
+) {
+    let delta = deterministic_mode.map_or_else(|| time.delta_seconds(), |mode| mode.dt)
+        * time_scale.map_or(1.0, |time_scale| time_scale.0);
+    clock.tick(delta);
+    countdown.0.tick(Duration::from_secs_f32(delta));
+}
+
+#[derive(Default)]
+pub struct RoundState {
+    /// Race clock time at which the race begins, once the countdown is over.
+    pub start_secs: f32,
+    pub players: Vec<PlayerState>,
+}
+
+impl RoundState {
+    /// Whether `entity` is the ball of `player` and still in play. Once a player has finished or
+    /// dropped out their ball is no longer in play, even though its despawn may not have been
+    /// applied yet, so systems running later in the same frame should leave it alone.
+    pub fn ball_in_play(&self, player: usize, entity: Entity) -> bool {
+        self.players.get(player).map_or(false, |player| {
+            player.entity == Some(entity) && player.end_secs.is_none()
+        })
+    }
+
+    /// How many seconds `player` trailed the first player through the last checkpoint that `player`
+    /// has passed through.
+    pub fn split_delta(&self, player: usize) -> Option<f32> {
+        let (checkpoint, split) = self.players[player]
+            .splits
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(i, split)| split.map(|split| (i, split)))?;
+        let leader = self
+            .players
+            .iter()
+            .filter_map(|player| player.splits.get(checkpoint).copied().flatten())
+            .reduce(f32::min)?;
+        Some(split - leader)
+    }
+
+    /// What the leaderboard shows for `player`: their time if they have finished, and otherwise how
+    /// far along they are, with the lap they are on in lap races and how far they trail the leader
+    /// at the last checkpoint. Without a track, how far along is the distance from the spawn point.
+    pub fn standing_text(
+        &self,
+        player: usize,
+        target_laps: Option<u32>,
+        track_info: Option<&TrackInfo>,
+    ) -> String {
+        let PlayerState {
+            distance,
+            progress,
+            end_secs,
+            finished,
+            laps,
+            ..
+        } = self.players[player];
+        if finished {
+            return format!("{:5.3}s", end_secs.unwrap() - self.start_secs);
+        }
+        let dnf = if end_secs.is_some() { "DNF " } else { "" };
+        let progress = match track_info {
+            Some(_) => format!("{}{:5.1}%", dnf, 100.0 * progress),
+            // Balls travel towards -z
+            None => format!("{}{:5.1}m", dnf, SPAWN_POSITION.z - distance),
+        };
+        let progress = match target_laps {
+            Some(target_laps) => format!(
+                "L{}/{} {}",
+                (laps + 1).min(target_laps),
+                target_laps,
+                progress
+            ),
+            None => progress,
+        };
+        match self.split_delta(player) {
+            Some(delta) if end_secs.is_none() => format!("{} +{:.2}s", progress, delta),
+            _ => progress,
+        }
+    }
+}
+
+fn cmp_secs(a: f32, b: f32) -> Ordering {
+    a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+}
+
+/// Indices of the players of `round` in order from first to last, so that the race leader is
+/// first. Finished players are ranked by finishing time, ahead of everyone else, who are ranked by
+/// laps completed and then by how far along the track they are on their lap, as measured by
+/// [`TrackInfo::progress`] and then by distance.
+pub fn rank_players(round: &RoundState) -> Vec<usize> {
+    let mut order = (0..round.players.len()).collect::<Vec<_>>();
+    order.sort_by(|&a, &b| {
+        let (a, b) = (&round.players[a], &round.players[b]);
+        b.finished
+            .cmp(&a.finished)
+            .then_with(|| match (a.end_secs, b.end_secs) {
+                (Some(a_end), Some(b_end)) if a.finished && b.finished => cmp_secs(a_end, b_end),
+                _ => Ordering::Equal,
+            })
+            .then_with(|| b.laps.cmp(&a.laps))
+            .then_with(|| {
+                b.progress
+                    .partial_cmp(&a.progress)
+                    .unwrap_or(Ordering::Equal)
+            })
+            // Ascending distance, as the most negative distance is the furthest along
+            .then_with(|| {
+                a.distance
+                    .partial_cmp(&b.distance)
+                    .unwrap_or(Ordering::Equal)
+            })
+            .then_with(|| {
+                cmp_secs(
+                    a.end_secs.unwrap_or(round.start_secs),
+                    b.end_secs.unwrap_or(round.start_secs),
+                )
+            })
+    });
+    order
+}
+
+pub const MAX_DISADVANTAGE_MS: u64 = 10000;
+pub const COUNTDOWN: Duration = Duration::from_secs(3);
+
+/// Counts down to the start of the race, before which no balls are spawned.
+pub struct CountdownTimer(pub Timer);
+
+impl Default for CountdownTimer {
+    fn default() -> Self {
+        Self(Timer::new(COUNTDOWN, false))
+    }
+}
+
+/// The placings of each player in the previous round of a series, from which start delays are
+/// handicapped. Only present when rounds are handicapped.
+#[derive(Default)]
+pub struct SeriesState {
+    /// The place of each player in the previous round, from 0 for the winner, or `None` before the
+    /// first round has been played. Players who did not finish share last place.
+    pub previous_placements: Option<Vec<usize>>,
+}
+
+/// The place of each player of `round`, from 0 for the winner. Players who did not finish share last
+/// place.
+pub fn placements(round: &RoundState) -> Vec<usize> {
+    let last = round.players.len().saturating_sub(1);
+    let mut placements = vec![last; round.players.len()];
+    for (place, player) in rank_players(round).into_iter().enumerate() {
+        if round.players[player].finished {
+            placements[player] = place;
+        }
+    }
+    placements
+}
+
+/// Start delays, in milliseconds, that handicap each player by their place in `placements`. The
+/// winner is delayed the most, by `max_delay_ms`, and last place is not delayed at all.
+pub fn handicap_delays(placements: &[usize], max_delay_ms: u64) -> Vec<u64> {
+    let last = placements.len().saturating_sub(1).max(1) as u64;
+    placements
+        .iter()
+        .map(|&place| max_delay_ms * (last - (place as u64).min(last)) / last)
+        .collect()
+}
+
+/// Offsets from [`SPAWN_POSITION`] at which `n_players` balls of `radius` can all spawn at once
+/// without overlapping. Each ball gets its own slot of the track's width, within `half_width` of
+/// its center, in shuffled order and jittered within the slot. If there are more balls than fit
+/// across the track, the slots continue in further rows ahead of the first.
+pub fn spawn_offsets(
+    n_players: usize,
+    half_width: f32,
+    radius: f32,
+    rng: &mut impl Rng,
+) -> Vec<Vec3> {
+    let diameter = 2.0 * radius;
+    let width = 2.0 * half_width.max(0.0);
+    let per_row = ((width / diameter) as usize).clamp(1, n_players.max(1));
+    let spacing = width / per_row as f32;
+    // Jittering each ball by less than half of the slack in its slot keeps neighbors apart
+    let jitter = 0.5 * (spacing - diameter).max(0.0);
+    let mut offsets = (0..n_players)
+        .map(|slot| {
+            let (row, column) = (slot / per_row, slot % per_row);
+            let x = -half_width + spacing * (column as f32 + 0.5);
+            let x = if jitter > 0.0 {
+                x + rng.gen_range(-jitter..jitter)
+            } else {
+                x
+            };
+            Vec3::new(x, 0.0, -1.0 - diameter * row as f32)
+        })
+        .collect::<Vec<_>>();
+    offsets.shuffle(rng);
+    offsets
+}
+
+pub fn record_placements(round: Res<RoundState>, mut series: ResMut<SeriesState>) {
+    series.previous_placements = Some(placements(&round));
+}
+
+/// Something that happened in a race, for anything that reports on races to follow.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RaceEvent {
+    RoundStarted,
+    /// A player's ball crossed the finish line, `secs` after the start of the round.
+    BallFinished {
+        player: usize,
+        secs: f32,
+    },
+    /// A player's ball left the race without finishing, having completed `progress` of the track,
+    /// from 0 to 1.
+    BallDnf {
+        player: usize,
+        progress: f32,
+    },
+    RoundEnded,
+}
+
+/// Logs each [`RaceEvent`].
+pub fn log_race_events(mut events: EventReader<RaceEvent>, round: Res<RoundState>) {
+    for event in events.iter() {
+        match *event {
+            RaceEvent::RoundStarted => info!("Starting the round!"),
+            RaceEvent::BallFinished { player, secs } => {
+                if let Some(player) = round.players.get(player) {
+                    info!(
+                        "{} finished in {:3.2}s ({:3.2}s)",
+                        player.name,
+                        secs,
+                        secs + round.start_secs - player.start_secs
+                    );
+                }
+            }
+            RaceEvent::BallDnf { player, progress } => {
+                if let Some(player) = round.players.get(player) {
+                    let end = player.end_secs.unwrap_or(round.start_secs);
+                    info!(
+                        "{} did not finish ({:2.1}% complete) in {:3.2}s ({:3.2}s)",
+                        player.name,
+                        100.0 * progress,
+                        end - round.start_secs,
+                        end - player.start_secs
+                    );
+                }
+            }
+            RaceEvent::RoundEnded => info!("The round is over"),
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn start_round(
+    mut round: ResMut<RoundState>,
+    mut clock: ResMut<RaceClock>,
+    mut countdown: ResMut<CountdownTimer>,
+    race_config: Res<RaceConfig>,
+    ball_physics: Res<BallPhysics>,
+    seed: Res<RaceSeed>,
+    game_config: Res<GameConfig>,
+    series: Option<Res<SeriesState>>,
+    start_seed: Option<Res<StartSeed>>,
+    mut race_events: EventWriter<RaceEvent>,
+) {
+    let margin = ball_physics.radius;
+    let spawn_radius = game_config.spawn_radius;
+    let mut rng = SmallRng::seed_from_u64(start_seed.map_or(seed.0, |start_seed| start_seed.0));
+    // The first round of a series, or one with a different number of players, has random delays
+    let handicaps = series
+        .and_then(|series| series.previous_placements.clone())
+        .filter(|placements| placements.len() == race_config.n_players)
+        .map(|placements| handicap_delays(&placements, game_config.max_disadvantage_ms));
+    let spawn_offsets = spawn_offsets(
+        race_config.n_players,
+        0.9 * spawn_radius - margin,
+        ball_physics.radius,
+        &mut rng,
+    );
+    clock.reset();
+    countdown.0.reset();
+    // The race, and all start delays, begin when the countdown ends
+    round.start_secs = countdown.0.duration().as_secs_f32();
+    round.players.clear();
+    let simultaneous = game_config.start_mode == StartMode::Simultaneous;
+    round.players = race_config
+        .balls
+        .iter()
+        .take(race_config.n_players)
+        .enumerate()
+        .map(|(i, ball_info)| {
+            PlayerState::new(
+                // Only the first ten balls can be selected with the number keys
+                if i < 10 {
+                    format!("{} ({})", ball_info.name, (i + 1) % 10)
+                } else {
+                    ball_info.name.to_string()
+                },
+                ball_info.color,
+                SPAWN_POSITION + spawn_offsets[i],
+                round.start_secs
+                    + Duration::from_millis(match &handicaps {
+                        _ if simultaneous => 0,
+                        Some(handicaps) => handicaps[i],
+                        None => rng.gen_range(0..game_config.max_disadvantage_ms.max(1)),
+                    })
+                    .as_secs_f32(),
+            )
+        })
+        .collect();
+    race_events.send(RaceEvent::RoundStarted);
+}
+
+/// Holds back the balls of a [`StartMode::Simultaneous`] start until the countdown ends.
+#[derive(Component)]
+pub struct StartGate;
+
+/// Thickness of the start gate.
+const START_GATE_THICKNESS: f32 = 0.1;
+
+/// Spawns the balls of players whose start delays have passed. In a simultaneous start, all balls
+/// are instead spawned when the countdown begins, behind a gate that is removed when it ends.
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_balls(
+    mut commands: Commands,
+    countdown: Res<CountdownTimer>,
+    clock: Res<RaceClock>,
+    ball_physics: Res<BallPhysics>,
+    ball_collision: Res<BallCollision>,
+    game_config: Res<GameConfig>,
+    gates: Query<Entity, With<StartGate>>,
+    mut round: ResMut<RoundState>,
+) {
+    let simultaneous = game_config.start_mode == StartMode::Simultaneous;
+    if countdown.0.finished() {
+        for entity in gates.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+    } else if !simultaneous {
+        return;
+    }
+    let now = clock.elapsed;
+    let mut spawned = false;
+    for (i, player) in round.players.iter_mut().enumerate() {
+        if player.entity.is_none()
+            && player.end_secs.is_none()
+            && (now > player.start_secs || simultaneous)
+        {
+            player.entity = Some(spawn_ball(
+                &mut commands,
+                i,
+                player.spawn_point,
+                &ball_physics,
+                &ball_collision,
+            ));
+            spawned = true;
+        }
+    }
+    if spawned && simultaneous && !countdown.0.finished() {
+        // Just ahead of the foremost ball, across the whole width of the track
+        let front = round
+            .players
+            .iter()
+            .map(|player| player.spawn_point.z)
+            .fold(SPAWN_POSITION.z, f32::min);
+        let z = front - ball_physics.radius - 0.5 * START_GATE_THICKNESS;
+        let radius = game_config.spawn_radius;
+        commands
+            .spawn_bundle(ColliderBundle {
+                shape: ColliderShape::cuboid(radius, radius, 0.5 * START_GATE_THICKNESS).into(),
+                position: ColliderPosition(isometry(
+                    Vec3::new(SPAWN_POSITION.x, SPAWN_POSITION.y, z),
+                    Quat::IDENTITY,
+                ))
+                .into(),
+                ..Default::default()
+            })
+            .insert_bundle((StartGate, GameLevel));
+    }
+}
+
+pub fn spawn_ball(
+    commands: &mut Commands,
+    player: usize,
+    spawn_point: Vec3,
+    ball_physics: &BallPhysics,
+    ball_collision: &BallCollision,
+) -> Entity {
+    commands
+        .spawn_bundle(RigidBodyBundle {
+            body_type: RigidBodyType::Dynamic.into(),
+            position: spawn_point.into(),
+            velocity: RigidBodyVelocity {
+                linvel: -1.0f32 * Vector3::z(),
+                ..Default::default()
+            }
+            .into(),
+            ccd: RigidBodyCcd {
+                ccd_enabled: true,
+                ..Default::default()
+            }
+            .into(),
+            ..Default::default()
+        })
+        .insert_bundle((
+            Ball { player },
+            RigidBodyPositionSync::Discrete,
+            Transform::from_translation(spawn_point),
+            GlobalTransform::from_translation(spawn_point),
+        ))
+        .with_children(|builder| {
+            builder
+                .spawn_bundle(ColliderBundle {
+                    shape: ColliderShape::ball(ball_physics.radius).into(),
+                    mass_properties: ColliderMassProps::Density(ball_physics.density).into(),
+                    material: ColliderMaterial {
+                        friction: ball_physics.friction,
+                        restitution: ball_physics.restitution,
+                        ..Default::default()
+                    }
+                    .into(),
+                    flags: ColliderFlags {
+                        collision_groups: ball_collision.interaction_groups(),
+                        solver_groups: ball_collision.interaction_groups(),
+                        ..Default::default()
+                    }
+                    .into(),
+                    ..Default::default()
+                })
+                .insert_bundle((
+                    ColliderPositionSync::Discrete,
+                    Transform::default(),
+                    GlobalTransform::default(),
+                ));
+        })
+        .id()
+}
+
+pub const BOUNDS: Vec3 = const_vec3!([0.0, -1000.0, f32::MIN]);
+pub const BOUNDS_MARGIN: Vec3 = const_vec3!([0.0, -SPAWN_RADIUS - 10.0, 0.0]);
+
+/// Speed above which a ball moves far enough in one physics step to risk passing through the thin
+/// track.
+const FAST_BALL_SPEED: f32 = 100.0;
+/// Maximum CCD substeps per physics step while any ball is moving fast.
+const FAST_BALL_CCD_SUBSTEPS: usize = 4;
+
+/// The offset of `point` from the nearest point on the middle of the tube of `segment` of the
+/// track with `sections`, and the radius and up direction of the tube there.
+fn tube_offset(sections: &[PathSection], segment: usize, point: Vec3) -> (Vec3, f32, Vec3) {
+    let (start, end) = (sections[segment], sections[segment + 1]);
+    let along = end.position - start.position;
+    let t = ((point - start.position).dot(along) / along.length_squared().max(f32::EPSILON))
+        .clamp(0.0, 1.0);
+    (
+        point - (start.position + along * t),
+        start.radius + (end.radius - start.radius) * t,
+        start.up.lerp(end.up, t),
+    )
+}
+
+/// If a ball that was at `last` in the previous frame has since passed through the floor of the
+/// tube of the track with `sections` to reach `ball`, the direction from it towards the middle of
+/// the tube and how far away the middle is.
+///
+/// A ball has passed through the floor if it was inside the tube, and is now in the lower half of
+/// the tube, outside it by at most `max_depth`. Balls that have flown out over the sides are
+/// further away, and balls that were already outside the tube, such as those falling past it,
+/// have not passed through it.
+fn passed_through_floor(
+    sections: &[PathSection],
+    last: Vec3,
+    ball: Vec3,
+    max_depth: f32,
+) -> Option<(Vec3, f32)> {
+    // The nearest section joins two segments. On a bend, a ball inside one of them can be outside
+    // the other, so the ball is taken to be in whichever it is nearer the middle of
+    let nearest = nearest_segment(sections, ball);
+    let segment = [nearest.saturating_sub(1), nearest]
+        .into_iter()
+        .map(|segment| (segment, tube_offset(sections, segment, ball).0.length()))
+        .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal))
+        .map_or(nearest, |(segment, _)| segment);
+    let (offset, radius, up) = tube_offset(sections, segment, ball);
+    let distance = offset.length();
+    let (last_offset, last_radius, _) = tube_offset(sections, segment, last);
+    if last_offset.length() > last_radius
+        || distance <= radius
+        || distance > radius + max_depth
+        || offset.dot(up) > 0.0
+    {
+        return None;
+    }
+    Some((-offset / distance, distance))
+}
+
+/// Guards against balls passing through the track. While any ball is moving fast, continuous
+/// collision detection takes more substeps. Any ball that has passed through the floor of the
+/// tube anyway, as found by [`passed_through_floor`] with the track between it and the middle of
+/// the tube, is moved back onto it, keeping its velocity. Gaps have no track to hit.
+#[allow(clippy::too_many_arguments)]
+pub fn prevent_tunneling(
+    mut integration_parameters: ResMut<IntegrationParameters>,
+    mut base_ccd_substeps: Local<Option<usize>>,
+    mut last_positions: Local<HashMap<Entity, Vec3>>,
+    track_sections: Option<Res<TrackSections>>,
+    ball_physics: Res<BallPhysics>,
+    query_pipeline: Res<QueryPipeline>,
+    colliders: QueryPipelineColliderComponentsQuery,
+    tracks: Query<(), With<Track>>,
+    mut balls: Query<
+        (
+            Entity,
+            &mut RigidBodyPositionComponent,
+            &RigidBodyVelocityComponent,
+        ),
+        With<Ball>,
+    >,
+) {
+    let base_ccd_substeps =
+        *base_ccd_substeps.get_or_insert(integration_parameters.max_ccd_substeps);
+    let any_fast = balls
+        .iter()
+        .any(|(_, _, velocity)| velocity.linvel.norm() > FAST_BALL_SPEED);
+    let ccd_substeps = if any_fast {
+        base_ccd_substeps.max(FAST_BALL_CCD_SUBSTEPS)
+    } else {
+        base_ccd_substeps
+    };
+    if integration_parameters.max_ccd_substeps != ccd_substeps {
+        integration_parameters.max_ccd_substeps = ccd_substeps;
+    }
+
+    let sections = match track_sections {
+        Some(track_sections) if track_sections.0.len() > 1 => track_sections,
+        _ => return,
+    };
+    let sections = &sections.0;
+    let collider_set = QueryPipelineColliderComponentsSet(&colliders);
+    let dt = integration_parameters.dt;
+    last_positions.retain(|&entity, _| balls.get(entity).is_ok());
+    for (entity, mut position, velocity) in balls.iter_mut() {
+        let translation = position.position.translation.vector;
+        let ball = Vec3::new(translation.x, translation.y, translation.z);
+        let last = last_positions.insert(entity, ball);
+        // A ball can have gone at most one step's travel through the floor
+        let max_depth = (velocity.linvel.norm() * dt).max(2.0 * ball_physics.radius);
+        let (direction, distance) =
+            match last.and_then(|last| passed_through_floor(sections, last, ball, max_depth)) {
+                Some(through_floor) => through_floor,
+                None => continue,
+            };
+        let ray = Ray::new(
+            Point3::new(ball.x, ball.y, ball.z),
+            Vector3::new(direction.x, direction.y, direction.z),
+        );
+        let filter = |handle: ColliderHandle| tracks.get(handle.entity()).is_ok();
+        let toi = match query_pipeline.cast_ray(
+            &collider_set,
+            &ray,
+            distance,
+            true,
+            InteractionGroups::all(),
+            Some(&filter),
+        ) {
+            Some((_, toi)) => toi,
+            None => continue,
+        };
+        let recovered = ball + direction * (toi + ball_physics.radius);
+        last_positions.insert(entity, recovered);
+        let recovered = Vector3::new(recovered.x, recovered.y, recovered.z);
+        position.position.translation.vector = recovered;
+        position.next_position.translation.vector = recovered;
+    }
+}
+
+/// Speed below which a ball is considered to have settled.
+const STUCK_SPEED: f32 = 0.5;
+/// Seconds that a ball can stay settled before it is considered stuck and out of the race.
+const STUCK_SECS: f32 = 5.0;
+
+/// Seconds into a race after which sudden death begins.
+pub const SUDDEN_DEATH_GRACE_SECS: f32 = 30.0;
+
+/// Bounds that close in on the balls as a race goes long, so that stragglers are eliminated and a
+/// race always ends, even if a ball gets stuck.
+///
+/// After [`SUDDEN_DEATH_GRACE_SECS`], the floor below which balls are out rises at `rate` units per
+/// second, and the sides of the track's bounds move in towards its middle at the same rate.
+#[derive(Clone, Copy, Debug)]
+pub struct SuddenDeath {
+    pub enabled: bool,
+    pub rate: f32,
+}
+
+impl Default for SuddenDeath {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            rate: 10.0,
+        }
+    }
+}
+
+impl SuddenDeath {
+    /// How far the bounds have closed in `race_secs` into a race, which is zero until sudden death
+    /// begins.
+    pub fn closed_in(&self, race_secs: f32) -> f32 {
+        if self.enabled {
+            (self.rate * (race_secs - SUDDEN_DEATH_GRACE_SECS)).max(0.0)
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Of a pair of colliders, finds the one for which `is_target` is true and the ball entity that
+/// the other belongs to.
+fn ball_and_target(
+    (collider1, collider2): (Entity, Entity),
+    is_target: impl Fn(Entity) -> bool,
+    parents: &Query<&Parent>,
+) -> Option<(Entity, Entity)> {
+    let (target, ball_collider) = if is_target(collider1) {
+        (collider1, collider2)
+    } else if is_target(collider2) {
+        (collider2, collider1)
+    } else {
+        return None;
+    };
+    // Ball colliders are children of the ball's rigid body
+    let ball_entity = parents.get(ball_collider).ok()?.0;
+    Some((target, ball_entity))
+}
+
+/// Finds the sensor for which `is_sensor` is true, and the ball entity, of an intersection that
+/// has just started between the two.
+fn ball_entering_sensor(
+    event: &IntersectionEvent,
+    is_sensor: impl Fn(Entity) -> bool,
+    parents: &Query<&Parent>,
+) -> Option<(Entity, Entity)> {
+    if !event.intersecting {
+        return None;
+    }
+    ball_and_target(
+        (event.collider1.entity(), event.collider2.entity()),
+        is_sensor,
+        parents,
+    )
+}
+
+/// Minimum time between boosts of the same ball, so that a ball resting on a boost pad is not
+/// launched every frame.
+pub const BOOST_COOLDOWN: Duration = Duration::from_millis(500);
+
+/// Pushes the balls of human players in the directions of the nudge keys they hold. Players whose
+/// balls have finished, dropped out, or not yet been spawned are ignored.
+pub fn nudge_balls(
+    keyboard_input: Res<Input<KeyCode>>,
+    race_config: Res<RaceConfig>,
+    round: Res<RoundState>,
+    mut balls: Query<(&mut RigidBodyForcesComponent, &RigidBodyMassPropsComponent), With<Ball>>,
+) {
+    for (&player, keys) in race_config.nudge_keys.iter() {
+        let entity = match round.players.get(player) {
+            Some(PlayerState {
+                entity: Some(entity),
+                end_secs: None,
+                ..
+            }) => *entity,
+            _ => continue,
+        };
+        let (mut forces, mass_props) = match balls.get_mut(entity) {
+            Ok(ball) => ball,
+            Err(_) => continue,
+        };
+        let mut direction = Vec3::ZERO;
+        for (key, key_direction) in [
+            (keys.left, -Vec3::X),
+            (keys.right, Vec3::X),
+            (keys.forward, -Vec3::Z),
+            (keys.back, Vec3::Z),
+        ] {
+            if keyboard_input.pressed(key) {
+                direction += key_direction;
+            }
+        }
+        // Scaled by mass so that every ball is nudged alike, and normalized so that holding two
+        // keys is no stronger than holding one
+        let force =
+            direction.normalize_or_zero() * race_config.nudge_acceleration * mass_props.mass();
+        // Forces are cleared after every physics step, so they are added afresh each frame
+        forces.force += Vector3::new(force.x, force.y, force.z);
+    }
+}
+
+/// Helps trailing balls catch up with the leader, by pushing each towards the finish in proportion
+/// to how far behind the leader it is.
+pub fn apply_catch_up(
+    game_config: Res<GameConfig>,
+    round: Res<RoundState>,
+    mut balls: Query<(&mut RigidBodyForcesComponent, &RigidBodyMassPropsComponent), With<Ball>>,
+) {
+    if game_config.catch_up_strength <= 0.0 {
+        return;
+    }
+    let live_players = || {
+        round
+            .players
+            .iter()
+            .filter(|player| player.end_secs.is_none())
+            .filter_map(|player| player.entity.map(|entity| (entity, player.distance)))
+    };
+    // Balls travel towards -z
+    let leader_distance = match live_players()
+        .map(|(_, distance)| distance)
+        .reduce(f32::min)
+    {
+        Some(distance) => distance,
+        None => return,
+    };
+    let max_deficit = game_config.catch_up_max_deficit.max(f32::EPSILON);
+    for (entity, distance) in live_players() {
+        let (mut forces, mass_props) = match balls.get_mut(entity) {
+            Ok(ball) => ball,
+            Err(_) => continue,
+        };
+        let deficit = (distance - leader_distance).min(max_deficit);
+        if deficit <= 0.0 {
+            continue;
+        }
+        let force = game_config.catch_up_strength * deficit / max_deficit * mass_props.mass();
+        forces.force += Vector3::new(0.0, 0.0, -force);
+    }
+}
+
+/// Pushes balls while they are within wind zones, with the accelerations of all of the zones that
+/// they are in. Balls go back to normal once they leave them all.
+pub fn apply_wind_zones(
+    mut intersection_events: EventReader<IntersectionEvent>,
+    zones: Query<&WindZone>,
+    parents: Query<&Parent>,
+    mut balls: Query<(&mut RigidBodyForcesComponent, &RigidBodyMassPropsComponent), With<Ball>>,
+    mut overlaps: Local<HashMap<Entity, Vec<Entity>>>,
+) {
+    for event in intersection_events.iter() {
+        let (zone, ball_entity) = match ball_and_target(
+            (event.collider1.entity(), event.collider2.entity()),
+            |entity| zones.get(entity).is_ok(),
+            &parents,
+        ) {
+            Some(entities) => entities,
+            None => continue,
+        };
+        let ball_zones = overlaps.entry(ball_entity).or_default();
+        if event.intersecting {
+            ball_zones.push(zone);
+        } else {
+            ball_zones.retain(|&entity| entity != zone);
+        }
+    }
+    overlaps.retain(|&ball_entity, ball_zones| {
+        !ball_zones.is_empty() && balls.get(ball_entity).is_ok()
+    });
+
+    for (&ball_entity, ball_zones) in overlaps.iter() {
+        let acceleration = ball_zones
+            .iter()
+            .filter_map(|&zone| zones.get(zone).ok())
+            .map(|zone| zone.acceleration)
+            .fold(Vec3::ZERO, |sum, acceleration| sum + acceleration);
+        if let Ok((mut forces, mass_props)) = balls.get_mut(ball_entity) {
+            let force = acceleration * mass_props.mass();
+            forces.force += Vector3::new(force.x, force.y, force.z);
+        }
+    }
+}
+
+/// Speeds up balls along their direction of travel when they touch a boost pad.
+pub fn apply_boosts(
+    mut contact_events: EventReader<ContactEvent>,
+    segments: Query<&TrackSegment>,
+    parents: Query<&Parent>,
+    mut balls: Query<&mut RigidBodyVelocityComponent, With<Ball>>,
+    race_config: Res<RaceConfig>,
+    clock: Res<RaceClock>,
+    mut last_boosts: Local<HashMap<Entity, f32>>,
+) {
+    let now = clock.elapsed;
+    // Boosts after the clock was rewound to before them are forgotten too
+    last_boosts
+        .retain(|_, last_boost| (0.0..BOOST_COOLDOWN.as_secs_f32()).contains(&(now - *last_boost)));
+    for event in contact_events.iter() {
+        let colliders = match event {
+            ContactEvent::Started(collider1, collider2) => (collider1.entity(), collider2.entity()),
+            ContactEvent::Stopped(_, _) => continue,
+        };
+        let is_boost = |entity| {
+            segments
+                .get(entity)
+                .map_or(false, |segment| segment.kind == SegmentKind::Boost)
+        };
+        let ball_entity = match ball_and_target(colliders, is_boost, &parents) {
+            Some((_, ball_entity)) => ball_entity,
+            None => continue,
+        };
+        if last_boosts.contains_key(&ball_entity) {
+            continue;
+        }
+        if let Ok(mut velocity) = balls.get_mut(ball_entity) {
+            let direction = velocity.linvel.try_normalize(f32::EPSILON);
+            if let Some(direction) = direction {
+                velocity.linvel += direction * race_config.boost_strength;
+                last_boosts.insert(ball_entity, now);
+            }
+        }
+    }
+}
+
+/// Marks players whose balls pass forward through the finish line as finished, or in lap races,
+/// counts their laps and marks them as finished once they have completed the target number.
+pub fn detect_finish(
+    mut intersection_events: EventReader<IntersectionEvent>,
+    finish_lines: Query<&FinishLine>,
+    parents: Query<&Parent>,
+    balls: Query<(&Ball, &RigidBodyVelocityComponent)>,
+    race_config: Res<RaceConfig>,
+    clock: Res<RaceClock>,
+    mut round: ResMut<RoundState>,
+) {
+    let now = clock.elapsed;
+    for event in intersection_events.iter() {
+        let (sensor, ball_entity) = match ball_entering_sensor(
+            event,
+            |entity| finish_lines.get(entity).is_ok(),
+            &parents,
+        ) {
+            Some(entities) => entities,
+            None => continue,
+        };
+        let finish_line = finish_lines.get(sensor).unwrap();
+        if let Ok((ball, velocity)) = balls.get(ball_entity) {
+            let linvel = Vec3::new(velocity.linvel.x, velocity.linvel.y, velocity.linvel.z);
+            // Balls rolling backwards over the line do not count
+            if linvel.dot(finish_line.normal) <= 0.0 {
+                continue;
+            }
+            if let Some(player) = round.players.get_mut(ball.player) {
+                if player.entity != Some(ball_entity) || player.end_secs.is_some() {
+                    continue;
+                }
+                if let Some(target_laps) = race_config.target_laps {
+                    if !player.passed_last_checkpoint {
+                        continue;
+                    }
+                    player.passed_last_checkpoint = false;
+                    player.laps += 1;
+                    if player.laps < target_laps {
+                        continue;
+                    }
+                }
+                player.end_secs = Some(now);
+                player.finished = true;
+            }
+        }
+    }
+}
+
+/// Records the first time each player's ball passes through each checkpoint.
+pub fn record_splits(
+    mut intersection_events: EventReader<IntersectionEvent>,
+    checkpoints: Query<&Checkpoint>,
+    parents: Query<&Parent>,
+    balls: Query<&Ball>,
+    clock: Res<RaceClock>,
+    mut round: ResMut<RoundState>,
+) {
+    let now = clock.elapsed;
+    for event in intersection_events.iter() {
+        let (sensor, ball_entity) =
+            match ball_entering_sensor(event, |entity| checkpoints.get(entity).is_ok(), &parents) {
+                Some(entities) => entities,
+                None => continue,
+            };
+        let checkpoint = checkpoints.get(sensor).unwrap();
+        if let Ok(ball) = balls.get(ball_entity) {
+            if let Some(player) = round.players.get_mut(ball.player) {
+                if player.entity != Some(ball_entity) || player.end_secs.is_some() {
+                    continue;
+                }
+                if let Some(split) = player.splits.get_mut(checkpoint.index) {
+                    split.get_or_insert(now);
+                }
+                if checkpoint.index + 1 == N_CHECKPOINTS {
+                    player.passed_last_checkpoint = true;
+                }
+            }
+        }
+    }
+}
+
+/// Damps the motion of balls while they touch any slow zone, restoring normal damping once they
+/// have left them all.
+pub fn apply_slow_zones(
+    mut contact_events: EventReader<ContactEvent>,
+    segments: Query<&TrackSegment>,
+    parents: Query<&Parent>,
+    mut balls: Query<&mut RigidBodyDampingComponent, With<Ball>>,
+    race_config: Res<RaceConfig>,
+    mut overlaps: Local<HashMap<Entity, usize>>,
+) {
+    overlaps.retain(|&ball_entity, _| balls.get(ball_entity).is_ok());
+    for event in contact_events.iter() {
+        let (colliders, started) = match event {
+            ContactEvent::Started(collider1, collider2) => {
+                ((collider1.entity(), collider2.entity()), true)
+            }
+            ContactEvent::Stopped(collider1, collider2) => {
+                ((collider1.entity(), collider2.entity()), false)
+            }
+        };
+        let is_slow_zone = |entity| {
+            segments
+                .get(entity)
+                .map_or(false, |segment| segment.kind == SegmentKind::SlowZone)
+        };
+        let ball_entity = match ball_and_target(colliders, is_slow_zone, &parents) {
+            Some((_, ball_entity)) => ball_entity,
+            None => continue,
+        };
+        let mut damping = match balls.get_mut(ball_entity) {
+            Ok(damping) => damping,
+            Err(_) => continue,
+        };
+        // A ball can touch more than one slow zone at once where they adjoin
+        let count = overlaps.entry(ball_entity).or_insert(0);
+        if started {
+            *count += 1;
+            if *count == 1 {
+                damping.linear_damping = race_config.slow_linear_damping;
+                damping.angular_damping = race_config.slow_angular_damping;
+            }
+        } else {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                **damping = RigidBodyDamping::default();
+                overlaps.remove(&ball_entity);
+            }
+        }
+    }
+}
+
+/// Ends the race for balls that have finished or fallen out of bounds, despawning them and their
+/// trails. The bounds are below the lowest point of the whole track, so balls in flight across gaps
+/// in the track are not failed. Under [`SuddenDeath`], the bounds close in as the race goes on.
+///
+/// Balls that have wedged somewhere, staying below [`STUCK_SPEED`] for more than [`STUCK_SECS`],
+/// are out as well, so that a race cannot hang waiting for them.
+#[allow(clippy::too_many_arguments)]
+pub fn despawn_balls(
+    mut commands: Commands,
+    track_bounds: Option<Res<TrackBounds>>,
+    track_info: Option<Res<TrackInfo>>,
+    sudden_death: Option<Res<SuddenDeath>>,
+    balls: Query<(&GlobalTransform, &RigidBodyVelocityComponent), With<Ball>>,
+    trails: Query<(Entity, &Trail)>,
+    clock: Res<RaceClock>,
+    mut round: ResMut<RoundState>,
+    mut state: ResMut<State<GameState>>,
+    mut settled_since: Local<HashMap<Entity, f32>>,
+    mut race_events: EventWriter<RaceEvent>,
+) {
+    let track_bounds = track_bounds.map(|track_bounds| *track_bounds);
+    let bounds = track_bounds.map_or(BOUNDS, |track_bounds| track_bounds.min + BOUNDS_MARGIN);
+    let now = clock.elapsed;
+    let round_start = round.start_secs;
+    let closed_in = sudden_death.map_or(0.0, |sudden_death| {
+        sudden_death.closed_in(now - round_start)
+    });
+    let floor = bounds.y + closed_in;
+    // The sides only bound the balls once they start closing in, towards the middle of the track
+    let sides = track_bounds
+        .filter(|_| closed_in > 0.0)
+        .map(|track_bounds| (track_bounds.min.x + closed_in)..=(track_bounds.max.x - closed_in));
+    let mut finished_count = 0;
+    for (index, player) in round.players.iter_mut().enumerate() {
+        if let Some(entity) = player.entity {
+            // The ball of a player who finished this frame is left where it crossed the line
+            if player.end_secs.is_none() {
+                if let Ok((transform, velocity)) = balls.get(entity) {
+                    player.distance = transform.translation.z.max(bounds.z);
+                    if let Some(track_info) = track_info.as_ref() {
+                        player.progress = track_info.progress(transform.translation);
+                    }
+                    let out_of_bounds = transform.translation.y < floor
+                        || sides
+                            .as_ref()
+                            .map_or(false, |sides| !sides.contains(&transform.translation.x));
+                    // Any movement restarts the wait for a settled ball
+                    let stuck = if velocity.linvel.norm() < STUCK_SPEED {
+                        // The clock may have been rewound to before the ball settled
+                        let since = settled_since.entry(entity).or_insert(now);
+                        *since = since.min(now);
+                        now - *since > STUCK_SECS
+                    } else {
+                        settled_since.remove(&entity);
+                        false
+                    };
+                    if stuck {
+                        info!("{} is stuck", player.name);
+                    }
+                    if out_of_bounds || stuck {
+                        player.end_secs = Some(now);
+                    }
+                }
+            }
+            if let Some(end) = player.end_secs {
+                race_events.send(if player.finished {
+                    RaceEvent::BallFinished {
+                        player: index,
+                        secs: end - round_start,
+                    }
+                } else {
+                    RaceEvent::BallDnf {
+                        player: index,
+                        progress: player.progress,
+                    }
+                });
+                commands.entity(entity).despawn_recursive();
+                for (trail_entity, trail) in trails.iter() {
+                    if trail.ball == entity {
+                        commands.entity(trail_entity).despawn_recursive();
+                    }
+                }
+                settled_since.remove(&entity);
+                player.entity = None;
+            }
+        }
+        if player.end_secs.is_some() {
+            finished_count += 1;
+        }
+    }
+    if finished_count >= round.players.len() && state.set(GameState::GameOver).is_ok() {
+        race_events.send(RaceEvent::RoundEnded);
+    }
+}
+
+pub fn despawn_level(mut commands: Commands, level_entities: Query<Entity, With<GameLevel>>) {
+    for entity in level_entities.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub fn despawn_all_balls(mut commands: Commands, mut round: ResMut<RoundState>) {
+    for player in round.players.iter_mut() {
+        if let Some(entity) = player.entity {
+            commands.entity(entity).despawn_recursive();
+            player.entity = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        ecs::{
+            event::{Events, ManualEventReader},
+            system::CommandQueue,
+        },
+        render::mesh::VertexAttributeValues,
+    };
+
+    use super::*;
+
+    /// The distance between `a` and `b` in RGB, weighted by how sensitive the eye is to each
+    /// channel at their level of red.
+    fn color_distance(a: Color, b: Color) -> f32 {
+        let [ar, ag, ab, _] = a.as_rgba_f32();
+        let [br, bg, bb, _] = b.as_rgba_f32();
+        let red = 0.5 * (ar + br);
+        let (dr, dg, db) = (ar - br, ag - bg, ab - bb);
+        ((2.0 + red) * dr * dr + 4.0 * dg * dg + (3.0 - red) * db * db).sqrt()
+    }
+
+    #[test]
+    fn palette_has_one_distinct_ball_per_player() {
+        for n in [3, BALL_INFO.len(), 25] {
+            let palette = generate_ball_palette(n);
+            assert_eq!(palette.len(), n);
+            for (i, a) in palette.iter().enumerate() {
+                for b in &palette[i + 1..] {
+                    assert_ne!(a.name, b.name);
+                    assert_ne!(a.color, b.color);
+                }
+            }
+        }
+        assert_eq!(generate_ball_palette(3)[2].name, BALL_INFO[2].name);
+    }
+
+    #[test]
+    fn palette_colors_are_far_enough_apart_to_tell_apart() {
+        // Up to three full bands of generated colors
+        for n in BALL_INFO.len()..=BALL_INFO.len() + 3 * HUE_NAMES.len() {
+            let palette = generate_ball_palette(n);
+            for (i, a) in palette.iter().enumerate() {
+                for b in &palette[i + 1..] {
+                    let distance = color_distance(a.color, b.color);
+                    assert!(
+                        distance > 0.2,
+                        "{} and {} are {} apart with {} players",
+                        a.name,
+                        b.name,
+                        distance,
+                        n
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn ball_collider_has_configured_radius() {
+        let mut world = World::default();
+        let mut queue = CommandQueue::default();
+        let ball_physics = BallPhysics {
+            radius: 2.0,
+            ..Default::default()
+        };
+        let ball = spawn_ball(
+            &mut Commands::new(&mut queue, &world),
+            0,
+            SPAWN_POSITION,
+            &ball_physics,
+            &BallCollision::default(),
+        );
+        queue.apply(&mut world);
+
+        let collider = world.get::<Children>(ball).unwrap()[0];
+        let shape = world.get::<ColliderShapeComponent>(collider).unwrap();
+        assert_eq!(shape.as_ball().unwrap().radius, 2.0);
+    }
+
+    #[test]
+    fn only_balls_that_were_inside_pass_through_the_floor() {
+        // A straight tube of radius 10 along -z
+        let section = |z| PathSection {
+            position: Vec3::new(0.0, 0.0, z),
+            forward: -Vec3::Z,
+            up: Vec3::Y,
+            radius: 10.0,
+        };
+        let sections = [section(0.0), section(-100.0), section(-200.0)];
+        let through_floor =
+            |last, ball| passed_through_floor(&sections, last, ball, 2.0).map(|(_, d)| d);
+
+        let inside = Vec3::new(0.0, -9.0, -50.0);
+        assert_eq!(
+            through_floor(inside, Vec3::new(0.0, -11.0, -50.0)),
+            Some(11.0)
+        );
+        // Still inside
+        assert_eq!(through_floor(inside, Vec3::new(0.0, -9.5, -50.0)), None);
+        // Already outside, as when falling past the track after flying out of it
+        assert_eq!(
+            through_floor(Vec3::new(0.0, -11.5, -50.0), Vec3::new(0.0, -11.0, -50.0)),
+            None
+        );
+        // Out over the top, not through the floor
+        assert_eq!(
+            through_floor(Vec3::new(0.0, 9.0, -50.0), Vec3::new(0.0, 11.0, -50.0)),
+            None
+        );
+        // Further than a step could have taken it
+        assert_eq!(through_floor(inside, Vec3::new(0.0, -20.0, -50.0)), None);
+        // Across the joint between segments
+        assert_eq!(
+            through_floor(Vec3::new(0.0, -9.0, -99.0), Vec3::new(0.0, -11.0, -101.0)),
+            Some(11.0)
+        );
+    }
+
+    #[test]
+    fn progress_is_fraction_of_distance_along_the_track() {
+        // Down 400 and then across 400
+        let track_info = TrackInfo::new(
+            800.0,
+            vec![
+                SPAWN_POSITION,
+                SPAWN_POSITION - 400.0 * Vec3::Z,
+                SPAWN_POSITION - 400.0 * Vec3::Z + 400.0 * Vec3::X,
+            ],
+        );
+        assert_eq!(track_info.finish_z, SPAWN_POSITION.z - 400.0);
+        assert_eq!(track_info.progress(SPAWN_POSITION), 0.0);
+        assert_eq!(track_info.progress(SPAWN_POSITION - 200.0 * Vec3::Z), 0.25);
+        assert_eq!(track_info.progress(SPAWN_POSITION - 400.0 * Vec3::Z), 0.5);
+        // Off to the side of the track and above it
+        assert_eq!(
+            track_info.progress(SPAWN_POSITION + Vec3::new(200.0, 10.0, -420.0)),
+            0.75
+        );
+        // Bouncing back behind the spawn point or overshooting the finish stays in range
+        assert_eq!(track_info.progress(SPAWN_POSITION + 10.0 * Vec3::Z), 0.0);
+        assert_eq!(
+            track_info.progress(SPAWN_POSITION + Vec3::new(500.0, 0.0, -400.0)),
+            1.0
+        );
+    }
+
+    #[test]
+    fn progress_goes_all_the_way_round_closed_loops() {
+        let path = track_path(7, true, &GameConfig::default());
+        let sections = path.sections();
+        let track_info = TrackInfo::new(
+            path.length(),
+            sections.iter().map(|section| section.position).collect(),
+        );
+        // The loop ends back where it started, so progress by z alone would not move
+        assert!((track_info.finish_z - SPAWN_POSITION.z).abs() < path.segment_length);
+        let quarter = sections.len() / 4;
+        let mut last = -1.0;
+        for section in sections.iter().skip(1).step_by(quarter) {
+            let progress = track_info.progress(section.position + section.up);
+            assert!(progress > last, "{} after {}", progress, last);
+            last = progress;
+        }
+        let halfway = track_info.progress(sections[sections.len() / 2].position);
+        assert!((halfway - 0.5).abs() < 0.05, "{}", halfway);
+    }
+
+    #[test]
+    fn race_clock_excludes_paused_time() {
+        let mut app = App::new();
+        app.add_state(GameState::Playing)
+            .init_resource::<Time>()
+            .init_resource::<RaceClock>()
+            .init_resource::<CountdownTimer>()
+            .insert_resource(DeterministicMode::default())
+            .add_system_set(SystemSet::on_update(GameState::Playing).with_system(tick_race_clock));
+        let run = |app: &mut App, frames| {
+            for _ in 0..frames {
+                app.update();
+            }
+        };
+        let set_paused = |app: &mut App, paused| {
+            let mut state = app
+                .world
+                .get_resource_mut::<State<GameState>>()
+                .expect("Missing GameState");
+            if paused {
+                state.push(GameState::Paused).unwrap();
+            } else {
+                state.pop().unwrap();
+            }
+        };
+
+        run(&mut app, 10);
+        set_paused(&mut app, true);
+        run(&mut app, 30);
+        set_paused(&mut app, false);
+        run(&mut app, 10);
+
+        let clock = app.world.get_resource::<RaceClock>().unwrap();
+        let dt = DeterministicMode::default().dt;
+        assert!(
+            (clock.elapsed - 20.0 * dt).abs() < 1e-4,
+            "{}",
+            clock.elapsed
+        );
+    }
+
+    #[test]
+    fn track_bounds_cover_every_piece_of_track() {
+        let path = HalfCylinderPath {
+            n_segments: 12,
+            radius: 5.0,
+            segment_length: 20.0,
+            gap_segments: [6].into_iter().collect(),
+            ..Default::default()
+        };
+        let sections = path.sections();
+        let race_config = RaceConfig {
+            boost_segments: vec![2],
+            ..Default::default()
+        };
+        let mut world = World::default();
+        let mut queue = CommandQueue::default();
+        let segments = spawn_track(
+            &mut Commands::new(&mut queue, &world),
+            path,
+            &ColliderKind::default(),
+            &race_config,
+        )
+        .unwrap();
+        queue.apply(&mut world);
+
+        // The gap splits the track into disjoint pieces either side of it
+        assert_eq!(segments.len(), 11);
+        let bounds = *world.get_resource::<TrackBounds>().unwrap();
+        for (_, mesh, _) in &segments {
+            let aabb = mesh.compute_aabb().unwrap();
+            assert!(bounds.min.cmple(aabb.min().into()).all());
+            assert!(bounds.max.cmpge(aabb.max().into()).all());
+        }
+        let (first, last) = (sections[0].position, sections[12].position);
+        assert!(bounds.max.y >= first.y && bounds.min.y < last.y);
+        assert!(bounds.max.z >= first.z && bounds.min.z <= last.z);
+    }
+
+    /// A round of players who have reached each of `distances`, none of whom have finished.
+    fn round_at(distances: &[f32]) -> RoundState {
+        RoundState {
+            start_secs: 0.0,
+            players: distances
+                .iter()
+                .enumerate()
+                .map(|(i, &distance)| {
+                    let mut player = PlayerState::new(i.to_string(), Color::WHITE, Vec3::ZERO, 0.0);
+                    player.distance = distance;
+                    player
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn finished_players_rank_by_time_ahead_of_everyone_else() {
+        // In progress, finished, dropped out, finished and in progress
+        let mut round = round_at(&[-300.0, -800.0, -500.0, -790.0, -100.0]);
+        for (player, end_secs) in [(1, 30.0), (3, 25.0)] {
+            round.players[player].finished = true;
+            round.players[player].end_secs = Some(end_secs);
+        }
+        // Dropping out ends a player's race without finishing
+        round.players[2].end_secs = Some(10.0);
+
+        assert_eq!(rank_players(&round), vec![3, 1, 2, 0, 4]);
+    }
+
+    #[test]
+    fn more_laps_rank_ahead_of_distance() {
+        let mut round = round_at(&[-50.0, -700.0, -10.0]);
+        round.players[0].laps = 1;
+        round.players[2].laps = 1;
+
+        assert_eq!(rank_players(&round), vec![0, 2, 1]);
+    }
+
+    #[test]
+    fn furthest_along_leads() {
+        let round = round_at(&[-10.0, -50.0, -30.0]);
+        let distances = rank_players(&round)
+            .into_iter()
+            .map(|player| round.players[player].distance)
+            .collect::<Vec<_>>();
+        assert_eq!(distances, vec![-50.0, -30.0, -10.0]);
+    }
+
+    #[test]
+    fn names_are_sanitized_and_capped() {
+        assert_eq!(sanitize_name("  ALICE\n"), "ALICE");
+        assert_eq!(sanitize_name("B\u{7}O\tB"), "BOB");
+        assert_eq!(sanitize_name("ABCDEFGHIJKLMNOP"), "ABCDEFGHIJKL");
+        assert_eq!(sanitize_name("ABCDEFGHIJK MNOP"), "ABCDEFGHIJK");
+        assert_eq!(sanitize_name(" \r\n"), "");
+    }
+
+    #[test]
+    fn unnamed_entrants_keep_their_default_names() {
+        let mut race_config = RaceConfig::new(4);
+        race_config.set_names(&[
+            "ALICE".to_string(),
+            "\u{1b}".to_string(),
+            "A VERY LONG NAME INDEED".to_string(),
+        ]);
+        let names = race_config
+            .balls
+            .iter()
+            .map(|ball| ball.name.as_ref())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                "ALICE",
+                &BALL_INFO[1].name,
+                "A VERY LONG",
+                &BALL_INFO[3].name
+            ]
+        );
+    }
+
+    #[test]
+    fn minimap_fits_the_top_down_track() {
+        // 200 along -z and 100 across x, so centered across the minimap
+        let preview = TrackPreview::new(&[
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(100.0, -50.0, -100.0),
+            Vec3::new(50.0, -100.0, -200.0),
+        ]);
+        assert_eq!(
+            preview.points,
+            vec![
+                Vec2::new(0.25, 0.0),
+                Vec2::new(0.75, 0.5),
+                Vec2::new(0.5, 1.0)
+            ]
+        );
+        // Height makes no difference from above
+        assert_eq!(
+            preview.project(Vec3::new(100.0, 80.0, -200.0)),
+            Vec2::new(0.75, 1.0)
+        );
+    }
+
+    #[test]
+    fn balls_spawn_once_their_start_delay_is_over() {
+        let mut app = App::new();
+        let mut countdown = CountdownTimer::default();
+        countdown.0.tick(COUNTDOWN);
+        app.insert_resource(countdown)
+            .init_resource::<RaceClock>()
+            .init_resource::<BallPhysics>()
+            .init_resource::<BallCollision>()
+            .init_resource::<GameConfig>()
+            .insert_resource(RoundState {
+                start_secs: 3.0,
+                players: [3.5, 4.0, 5.0]
+                    .into_iter()
+                    .map(|start_secs| {
+                        PlayerState::new(String::new(), Color::WHITE, SPAWN_POSITION, start_secs)
+                    })
+                    .collect(),
+            })
+            .add_system(spawn_balls);
+        let spawned = |app: &App| {
+            let round = app.world.get_resource::<RoundState>().unwrap();
+            round
+                .players
+                .iter()
+                .map(|player| player.entity.is_some())
+                .collect::<Vec<_>>()
+        };
+        let run_until = |app: &mut App, secs| {
+            let mut clock = app.world.get_resource_mut::<RaceClock>().unwrap();
+            let delta = secs - clock.elapsed;
+            clock.tick(delta);
+            app.update();
+        };
+
+        run_until(&mut app, 3.0);
+        assert_eq!(spawned(&app), vec![false, false, false]);
+        run_until(&mut app, 3.75);
+        assert_eq!(spawned(&app), vec![true, false, false]);
+        run_until(&mut app, 4.5);
+        assert_eq!(spawned(&app), vec![true, true, false]);
+        run_until(&mut app, 5.25);
+        assert_eq!(spawned(&app), vec![true, true, true]);
+    }
+
+    #[test]
+    fn winners_are_handicapped_the_most() {
+        // Player 2 won, then players 0 and 3, and players 1 and 4 did not finish
+        let mut round = round_at(&[-500.0, -300.0, -800.0, -500.0, -700.0]);
+        for (player, end_secs) in [(2, 20.0), (0, 25.0), (3, 30.0)] {
+            round.players[player].finished = true;
+            round.players[player].end_secs = Some(end_secs);
+        }
+        let placements = placements(&round);
+        assert_eq!(placements, vec![1, 4, 0, 2, 4]);
+        assert_eq!(
+            handicap_delays(&placements, 10000),
+            vec![7500, 0, 10000, 5000, 0]
+        );
+    }
+
+    #[test]
+    fn simultaneous_spawns_do_not_overlap() {
+        let radius = BallPhysics::default().radius;
+        let mut rng = SmallRng::seed_from_u64(1);
+        // A single row, several rows, and a track narrower than a ball
+        for (n_players, half_width) in [(N_PLAYERS, SPAWN_RADIUS), (40, 10.0), (3, 0.5)] {
+            let offsets = spawn_offsets(n_players, half_width, radius, &mut rng);
+            assert_eq!(offsets.len(), n_players);
+            for (i, a) in offsets.iter().enumerate() {
+                for b in &offsets[i + 1..] {
+                    assert!(
+                        a.distance(*b) >= 2.0 * radius - 1e-4,
+                        "{} and {} overlap",
+                        a,
+                        b
+                    );
+                }
+            }
+        }
+    }
+
+    /// An app that runs [`despawn_balls`] on a round with a stationary ball at each of `positions`.
+    fn despawn_app(positions: &[Vec3]) -> App {
+        let mut app = App::new();
+        app.init_resource::<RaceClock>()
+            .add_event::<RaceEvent>()
+            .add_state(GameState::Playing)
+            .add_system(despawn_balls);
+        let mut round = round_at(&vec![0.0; positions.len()]);
+        for (player, (state, &position)) in round.players.iter_mut().zip(positions).enumerate() {
+            let velocity: RigidBodyVelocityComponent = RigidBodyVelocity::default().into();
+            let ball = app
+                .world
+                .spawn()
+                .insert_bundle((
+                    Ball { player },
+                    GlobalTransform::from_translation(position),
+                    velocity,
+                ))
+                .id();
+            state.entity = Some(ball);
+        }
+        app.insert_resource(round);
+        app
+    }
+
+    /// Every race event sent in `app`, which has no system to clear old events.
+    fn race_events(app: &App) -> Vec<RaceEvent> {
+        let events = app.world.get_resource::<Events<RaceEvent>>().unwrap();
+        ManualEventReader::default().iter(events).cloned().collect()
+    }
+
+    #[test]
+    fn balls_that_finished_this_frame_are_left_where_they_crossed() {
+        // The first ball crossed the finish line earlier in the frame, and has since fallen below
+        // the track
+        let mut app = despawn_app(&[
+            Vec3::new(0.0, 2.0 * BOUNDS.y, -900.0),
+            Vec3::new(0.0, 0.0, -100.0),
+        ]);
+        app.world.get_resource_mut::<RaceClock>().unwrap().elapsed = 20.0;
+        let ball = {
+            let mut round = app.world.get_resource_mut::<RoundState>().unwrap();
+            let player = &mut round.players[0];
+            player.end_secs = Some(20.0);
+            player.finished = true;
+            player.distance = -800.0;
+            player.entity.unwrap()
+        };
+        assert!(!app
+            .world
+            .get_resource::<RoundState>()
+            .unwrap()
+            .ball_in_play(0, ball));
+        app.update();
+
+        let round = app.world.get_resource::<RoundState>().unwrap();
+        assert_eq!(round.players[0].end_secs, Some(20.0));
+        assert!(round.players[0].finished);
+        assert_eq!(round.players[0].distance, -800.0);
+        assert_eq!(round.players[0].entity, None);
+        assert!(app.world.get_entity(ball).is_none());
+        assert_eq!(round.players[1].distance, -100.0);
+        assert!(round.players[1].entity.is_some());
+        assert_eq!(
+            race_events(&app),
+            vec![RaceEvent::BallFinished {
+                player: 0,
+                secs: 20.0
+            }]
+        );
+    }
+
+    #[test]
+    fn streamed_chunks_match_the_whole_track() {
+        let path = HalfCylinderPath {
+            n_segments: 5 * STREAM_CHUNK_SEGMENTS / 2,
+            ..Default::default()
+        };
+        let positions = |mesh: &Mesh| match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+            Some(VertexAttributeValues::Float32x3(positions)) => positions.clone(),
+            _ => panic!("Mesh has no positions"),
+        };
+        let whole = positions(&Mesh::from(path.clone()));
+        let ring_vertex_count = whole.len() / (path.n_segments + 1);
+
+        let mut streamer = TrackStreamer::new(path.clone());
+        let chunks = std::iter::from_fn(|| streamer.next_chunk()).collect::<Vec<_>>();
+        assert_eq!(chunks.len(), 3);
+        let mut next_segment = 0;
+        for (segments, mesh, collider_mesh) in chunks {
+            assert_eq!(segments.start, next_segment);
+            next_segment = segments.end;
+            assert!(collider_mesh.is_none());
+            let rings = segments.start * ring_vertex_count..(segments.end + 1) * ring_vertex_count;
+            for (streamed, built) in positions(&mesh).iter().zip(&whole[rings.clone()]) {
+                assert!(Vec3::from(*streamed).abs_diff_eq(Vec3::from(*built), 1e-4));
+            }
+            assert_eq!(positions(&mesh).len(), rings.len());
+        }
+        assert_eq!(next_segment, path.n_segments);
+    }
+
+    #[test]
+    fn obstacles_are_placed_inside_the_track_from_its_seed() {
+        let path = HalfCylinderPath {
+            n_segments: 50,
+            radius: 2.0,
+            segment_length: 10.0,
+            obstacles: 12,
+            ..Default::default()
+        };
+        let sections = path.sections();
+        let placements = obstacle_placements(&path, &sections);
+        assert_eq!(placements.len(), 12);
+        assert_eq!(placements, obstacle_placements(&path, &sections));
+        let other_path = HalfCylinderPath {
+            seed: path.seed + 1,
+            ..path.clone()
+        };
+        assert_ne!(
+            placements,
+            obstacle_placements(&other_path, &other_path.sections())
+        );
+        for (position, _) in placements {
+            // None are where the balls spawn
+            assert!(nearest_segment(&sections, position) >= 5);
+            // Within the tube, so nearer the middle of the track than its radius
+            let from_middle = sections
+                .windows(2)
+                .map(|pair| {
+                    let (start, end) = (pair[0].position, pair[1].position);
+                    let t = (position - start).dot(end - start) / end.distance_squared(start);
+                    position.distance(start.lerp(end, t.clamp(0.0, 1.0)))
+                })
+                .fold(f32::MAX, f32::min);
+            assert!(from_middle < path.radius, "{}", from_middle);
+        }
+        assert!(obstacle_placements(
+            &HalfCylinderPath {
+                obstacles: 0,
+                ..path
+            },
+            &sections
+        )
+        .is_empty());
+    }
+
+    #[test]
+    fn sudden_death_closes_in_on_stuck_balls() {
+        // One ball wedged below the middle of the track, and one against its side
+        let mut app = despawn_app(&[Vec3::new(0.0, -135.0, -100.0), Vec3::new(95.0, 0.0, -100.0)]);
+        app.insert_resource(TrackBounds {
+            min: Vec3::new(-100.0, -100.0, -1000.0),
+            max: Vec3::new(100.0, 10.0, 0.0),
+        })
+        .insert_resource(SuddenDeath {
+            enabled: true,
+            rate: 10.0,
+        });
+        // Rocking back and forth where they are wedged, so that they never settle
+        let mut velocities = app.world.query::<&mut RigidBodyVelocityComponent>();
+        for mut velocity in velocities.iter_mut(&mut app.world) {
+            velocity.linvel = Vector3::new(1.0, 0.0, 0.0);
+        }
+        let floor = -100.0 + BOUNDS_MARGIN.y;
+        assert_eq!(floor + 50.0, -135.0);
+        let in_race = |app: &mut App, race_secs: f32| {
+            app.world.get_resource_mut::<RaceClock>().unwrap().elapsed = race_secs;
+            app.update();
+            let round = app.world.get_resource::<RoundState>().unwrap();
+            round
+                .players
+                .iter()
+                .map(|player| player.entity.is_some())
+                .collect::<Vec<_>>()
+        };
+        // The sides reach the second ball 5 units in, and the floor the first 50 units up
+        assert_eq!(in_race(&mut app, 30.4), vec![true, true]);
+        assert_eq!(in_race(&mut app, 30.6), vec![true, false]);
+        assert_eq!(in_race(&mut app, 34.9), vec![true, false]);
+        assert_eq!(in_race(&mut app, 35.1), vec![false, false]);
+        let round = app.world.get_resource::<RoundState>().unwrap();
+        assert_eq!(round.players[0].end_secs, Some(35.1));
+        assert!(!round.players[0].finished);
+    }
+
+    #[test]
+    fn bounds_stay_put_without_sudden_death() {
+        let sudden_death = SuddenDeath::default();
+        assert!(!sudden_death.enabled);
+        assert_eq!(sudden_death.closed_in(1000.0), 0.0);
+        let sudden_death = SuddenDeath {
+            enabled: true,
+            rate: 2.0,
+        };
+        assert_eq!(sudden_death.closed_in(SUDDEN_DEATH_GRACE_SECS), 0.0);
+        assert_eq!(sudden_death.closed_in(SUDDEN_DEATH_GRACE_SECS + 3.0), 6.0);
+    }
+
+    #[test]
+    fn settled_balls_are_out_after_a_while() {
+        let mut app = despawn_app(&[Vec3::new(0.0, 0.0, -100.0), Vec3::new(5.0, 0.0, -100.0)]);
+        let moving_ball = app.world.get_resource::<RoundState>().unwrap().players[1]
+            .entity
+            .unwrap();
+        let mut update = |race_secs: f32, moving_speed: f32| {
+            app.world.get_resource_mut::<RaceClock>().unwrap().elapsed = race_secs;
+            app.world
+                .get_mut::<RigidBodyVelocityComponent>(moving_ball)
+                .unwrap()
+                .linvel = Vector3::new(moving_speed, 0.0, 0.0);
+            app.update();
+            let round = app.world.get_resource::<RoundState>().unwrap();
+            (
+                round.players[0].entity.is_some(),
+                round.players[1].entity.is_some(),
+            )
+        };
+        assert_eq!(update(10.0, 0.0), (true, true));
+        // Moving restarts the wait for the second ball
+        assert_eq!(update(12.0, 1.0), (true, true));
+        assert_eq!(update(12.5, 0.0), (true, true));
+        assert_eq!(update(14.9, 0.0), (true, true));
+        assert_eq!(update(10.0 + STUCK_SECS + 0.1, 0.0), (false, true));
+        assert_eq!(update(17.6, 0.0), (false, false));
+
+        let round = app.world.get_resource::<RoundState>().unwrap();
+        assert_eq!(round.players[0].end_secs, Some(10.0 + STUCK_SECS + 0.1));
+        assert!(!round.players[0].finished);
+        assert!(matches!(
+            race_events(&app)[..],
+            [
+                RaceEvent::BallDnf { player: 0, .. },
+                RaceEvent::BallDnf { player: 1, .. },
+                RaceEvent::RoundEnded
+            ]
+        ));
+    }
+
+    /// The hue in degrees, saturation, and lightness of `color`.
+    fn hsl(color: Color) -> (f32, f32, f32) {
+        match color.as_hsla() {
+            Color::Hsla {
+                hue,
+                saturation,
+                lightness,
+                ..
+            } => (hue, saturation, lightness),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn named_balls_are_the_color_of_their_names() {
+        let hues = [
+            ("RED", 0.0),
+            ("ORANGE", 39.0),
+            ("YELLOW", 60.0),
+            ("GREEN", 120.0),
+            ("BLUE", 240.0),
+            ("INDIGO", 275.0),
+            ("VIOLET", 300.0),
+        ];
+        for ball in &BALL_INFO {
+            let (hue, saturation, lightness) = hsl(ball.color);
+            match hues.iter().find(|(name, _)| *name == ball.name) {
+                Some(&(_, expected)) => {
+                    let off = (hue - expected).rem_euclid(360.0);
+                    assert!(off.min(360.0 - off) < 10.0, "{} has hue {}", ball.name, hue);
+                    assert!(saturation > 0.5, "{} is washed out", ball.name);
+                }
+                None => {
+                    assert_eq!(saturation, 0.0, "{} is not gray", ball.name);
+                    let expected = match &*ball.name {
+                        "WHITE" => 1.0,
+                        "DARK_GRAY" => 0.25,
+                        "BLACK" => 0.0,
+                        name => panic!("Unexpected ball name {}", name),
+                    };
+                    assert!((lightness - expected).abs() < 0.01, "{}", ball.name);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn simultaneous_starts_release_everyone_together() {
+        let mut app = App::new();
+        app.init_resource::<RoundState>()
+            .init_resource::<RaceClock>()
+            .init_resource::<CountdownTimer>()
+            .insert_resource(RaceConfig::new(6))
+            .init_resource::<BallPhysics>()
+            .init_resource::<BallCollision>()
+            .insert_resource(RaceSeed(5))
+            .insert_resource(GameConfig {
+                start_mode: StartMode::Simultaneous,
+                ..Default::default()
+            })
+            .add_event::<RaceEvent>()
+            .add_startup_system(start_round)
+            .add_system(spawn_balls);
+        let gates = |app: &mut App| {
+            let mut gates = app.world.query_filtered::<(), With<StartGate>>();
+            gates.iter(&app.world).count()
+        };
+
+        // Everyone is spawned during the countdown, behind the gate
+        app.update();
+        let round = app.world.get_resource::<RoundState>().unwrap();
+        assert_eq!(round.players.len(), 6);
+        for player in &round.players {
+            assert_eq!(player.start_secs, round.start_secs);
+            assert!(player.entity.is_some());
+        }
+        assert_eq!(gates(&mut app), 1);
+
+        app.world
+            .get_resource_mut::<CountdownTimer>()
+            .unwrap()
+            .0
+            .tick(COUNTDOWN);
+        app.update();
+        assert_eq!(gates(&mut app), 0);
+        let mut balls = app.world.query::<&Ball>();
+        assert_eq!(balls.iter(&app.world).count(), 6);
+    }
+
+    #[test]
+    fn dropping_out_reports_progress_towards_the_finish() {
+        let mut app = despawn_app(&[Vec3::new(0.0, 2.0 * BOUNDS.y, -250.0)]);
+        app.insert_resource(TrackInfo::new(1200.0, vec![Vec3::ZERO, -1000.0 * Vec3::Z]));
+        app.update();
+        assert_eq!(
+            race_events(&app),
+            vec![
+                RaceEvent::BallDnf {
+                    player: 0,
+                    progress: 0.25
+                },
+                RaceEvent::RoundEnded
+            ]
+        );
+    }
+
+    #[test]
+    fn close_finishes_are_slowed_down() {
+        let mut app = App::new();
+        let mut round = round_at(&[-990.0, -500.0, -999.0]);
+        for (i, player) in round.players.iter_mut().enumerate() {
+            player.entity = Some(Entity::from_raw(i as u32));
+        }
+        app.insert_resource(round)
+            .insert_resource(TrackInfo::new(1200.0, vec![Vec3::ZERO, -1000.0 * Vec3::Z]))
+            .insert_resource(RaceConfig::new(3))
+            .init_resource::<PhotoFinish>()
+            .init_resource::<TimeScale>()
+            .add_system(detect_photo_finish);
+        let slow_motion = |app: &mut App, distances: [f32; 3], end_secs: [Option<f32>; 3]| {
+            let mut round = app.world.get_resource_mut::<RoundState>().unwrap();
+            for (player, (distance, end_secs)) in round
+                .players
+                .iter_mut()
+                .zip(distances.into_iter().zip(end_secs))
+            {
+                player.distance = distance;
+                player.end_secs = end_secs;
+            }
+            app.update();
+            let photo_finish = app.world.get_resource::<PhotoFinish>().unwrap();
+            let time_scale = app.world.get_resource::<TimeScale>().unwrap();
+            assert_eq!(
+                time_scale.0,
+                if photo_finish.active {
+                    PHOTO_FINISH_TIME_SCALE
+                } else {
+                    1.0
+                }
+            );
+            photo_finish.active
+        };
+
+        assert!(!slow_motion(&mut app, [-990.0, -500.0, -999.0], [None; 3]));
+        assert!(slow_motion(&mut app, [-998.0, -500.0, -999.0], [None; 3]));
+        assert!(slow_motion(&mut app, [-999.5, -500.0, -999.9], [None; 3]));
+        // Once either has crossed the line, the other is not racing anyone
+        assert!(!slow_motion(
+            &mut app,
+            [-999.5, -500.0, -1000.1],
+            [None, None, Some(20.0)]
+        ));
+    }
+
+    #[test]
+    fn invalid_tracks_are_not_spawned() {
+        let mut world = World::default();
+        let mut queue = CommandQueue::default();
+        let path = HalfCylinderPath {
+            segment_length: 0.0,
+            ..Default::default()
+        };
+        let result = spawn_track(
+            &mut Commands::new(&mut queue, &world),
+            path,
+            &ColliderKind::default(),
+            &RaceConfig::default(),
+        );
+        queue.apply(&mut world);
+        assert_eq!(
+            result.err(),
+            Some(TrackError::Path(PathError::NonPositiveSegmentLength(0.0)))
+        );
+        assert!(world.get_resource::<TrackInfo>().is_none());
+        assert_eq!(world.entities().len(), 0);
+    }
+
+    #[test]
+    fn track_colliders_are_built_from_the_coarser_tube() {
+        let mut world = World::default();
+        let mut queue = CommandQueue::default();
+        let path = HalfCylinderPath {
+            n_segments: 4,
+            subdivisions: 32,
+            collider_subdivisions: 8,
+            ..Default::default()
+        };
+        let collider_mesh = Mesh::from(path.collider_path().unwrap());
+        let segments = spawn_track(
+            &mut Commands::new(&mut queue, &world),
+            path,
+            &ColliderKind::default(),
+            &RaceConfig::default(),
+        )
+        .unwrap();
+        queue.apply(&mut world);
+
+        let vertex_count = |mesh: &Mesh| mesh.attribute(Mesh::ATTRIBUTE_POSITION).unwrap().len();
+        assert_eq!(segments.len(), 1);
+        let (collider, mesh, _) = &segments[0];
+        let shape = world.get::<ColliderShapeComponent>(*collider).unwrap();
+        let collider_vertices = shape.as_trimesh().unwrap().vertices().len();
+        assert_eq!(collider_vertices, vertex_count(&collider_mesh));
+        assert!(collider_vertices < vertex_count(mesh) / 2);
+    }
+}
@@ -0,0 +1,540 @@
+use std::collections::VecDeque;
+#[cfg(not(target_arch = "wasm32"))]
+use std::{fs::File, io, path::Path};
+
+use bevy::prelude::*;
+use bevy_rapier3d::{
+    na::{Isometry3, Quaternion, Translation3, UnitQuaternion, Vector3},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::race::{
+    spawn_ball, Ball, BallCollision, BallPhysics, CountdownTimer, GameState, RaceClock, RaceSeed,
+    RoundState,
+};
+
+/// Frames kept by the recorder, at most. Ten minutes at 60 frames per second.
+pub const MAX_REPLAY_FRAMES: usize = 10 * 60 * 60;
+
+/// The transforms of every ball in play at one moment of a race.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReplayFrame {
+    /// Seconds since the start of the round.
+    pub time: f32,
+    /// The player, position, and rotation of each ball.
+    pub balls: Vec<(usize, Vec3, Quat)>,
+    /// The player, linear velocity, and angular velocity of each ball, for rewinding a live race.
+    /// Replays do not need them, so they may be missing from older replay files.
+    #[serde(default)]
+    pub velocities: Vec<(usize, Vec3, Vec3)>,
+}
+
+/// A recorded race, which can be played back on the track generated from `seed`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    pub seed: u64,
+    pub frames: Vec<ReplayFrame>,
+}
+
+/// Records the balls of the current round every frame. Only the latest `max_frames` frames are
+/// kept, so that a long race cannot use unbounded memory.
+pub struct ReplayRecorder {
+    pub seed: u64,
+    pub frames: VecDeque<ReplayFrame>,
+    pub max_frames: usize,
+}
+
+impl Default for ReplayRecorder {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            frames: VecDeque::new(),
+            max_frames: MAX_REPLAY_FRAMES,
+        }
+    }
+}
+
+impl ReplayRecorder {
+    pub fn push(&mut self, frame: ReplayFrame) {
+        while self.frames.len() >= self.max_frames.max(1) {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(frame);
+    }
+
+    /// Drops the frames recorded in the last `secs` seconds and returns the latest frame left,
+    /// from which the race can resume. Rewinds no further than the first frame kept.
+    pub fn rewind(&mut self, secs: f32) -> Option<&ReplayFrame> {
+        let time = self.frames.back()?.time - secs;
+        let kept = self
+            .frames
+            .iter()
+            .rposition(|frame| frame.time <= time)
+            .unwrap_or(0);
+        self.frames.truncate(kept + 1);
+        self.frames.back()
+    }
+
+    pub fn replay(&self) -> Replay {
+        Replay {
+            seed: self.seed,
+            frames: self.frames.iter().cloned().collect(),
+        }
+    }
+
+    /// Writes the recorded frames to `path` in RON format.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save_replay(&self, path: &Path) -> io::Result<()> {
+        let ron = ron::ser::to_string(&self.replay())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        std::fs::write(path, ron)
+    }
+}
+
+/// Reads a replay previously written by [`ReplayRecorder::save_replay`] from `path`.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_replay(path: &Path) -> io::Result<Replay> {
+    let file = File::open(path)?;
+    ron::de::from_reader(file).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// The recorded path of a single ball through a race on the track generated from `seed`, to race
+/// against as a ghost.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GhostRun {
+    pub seed: u64,
+    /// Seconds since the start of the round, with the position and rotation of the ball then.
+    pub frames: Vec<(f32, Vec3, Quat)>,
+}
+
+impl GhostRun {
+    /// The path of the ball of `player` through `replay`.
+    pub fn from_replay(replay: &Replay, player: usize) -> Self {
+        Self {
+            seed: replay.seed,
+            frames: replay
+                .frames
+                .iter()
+                .filter_map(|frame| {
+                    frame
+                        .balls
+                        .iter()
+                        .find(|(index, _, _)| *index == player)
+                        .map(|&(_, translation, rotation)| (frame.time, translation, rotation))
+                })
+                .collect(),
+        }
+    }
+
+    /// Where the ghost is `time` seconds into the round, interpolated between the recorded frames,
+    /// or `None` before its ball was spawned or after it left the race.
+    pub fn transform_at(&self, time: f32) -> Option<(Vec3, Quat)> {
+        let next = self.frames.iter().position(|&(t, _, _)| t >= time)?;
+        let (next_time, next_translation, next_rotation) = self.frames[next];
+        if next == 0 {
+            return (next_time == time).then(|| (next_translation, next_rotation));
+        }
+        let (prev_time, prev_translation, prev_rotation) = self.frames[next - 1];
+        let t = (time - prev_time) / (next_time - prev_time).max(f32::EPSILON);
+        Some((
+            prev_translation.lerp(next_translation, t),
+            prev_rotation.slerp(next_rotation, t),
+        ))
+    }
+}
+
+/// Writes `ghost` to `path` in RON format.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_ghost(ghost: &GhostRun, path: &Path) -> io::Result<()> {
+    let ron =
+        ron::ser::to_string(ghost).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    std::fs::write(path, ron)
+}
+
+/// Reads a ghost previously written by [`save_ghost`] from `path`.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_ghost(path: &Path) -> io::Result<GhostRun> {
+    let file = File::open(path)?;
+    ron::de::from_reader(file).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// A translucent ball following the [`GhostRun`] resource. It has no collider, so it passes
+/// through the track and live balls alike.
+#[derive(Component)]
+pub struct GhostBall;
+
+/// Moves the ghost ball along its recorded path, hiding it while it is not in the race.
+pub fn move_ghost(
+    ghost: Option<Res<GhostRun>>,
+    round: Res<RoundState>,
+    clock: Res<RaceClock>,
+    mut ghost_balls: Query<(&mut Transform, &mut Visibility), With<GhostBall>>,
+) {
+    let ghost = match ghost {
+        Some(ghost) => ghost,
+        None => return,
+    };
+    let transform = round_time(&round, &clock).and_then(|time| ghost.transform_at(time));
+    for (mut ball_transform, mut visibility) in ghost_balls.iter_mut() {
+        visibility.is_visible = transform.is_some();
+        if let Some((translation, rotation)) = transform {
+            ball_transform.translation = translation;
+            ball_transform.rotation = rotation;
+        }
+    }
+}
+
+/// Seconds since the start of `round`, or `None` during the countdown.
+fn round_time(round: &RoundState, clock: &RaceClock) -> Option<f32> {
+    let time = clock.elapsed - round.start_secs;
+    (time >= 0.0).then(|| time)
+}
+
+pub fn start_recording(mut recorder: ResMut<ReplayRecorder>, seed: Res<RaceSeed>) {
+    recorder.seed = seed.0;
+    recorder.frames.clear();
+}
+
+pub fn record_replay(
+    mut recorder: ResMut<ReplayRecorder>,
+    round: Res<RoundState>,
+    clock: Res<RaceClock>,
+    balls: Query<(&Ball, &GlobalTransform, &RigidBodyVelocityComponent)>,
+) {
+    let time = match round_time(&round, &clock) {
+        Some(time) => time,
+        None => return,
+    };
+    let (balls, velocities) = balls
+        .iter()
+        .filter(|(ball, _, _)| {
+            round
+                .players
+                .get(ball.player)
+                .map_or(false, |player| player.end_secs.is_none())
+        })
+        .map(|(ball, transform, velocity)| {
+            (
+                (ball.player, transform.translation, transform.rotation),
+                (
+                    ball.player,
+                    Vec3::from_slice(velocity.linvel.as_slice()),
+                    Vec3::from_slice(velocity.angvel.as_slice()),
+                ),
+            )
+        })
+        .unzip();
+    recorder.push(ReplayFrame {
+        time,
+        balls,
+        velocities,
+    });
+}
+
+const REWIND_KEY: KeyCode = KeyCode::R;
+/// Seconds the race goes back by with each press of the rewind key.
+const REWIND_SECS: f32 = 3.0;
+
+/// Rewinds the race by a few seconds when R is pressed, putting live balls back where they were
+/// and moving as they were then. The race clock goes back with them, so the rewound seconds do not
+/// count toward finish times, and splits taken in them are forgotten. Balls that have since left
+/// the race stay out of it, with the times they had.
+pub fn rewind_race(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut recorder: ResMut<ReplayRecorder>,
+    mut clock: ResMut<RaceClock>,
+    mut round: ResMut<RoundState>,
+    mut balls: Query<(
+        &Ball,
+        &mut RigidBodyPositionComponent,
+        &mut RigidBodyVelocityComponent,
+    )>,
+) {
+    if !keyboard_input.just_pressed(REWIND_KEY) {
+        return;
+    }
+    let time = match round_time(&round, &clock) {
+        Some(time) => time,
+        None => return,
+    };
+    let frame = match recorder.rewind(REWIND_SECS) {
+        Some(frame) => frame.clone(),
+        None => return,
+    };
+    for (ball, mut position, mut velocity) in balls.iter_mut() {
+        if let Some(&(_, translation, rotation)) = frame
+            .balls
+            .iter()
+            .find(|(index, _, _)| *index == ball.player)
+        {
+            position.position = isometry(translation, rotation);
+            position.next_position = position.position;
+        }
+        if let Some(&(_, linvel, angvel)) = frame
+            .velocities
+            .iter()
+            .find(|(index, _, _)| *index == ball.player)
+        {
+            velocity.linvel = Vector3::new(linvel.x, linvel.y, linvel.z);
+            velocity.angvel = Vector3::new(angvel.x, angvel.y, angvel.z);
+        }
+    }
+    // Winding the clock back, rather than moving the start of the round later, leaves the times
+    // of players who have already finished as they were
+    clock.elapsed -= time - frame.time;
+    let now = clock.elapsed;
+    for player in round.players.iter_mut().filter(|p| p.end_secs.is_none()) {
+        for split in player.splits.iter_mut() {
+            if split.map_or(false, |split| split > now) {
+                *split = None;
+            }
+        }
+    }
+}
+
+/// Plays back a recorded race in place of simulating one.
+pub struct ReplayPlayer {
+    pub replay: Replay,
+}
+
+fn isometry(translation: Vec3, rotation: Quat) -> Isometry3<f32> {
+    Isometry3::from_parts(
+        Translation3::new(translation.x, translation.y, translation.z),
+        UnitQuaternion::new_normalize(Quaternion::new(
+            rotation.w, rotation.x, rotation.y, rotation.z,
+        )),
+    )
+}
+
+/// Moves kinematic balls along their recorded transforms, spawning balls as they appear in the
+/// replay and despawning them as they leave it. The round ends with the last frame.
+#[allow(clippy::too_many_arguments)]
+pub fn play_replay(
+    mut commands: Commands,
+    replay_player: Res<ReplayPlayer>,
+    countdown: Res<CountdownTimer>,
+    clock: Res<RaceClock>,
+    ball_physics: Res<BallPhysics>,
+    ball_collision: Res<BallCollision>,
+    mut round: ResMut<RoundState>,
+    mut positions: Query<&mut RigidBodyPositionComponent, With<Ball>>,
+    mut state: ResMut<State<GameState>>,
+) {
+    if !countdown.0.finished() {
+        return;
+    }
+    let time = match round_time(&round, &clock) {
+        Some(time) => time,
+        None => return,
+    };
+    let frames = &replay_player.replay.frames;
+    let frame = match frames.iter().rev().find(|frame| frame.time <= time) {
+        Some(frame) => frame,
+        None => return,
+    };
+    for (i, player) in round.players.iter_mut().enumerate() {
+        match frame.balls.iter().find(|(index, _, _)| *index == i) {
+            Some(&(_, translation, rotation)) => {
+                player.distance = translation.z;
+                match player.entity {
+                    Some(entity) => {
+                        if let Ok(mut position) = positions.get_mut(entity) {
+                            position.next_position = isometry(translation, rotation);
+                        }
+                    }
+                    None => {
+                        let entity = spawn_ball(
+                            &mut commands,
+                            i,
+                            translation,
+                            &ball_physics,
+                            &ball_collision,
+                        );
+                        commands.entity(entity).insert(RigidBodyTypeComponent::from(
+                            RigidBodyType::KinematicPositionBased,
+                        ));
+                        player.entity = Some(entity);
+                    }
+                }
+            }
+            None => {
+                if let Some(entity) = player.entity.take() {
+                    commands.entity(entity).despawn_recursive();
+                    player.end_secs = Some(clock.elapsed);
+                }
+            }
+        }
+    }
+    if frames.last().map_or(true, |last| time >= last.time) {
+        state.set(GameState::GameOver).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::race::PlayerState;
+
+    /// A frame `time` seconds in, with player 0 that far along -z and player 1 half as far.
+    fn frame(time: f32) -> ReplayFrame {
+        let rotation = Quat::from_rotation_x(-time);
+        ReplayFrame {
+            time,
+            balls: vec![
+                (0, Vec3::new(0.1, -time, -time), rotation),
+                (1, Vec3::new(-0.3, -0.5 * time, -0.5 * time), rotation),
+            ],
+            velocities: vec![(0, -Vec3::Z, Vec3::X), (1, -0.5 * Vec3::Z, Vec3::X)],
+        }
+    }
+
+    #[test]
+    fn recorder_keeps_only_the_latest_frames() {
+        let mut recorder = ReplayRecorder {
+            max_frames: 3,
+            ..Default::default()
+        };
+        for i in 0..5 {
+            recorder.push(frame(i as f32 / 60.0));
+        }
+        let times = recorder.frames.iter().map(|f| f.time).collect::<Vec<_>>();
+        assert_eq!(times, vec![2.0 / 60.0, 3.0 / 60.0, 4.0 / 60.0]);
+    }
+
+    #[test]
+    fn replays_round_trip_through_ron() {
+        let mut recorder = ReplayRecorder {
+            seed: 99,
+            ..Default::default()
+        };
+        for i in 0..10 {
+            recorder.push(frame(1.234_567 * i as f32));
+        }
+        let path = std::env::temp_dir().join("bavy-balls-replay-round-trip.ron");
+        recorder.save_replay(&path).unwrap();
+        let replay = load_replay(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(replay.unwrap(), recorder.replay());
+    }
+
+    /// The ghost of player 0 over frames from 1 to 3 seconds into the round.
+    fn ghost() -> GhostRun {
+        let frames = (1..=3).map(|i| frame(i as f32)).collect();
+        GhostRun::from_replay(&Replay { seed: 7, frames }, 0)
+    }
+
+    #[test]
+    fn ghosts_are_interpolated_between_frames() {
+        let ghost = ghost();
+        assert_eq!(ghost.seed, 7);
+        assert_eq!(ghost.frames.len(), 3);
+        assert_eq!(ghost.transform_at(0.5), None);
+        assert_eq!(ghost.transform_at(3.5), None);
+        let (translation, rotation) = ghost.transform_at(1.0).unwrap();
+        assert!(translation.abs_diff_eq(Vec3::new(0.1, -1.0, -1.0), 1e-6));
+        assert!(rotation.abs_diff_eq(Quat::from_rotation_x(-1.0), 1e-6));
+        let (translation, rotation) = ghost.transform_at(2.25).unwrap();
+        assert!(translation.abs_diff_eq(Vec3::new(0.1, -2.25, -2.25), 1e-5));
+        assert!(rotation.abs_diff_eq(Quat::from_rotation_x(-2.25), 1e-5));
+    }
+
+    #[test]
+    fn ghost_ball_follows_the_recorded_run() {
+        let mut app = App::new();
+        app.insert_resource(ghost())
+            .insert_resource(RoundState {
+                start_secs: 4.0,
+                players: Vec::new(),
+            })
+            .init_resource::<RaceClock>()
+            .add_system(move_ghost);
+        let ball = app
+            .world
+            .spawn()
+            .insert_bundle((GhostBall, Transform::default(), Visibility::default()))
+            .id();
+        for (elapsed, expected) in [
+            (2.0, None),
+            (5.5, Some(Vec3::new(0.1, -1.5, -1.5))),
+            (7.0, Some(Vec3::new(0.1, -3.0, -3.0))),
+            (8.0, None),
+        ] {
+            app.world.get_resource_mut::<RaceClock>().unwrap().elapsed = elapsed;
+            app.update();
+            let visibility = app.world.get::<Visibility>(ball).unwrap();
+            assert_eq!(visibility.is_visible, expected.is_some());
+            if let Some(expected) = expected {
+                let transform = app.world.get::<Transform>(ball).unwrap();
+                assert!(transform.translation.abs_diff_eq(expected, 1e-5));
+            }
+        }
+    }
+
+    #[test]
+    fn rewinding_drops_the_latest_frames() {
+        let mut recorder = ReplayRecorder::default();
+        for i in 0..=10 {
+            recorder.push(frame(i as f32));
+        }
+        assert_eq!(recorder.rewind(3.0).unwrap().time, 7.0);
+        assert_eq!(recorder.rewind(2.5).unwrap().time, 4.0);
+        assert_eq!(recorder.frames.len(), 5);
+        assert_eq!(recorder.rewind(100.0).unwrap().time, 0.0);
+        assert_eq!(recorder.frames.len(), 1);
+        assert!(ReplayRecorder::default().rewind(1.0).is_none());
+    }
+
+    #[test]
+    fn rewinding_keeps_finished_times() {
+        let mut app = App::new();
+        let mut recorder = ReplayRecorder::default();
+        for i in 0..=10 {
+            recorder.push(frame(i as f32));
+        }
+        let mut finished = PlayerState::new(String::new(), Color::WHITE, Vec3::ZERO, 1.0);
+        finished.end_secs = Some(9.0);
+        finished.splits[0] = Some(8.0);
+        let mut racing = PlayerState::new(String::new(), Color::WHITE, Vec3::ZERO, 1.0);
+        racing.splits[0] = Some(5.0);
+        racing.splits[1] = Some(10.0);
+        let mut keyboard_input = Input::<KeyCode>::default();
+        keyboard_input.press(REWIND_KEY);
+        app.insert_resource(recorder)
+            .insert_resource(keyboard_input)
+            .insert_resource(RaceClock {
+                elapsed: 12.0,
+                delta: 0.0,
+            })
+            .insert_resource(RoundState {
+                start_secs: 2.0,
+                players: vec![finished, racing],
+            })
+            .add_system(rewind_race);
+        let ball = app
+            .world
+            .spawn()
+            .insert_bundle((
+                Ball { player: 1 },
+                RigidBodyPositionComponent::default(),
+                RigidBodyVelocityComponent::default(),
+            ))
+            .id();
+        app.update();
+
+        assert_eq!(app.world.get_resource::<RaceClock>().unwrap().elapsed, 9.0);
+        let round = app.world.get_resource::<RoundState>().unwrap();
+        assert_eq!(round.start_secs, 2.0);
+        assert_eq!(round.players[0].end_secs, Some(9.0));
+        assert_eq!(round.players[0].splits[0], Some(8.0));
+        assert_eq!(round.players[1].splits[..2], [Some(5.0), None]);
+        let position = app.world.get::<RigidBodyPositionComponent>(ball).unwrap();
+        let translation = position.position.translation.vector;
+        assert!((translation - Vector3::new(-0.3, -3.5, -3.5)).norm() < 1e-6);
+        assert_eq!(position.next_position, position.position);
+        let velocity = app.world.get::<RigidBodyVelocityComponent>(ball).unwrap();
+        assert_eq!(velocity.linvel, Vector3::new(0.0, 0.0, -0.5));
+        assert_eq!(velocity.angvel, Vector3::new(1.0, 0.0, 0.0));
+    }
+}
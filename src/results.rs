@@ -0,0 +1,305 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs::File;
+use std::{collections::HashMap, io, path::Path};
+
+use bevy::log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::race::{rank_players, RoundState};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PlayerResult {
+    pub name: String,
+    /// Non-linear sRGB, with alpha.
+    pub color: [f32; 4],
+    pub finished: bool,
+    /// Seconds from the start of the round until the player finished.
+    pub time: Option<f32>,
+    pub distance: f32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct RaceResult {
+    pub players: Vec<PlayerResult>,
+}
+
+impl From<&RoundState> for RaceResult {
+    fn from(round: &RoundState) -> Self {
+        Self {
+            players: round
+                .players
+                .iter()
+                .map(|player| PlayerResult {
+                    name: player.name.clone(),
+                    color: player.color.as_rgba_f32(),
+                    finished: player.finished,
+                    time: player
+                        .end_secs
+                        .filter(|_| player.finished)
+                        .map(|end| end - round.start_secs),
+                    distance: player.distance,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Writes the results of `round` to `path` as a JSON array with one entry per player.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write_results(round: &RoundState, path: &Path) -> io::Result<()> {
+    let file = File::create(path)?;
+    serde_json::to_writer_pretty(file, &RaceResult::from(round))?;
+    Ok(())
+}
+
+/// The best finishing time on each track, keyed by track seed, and the coins won from bets, kept
+/// between runs.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(from = "StoredHighScores")]
+pub struct HighScores {
+    /// Seconds from the start of the round until the winner finished.
+    pub best_times: HashMap<u64, f32>,
+    pub coins: u32,
+}
+
+/// High scores as stored, including those stored before there were bets, as just the best times.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredHighScores {
+    Current {
+        best_times: HashMap<u64, f32>,
+        #[serde(default)]
+        coins: u32,
+    },
+    BestTimes(HashMap<u64, f32>),
+}
+
+impl From<StoredHighScores> for HighScores {
+    fn from(stored: StoredHighScores) -> Self {
+        match stored {
+            StoredHighScores::Current { best_times, coins } => Self { best_times, coins },
+            StoredHighScores::BestTimes(best_times) => Self {
+                best_times,
+                coins: 0,
+            },
+        }
+    }
+}
+
+/// Coins won by picking the winner of a race.
+pub const BET_WINNINGS: u32 = 10;
+
+/// The player whose ball a local user has bet will win the race.
+pub struct Bet {
+    pub player: usize,
+}
+
+impl HighScores {
+    pub fn best_time(&self, seed: u64) -> Option<f32> {
+        self.best_times.get(&seed).copied()
+    }
+
+    /// Records the winning time of `round` on the track generated from `seed`, returning whether
+    /// it beat the previous best.
+    pub fn record(&mut self, seed: u64, round: &RoundState) -> bool {
+        let winning_time = RaceResult::from(round)
+            .players
+            .iter()
+            .filter_map(|player| player.time)
+            .reduce(f32::min);
+        match (winning_time, self.best_time(seed)) {
+            (Some(time), Some(best)) if time >= best => false,
+            (Some(time), _) => {
+                self.best_times.insert(seed, time);
+                true
+            }
+            (None, _) => false,
+        }
+    }
+
+    /// Pays out `bet` if its player won `round`, returning whether they did. Nobody wins a round in
+    /// which no ball finished.
+    pub fn settle_bet(&mut self, bet: &Bet, round: &RoundState) -> bool {
+        let won = rank_players(round).first().map_or(false, |&winner| {
+            winner == bet.player && round.players[winner].finished
+        });
+        if won {
+            self.coins += BET_WINNINGS;
+        }
+        won
+    }
+}
+
+/// Reads high scores from `path` in RON format. A missing or unreadable file gives empty high
+/// scores, so that a corrupt file does not stop the game from starting.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn load_high_scores(path: &Path) -> HighScores {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return HighScores::default(),
+        Err(e) => {
+            warn!("Failed to open high scores {}: {}", path.display(), e);
+            return HighScores::default();
+        }
+    };
+    ron::de::from_reader(file).unwrap_or_else(|e| {
+        warn!("Failed to read high scores {}: {}", path.display(), e);
+        HighScores::default()
+    })
+}
+
+/// Writes `high_scores` to `path` in RON format.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_high_scores(high_scores: &HighScores, path: &Path) -> io::Result<()> {
+    let ron = ron::ser::to_string_pretty(high_scores, ron::ser::PrettyConfig::default())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    std::fs::write(path, ron)
+}
+
+/// The browser's local storage, if the page is allowed to use it.
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> io::Result<web_sys::Storage> {
+    web_sys::window()
+        .and_then(|window| window.local_storage().ok().flatten())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::Unsupported, "local storage unavailable"))
+}
+
+/// Reads high scores in RON format from the local storage item named by `path`. A missing or
+/// unreadable item gives empty high scores, as with the file on native builds.
+#[cfg(target_arch = "wasm32")]
+pub fn load_high_scores(path: &Path) -> HighScores {
+    let key = path.display().to_string();
+    let ron = match local_storage().map(|storage| storage.get_item(&key)) {
+        Ok(Ok(Some(ron))) => ron,
+        Ok(Ok(None)) => return HighScores::default(),
+        Ok(Err(_)) | Err(_) => {
+            warn!("Failed to open high scores {}", key);
+            return HighScores::default();
+        }
+    };
+    ron::de::from_str(&ron).unwrap_or_else(|e| {
+        warn!("Failed to read high scores {}: {}", key, e);
+        HighScores::default()
+    })
+}
+
+/// Writes `high_scores` in RON format to the local storage item named by `path`.
+#[cfg(target_arch = "wasm32")]
+pub fn save_high_scores(high_scores: &HighScores, path: &Path) -> io::Result<()> {
+    let ron = ron::ser::to_string(high_scores)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    local_storage()?
+        .set_item(&path.display().to_string(), &ron)
+        .map_err(|_| io::Error::new(io::ErrorKind::Other, "local storage is full or disabled"))
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::*;
+
+    use super::*;
+    use crate::race::PlayerState;
+
+    /// A round that started at 2s, in which RED finished at 12.5s and BLUE dropped out 40m along.
+    fn round() -> RoundState {
+        let mut red = PlayerState::new("RED".to_string(), Color::RED, Vec3::ZERO, 2.0);
+        red.end_secs = Some(12.5);
+        red.distance = -1000.0;
+        red.finished = true;
+        let mut blue = PlayerState::new("BLUE".to_string(), Color::BLUE, Vec3::ZERO, 3.0);
+        blue.end_secs = Some(20.0);
+        blue.distance = -40.0;
+        RoundState {
+            start_secs: 2.0,
+            players: vec![red, blue],
+        }
+    }
+
+    #[test]
+    fn results_round_trip_through_json() {
+        let round = round();
+        let path = std::env::temp_dir().join("bavy-balls-results-round-trip.json");
+        write_results(&round, &path).unwrap();
+        let read: RaceResult = serde_json::from_reader(File::open(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(read, RaceResult::from(&round));
+        assert_eq!(read.players[0].name, "RED");
+        assert_eq!(read.players[0].color, Color::RED.as_rgba_f32());
+        assert_eq!(read.players[0].time, Some(10.5));
+        assert!(read.players[0].finished);
+        assert_eq!(read.players[1].time, None);
+        assert_eq!(read.players[1].distance, -40.0);
+        assert!(!read.players[1].finished);
+    }
+
+    #[test]
+    fn high_scores_keep_the_best_time_per_seed() {
+        let path = std::env::temp_dir().join("bavy-balls-high-scores.ron");
+        let _ = std::fs::remove_file(&path);
+        let mut high_scores = load_high_scores(&path);
+        assert_eq!(high_scores, HighScores::default());
+
+        let mut round = round();
+        assert!(high_scores.record(7, &round));
+        save_high_scores(&high_scores, &path).unwrap();
+        let mut high_scores = load_high_scores(&path);
+        assert_eq!(high_scores.best_time(7), Some(10.5));
+
+        // Slower on the same track, then faster, then on another track
+        round.players[0].end_secs = Some(13.0);
+        assert!(!high_scores.record(7, &round));
+        round.players[0].end_secs = Some(11.0);
+        assert!(high_scores.record(7, &round));
+        assert!(high_scores.record(8, &round));
+        save_high_scores(&high_scores, &path).unwrap();
+        let high_scores = load_high_scores(&path);
+        assert_eq!(high_scores.best_time(7), Some(9.0));
+        assert_eq!(high_scores.best_time(8), Some(9.0));
+        assert_eq!(high_scores.best_time(9), None);
+
+        std::fs::write(&path, "not ron").unwrap();
+        assert_eq!(load_high_scores(&path), HighScores::default());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn rounds_nobody_finished_set_no_record() {
+        let mut round = round();
+        round.players[0].finished = false;
+        let mut high_scores = HighScores::default();
+        assert!(!high_scores.record(7, &round));
+        assert_eq!(high_scores.best_time(7), None);
+    }
+
+    #[test]
+    fn only_picking_the_winner_wins_coins() {
+        let round = round();
+        let mut high_scores = HighScores::default();
+        assert!(high_scores.settle_bet(&Bet { player: 0 }, &round));
+        assert_eq!(high_scores.coins, BET_WINNINGS);
+        assert!(!high_scores.settle_bet(&Bet { player: 1 }, &round));
+        assert_eq!(high_scores.coins, BET_WINNINGS);
+
+        // Nobody wins when nobody finished
+        let mut round = round;
+        round.players[0].finished = false;
+        assert!(!high_scores.settle_bet(&Bet { player: 0 }, &round));
+        assert_eq!(high_scores.coins, BET_WINNINGS);
+    }
+
+    #[test]
+    fn coins_are_kept_with_high_scores() {
+        let high_scores = HighScores {
+            best_times: HashMap::from([(7, 10.5)]),
+            coins: 30,
+        };
+        let ron = ron::ser::to_string(&high_scores).unwrap();
+        assert_eq!(ron::from_str::<HighScores>(&ron).unwrap(), high_scores);
+        // High scores from before there were bets have no coins
+        let old: HighScores = ron::from_str("{7: 10.5}").unwrap();
+        assert_eq!(old.best_time(7), Some(10.5));
+        assert_eq!(old.coins, 0);
+    }
+}
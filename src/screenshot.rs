@@ -0,0 +1,267 @@
+#[cfg(not(target_arch = "wasm32"))]
+use bevy::{
+    core_pipeline::Opaque3d,
+    render::{
+        render_phase::RenderPhase,
+        render_resource::{
+            BufferDescriptor, BufferUsages, CommandEncoderDescriptor, Extent3d, ImageCopyBuffer,
+            ImageDataLayout, MapMode, Texture, TextureDescriptor, TextureDimension, TextureFormat,
+            TextureUsages, TextureViewDescriptor,
+        },
+        renderer::{RenderDevice, RenderQueue},
+        texture::BevyDefault,
+        view::{ExtractedView, ViewTarget},
+    },
+};
+use bevy::{
+    prelude::*,
+    render::{RenderApp, RenderStage},
+};
+
+/// Set to take a screenshot of the next frame rendered, which is saved as a PNG, or downloaded
+/// from the canvas on the web.
+#[derive(Clone, Copy, Default)]
+pub struct ScreenshotRequest(pub bool);
+
+/// Takes screenshots of the 3D view when a [`ScreenshotRequest`] is made.
+///
+/// The window's surface can only be rendered to, so natively the frame being captured is rendered
+/// to a texture of its own in place of the window, which then shows the clear color for that
+/// frame. On the web, the canvas still holds the frame until it is shown, so is read directly.
+pub struct ScreenshotPlugin;
+
+impl Plugin for ScreenshotPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ScreenshotRequest>();
+        let render_app = match app.get_sub_app_mut(RenderApp) {
+            Ok(render_app) => render_app,
+            Err(_) => return,
+        };
+        render_app.add_system_to_stage(RenderStage::Extract, extract_screenshot_request);
+        #[cfg(not(target_arch = "wasm32"))]
+        render_app
+            .add_system_to_stage(RenderStage::Queue, target_screenshot)
+            .add_system_to_stage(RenderStage::Cleanup, save_screenshot);
+        #[cfg(target_arch = "wasm32")]
+        render_app.add_system_to_stage(RenderStage::Cleanup, download_screenshot);
+    }
+}
+
+/// Hands a request made this frame over to the render world.
+fn extract_screenshot_request(mut commands: Commands, mut request: ResMut<ScreenshotRequest>) {
+    if request.0 {
+        commands.insert_resource(*request);
+        request.0 = false;
+    }
+}
+
+/// The name a screenshot taken now is saved under.
+#[cfg(not(target_arch = "wasm32"))]
+fn screenshot_name() -> String {
+    let millis = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis());
+    format!("screenshot-{}.png", millis)
+}
+
+/// The texture the 3D view is rendered to in place of the window, for the frame being captured.
+#[cfg(not(target_arch = "wasm32"))]
+struct ScreenshotTarget {
+    texture: Texture,
+    width: u32,
+    height: u32,
+}
+
+/// Points the 3D view at a texture that can be copied from, for the frame being captured.
+#[cfg(not(target_arch = "wasm32"))]
+fn target_screenshot(
+    mut commands: Commands,
+    request: Option<Res<ScreenshotRequest>>,
+    render_device: Res<RenderDevice>,
+    mut views: Query<(&ExtractedView, &mut ViewTarget), With<RenderPhase<Opaque3d>>>,
+) {
+    if !request.map_or(false, |request| request.0) {
+        return;
+    }
+    commands.remove_resource::<ScreenshotRequest>();
+    let (view, mut target) = match views.iter_mut().next() {
+        Some(view) => view,
+        None => return,
+    };
+    let texture = render_device.create_texture(&TextureDescriptor {
+        label: Some("screenshot_texture"),
+        size: Extent3d {
+            width: view.width,
+            height: view.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        // The view's pipelines are made for the window's format
+        format: TextureFormat::bevy_default(),
+        usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+    });
+    target.view = texture.create_view(&TextureViewDescriptor::default());
+    commands.insert_resource(ScreenshotTarget {
+        texture,
+        width: view.width,
+        height: view.height,
+    });
+}
+
+/// Copies the captured frame back from the GPU once it has been rendered, and saves it as a PNG.
+#[cfg(not(target_arch = "wasm32"))]
+fn save_screenshot(
+    mut commands: Commands,
+    target: Option<Res<ScreenshotTarget>>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    let target = match target {
+        Some(target) => target,
+        None => return,
+    };
+    commands.remove_resource::<ScreenshotTarget>();
+    let ScreenshotTarget { width, height, .. } = *target;
+    let bytes_per_row = padded_bytes_per_row(width);
+    let buffer = render_device.create_buffer(&BufferDescriptor {
+        label: Some("screenshot_buffer"),
+        size: bytes_per_row as u64 * height as u64,
+        usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let mut encoder = render_device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("screenshot_encoder"),
+    });
+    encoder.copy_texture_to_buffer(
+        target.texture.as_image_copy(),
+        ImageCopyBuffer {
+            buffer: &buffer,
+            layout: ImageDataLayout {
+                offset: 0,
+                bytes_per_row: std::num::NonZeroU32::new(bytes_per_row),
+                rows_per_image: None,
+            },
+        },
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+    render_queue.submit(std::iter::once(encoder.finish()));
+
+    let slice = buffer.slice(..);
+    render_device.map_buffer(&slice, MapMode::Read);
+    let pixels = rgba_pixels(
+        &slice.get_mapped_range(),
+        width,
+        height,
+        bytes_per_row,
+        TextureFormat::bevy_default(),
+    );
+    buffer.unmap();
+    let path = screenshot_name();
+    // Encoding a PNG takes long enough to drop frames, so is done off the render thread
+    std::thread::spawn(move || {
+        match image::save_buffer(&path, &pixels, width, height, image::ColorType::Rgba8) {
+            Ok(()) => info!("Saved screenshot to {}", path),
+            Err(e) => warn!("Failed to save screenshot {}: {}", path, e),
+        }
+    });
+}
+
+/// Bytes in each row of an image `width` pixels across when copied out of a texture, as rows are
+/// padded to a multiple of 256 bytes for copying.
+#[cfg(not(target_arch = "wasm32"))]
+fn padded_bytes_per_row(width: u32) -> u32 {
+    const ALIGNMENT: u32 = 256;
+    (4 * width + ALIGNMENT - 1) / ALIGNMENT * ALIGNMENT
+}
+
+/// The RGBA pixels of an image copied out of a texture of `format` into `data`, without the
+/// padding at the end of each of its rows of `bytes_per_row`.
+#[cfg(not(target_arch = "wasm32"))]
+fn rgba_pixels(
+    data: &[u8],
+    width: u32,
+    height: u32,
+    bytes_per_row: u32,
+    format: TextureFormat,
+) -> Vec<u8> {
+    let bgra = matches!(
+        format,
+        TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb
+    );
+    let mut pixels = Vec::with_capacity(4 * width as usize * height as usize);
+    for row in data.chunks(bytes_per_row as usize).take(height as usize) {
+        for pixel in row[..4 * width as usize].chunks_exact(4) {
+            if bgra {
+                pixels.extend_from_slice(&[pixel[2], pixel[1], pixel[0], pixel[3]]);
+            } else {
+                pixels.extend_from_slice(pixel);
+            }
+        }
+    }
+    pixels
+}
+
+/// Downloads the canvas once the frame has been rendered to it, before the browser clears it.
+#[cfg(target_arch = "wasm32")]
+fn download_screenshot(mut commands: Commands, request: Option<Res<ScreenshotRequest>>) {
+    if !request.map_or(false, |request| request.0) {
+        return;
+    }
+    commands.remove_resource::<ScreenshotRequest>();
+    // The browser numbers downloads of the same name
+    if let Err(e) = download_canvas("screenshot.png") {
+        warn!("Failed to download screenshot: {:?}", e);
+    }
+}
+
+/// Downloads the image on the page's canvas as a PNG named `name`.
+#[cfg(target_arch = "wasm32")]
+fn download_canvas(name: &str) -> Result<(), wasm_bindgen::JsValue> {
+    use wasm_bindgen::JsCast;
+
+    let document = web_sys::window()
+        .and_then(|window| window.document())
+        .ok_or("no document")?;
+    let canvas = document
+        .query_selector("canvas")?
+        .ok_or("no canvas")?
+        .dyn_into::<web_sys::HtmlCanvasElement>()?;
+    let link = document
+        .create_element("a")?
+        .dyn_into::<web_sys::HtmlAnchorElement>()?;
+    link.set_href(&canvas.to_data_url()?);
+    link.set_download(name);
+    link.click();
+    Ok(())
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_are_unpadded_and_swizzled_to_rgba() {
+        assert_eq!(padded_bytes_per_row(2), 256);
+        assert_eq!(padded_bytes_per_row(64), 256);
+        assert_eq!(padded_bytes_per_row(65), 512);
+
+        // Two rows of two pixels, padded to the alignment
+        let mut data = vec![0; 2 * 256];
+        data[..8].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+        data[256..264].copy_from_slice(&[9, 10, 11, 12, 13, 14, 15, 16]);
+        assert_eq!(
+            rgba_pixels(&data, 2, 2, 256, TextureFormat::Rgba8UnormSrgb),
+            vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16]
+        );
+        assert_eq!(
+            rgba_pixels(&data, 2, 2, 256, TextureFormat::Bgra8UnormSrgb),
+            vec![3, 2, 1, 4, 7, 6, 5, 8, 11, 10, 9, 12, 15, 14, 13, 16]
+        );
+    }
+}
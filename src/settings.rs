@@ -0,0 +1,93 @@
+/// How balls are lit.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LightingMode {
+    /// Each ball carries a point light of its own color.
+    PerBall,
+    /// One directional light lights the whole scene. Balls still glow in their own color, but do
+    /// not light their surroundings. This is much cheaper in races with many balls.
+    Directional,
+}
+
+impl LightingMode {
+    pub fn toggled(self) -> Self {
+        match self {
+            LightingMode::PerBall => LightingMode::Directional,
+            LightingMode::Directional => LightingMode::PerBall,
+        }
+    }
+}
+
+/// How the rows of the leaderboard are ordered.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LeaderboardStyle {
+    /// Rows are sorted by rank, so they move as players overtake each other.
+    Sorted,
+    /// Each player keeps the same row, which shows their rank as a number.
+    Sticky,
+}
+
+impl LeaderboardStyle {
+    pub fn toggled(self) -> Self {
+        match self {
+            LeaderboardStyle::Sorted => LeaderboardStyle::Sticky,
+            LeaderboardStyle::Sticky => LeaderboardStyle::Sorted,
+        }
+    }
+
+    /// The player shown in each row of the leaderboard, given the players in order of rank.
+    pub fn rows(self, ranking: &[usize]) -> Vec<usize> {
+        match self {
+            LeaderboardStyle::Sorted => ranking.to_vec(),
+            LeaderboardStyle::Sticky => (0..ranking.len()).collect(),
+        }
+    }
+}
+
+/// Master volumes that the settings menu steps through.
+pub const VOLUME_STEPS: [f32; 5] = [0.0, 0.25, 0.5, 0.75, 1.0];
+
+/// User-adjustable settings.
+pub struct Settings {
+    /// Volume of all sound effects, from 0 to 1.
+    pub master_volume: f32,
+    pub lighting: LightingMode,
+    /// Whether lights cast shadows.
+    pub shadows: bool,
+    pub leaderboard: LeaderboardStyle,
+}
+
+impl Settings {
+    /// Steps the master volume up through [`VOLUME_STEPS`], wrapping around to silence.
+    pub fn step_volume(&mut self) {
+        self.master_volume = VOLUME_STEPS
+            .iter()
+            .copied()
+            .find(|&volume| volume > self.master_volume + f32::EPSILON)
+            .unwrap_or(VOLUME_STEPS[0]);
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            master_volume: 1.0,
+            lighting: LightingMode::PerBall,
+            shadows: false,
+            leaderboard: LeaderboardStyle::Sorted,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sticky_rows_stay_put_as_ranks_change() {
+        let rankings = [[0, 1, 2, 3], [2, 0, 3, 1], [3, 2, 1, 0]];
+        for ranking in rankings {
+            assert_eq!(LeaderboardStyle::Sticky.rows(&ranking), vec![0, 1, 2, 3]);
+            assert_eq!(LeaderboardStyle::Sorted.rows(&ranking), ranking.to_vec());
+        }
+    }
+}
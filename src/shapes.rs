@@ -1,4 +1,8 @@
-use std::ops::Range;
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, Write},
+    ops::Range,
+};
 
 use bevy::{
     math::{const_vec3, Quat, Vec3},
@@ -8,11 +12,20 @@ use bevy::{
         render_resource::PrimitiveTopology,
     },
 };
-use bevy_rapier3d::{na::Point3, prelude::ColliderShape};
+use bevy_rapier3d::{
+    na::Point3, prelude::ColliderShape, rapier::parry::transformation::vhacd::VHACDParameters,
+};
 use rand::{prelude::SmallRng, SeedableRng};
+use serde::{Deserialize, Serialize};
 
 use crate::paths::WormPathIterator;
 
+/// Texture V coordinate for a point `distance` along the length of a half-cylinder. U spans 0..1
+/// around the half circumference, so V is scaled by that same length to keep texels square.
+fn uv_v(distance: f32, radius: f32) -> f32 {
+    distance / (std::f32::consts::PI * radius)
+}
+
 pub struct HalfCylinder {
     pub start: Vec3,
     pub end: Vec3,
@@ -33,6 +46,14 @@ impl HalfCylinder {
         }
     }
 
+    /// A half-cylinder filling a unit half-cylinder scaled by `scale`, as it would be by a
+    /// `Transform` with that scale. A half-cylinder has a single radius, so it cannot be squashed
+    /// to a different depth than its width. The radius is taken from `scale.x`, the width of its
+    /// opening, and `scale.y` is ignored.
+    pub fn from_scale(scale: Vec3) -> Self {
+        Self::from_radius_and_length(0.5 * scale.x, scale.z)
+    }
+
     pub fn from_radius_and_length(radius: f32, length: f32) -> Self {
         let mut half_cylinder = Self::default();
         half_cylinder.start *= length;
@@ -62,37 +83,31 @@ impl From<HalfCylinder> for Mesh {
         let mut normals = Vec::with_capacity(vertex_count);
         let mut uvs = Vec::with_capacity(vertex_count);
 
-        let up = Vec3::Y;
         let forward = (end - start).normalize_or_zero();
-        let right = up.cross(-forward).normalize_or_zero() * radius;
-        for i in 0..=subdivisions {
-            // start point
-            let offset = Quat::from_axis_angle(
+        let v_end = uv_v(start.distance(end), radius);
+        for (center, v) in [(start, 0.0), (end, v_end)] {
+            push_ring(
+                &mut positions,
+                &mut normals,
+                &mut uvs,
+                center,
                 forward,
-                std::f32::consts::PI * i as f32 / subdivisions as f32,
-            ) * right;
-            let normal = (-offset.normalize_or_zero()).to_array();
-            positions.push((start + offset).to_array());
-            normals.push(normal);
-            uvs.push([0.0, 0.0]);
-            // end point
-            positions.push((end + offset).to_array());
-            normals.push(normal);
-            uvs.push([0.0, 0.0]);
-        }
-
-        let mut indices = Vec::with_capacity(subdivisions * 2);
-        for i in 0..subdivisions as u32 {
-            let offset = i as u32 * 2;
-            indices.extend_from_slice(&[
-                offset + 2,
-                offset,
-                offset + 1,
-                offset + 1,
-                offset + 3,
-                offset + 2,
-            ]);
+                Vec3::Y,
+                radius,
+                subdivisions,
+                false,
+                v,
+            );
         }
+
+        let mut indices = Vec::with_capacity(subdivisions * 6);
+        connect_rings(
+            &mut indices,
+            0,
+            subdivisions as u32 + 1,
+            subdivisions,
+            false,
+        );
         let indices = Indices::U32(indices);
 
         let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
@@ -104,6 +119,65 @@ impl From<HalfCylinder> for Mesh {
     }
 }
 
+/// Pushes a ring of `subdivisions + 1` vertices around the lower half of a circle of `radius`
+/// about `center`, facing along `forward` with `up` as the open side. Normals point in towards
+/// the center, and U runs from 0 to 1 around the ring at the given `v`.
+///
+/// If `closed` is set, the ring goes all the way around the circle instead, with `subdivisions`
+/// vertices as the last would coincide with the first.
+#[allow(clippy::too_many_arguments)]
+fn push_ring(
+    positions: &mut Vec<[f32; 3]>,
+    normals: &mut Vec<[f32; 3]>,
+    uvs: &mut Vec<[f32; 2]>,
+    center: Vec3,
+    forward: Vec3,
+    up: Vec3,
+    radius: f32,
+    subdivisions: usize,
+    closed: bool,
+    v: f32,
+) {
+    let right = up.cross(-forward).normalize_or_zero() * radius;
+    let (sweep, n_vertices) = if closed {
+        (std::f32::consts::TAU, subdivisions)
+    } else {
+        (std::f32::consts::PI, subdivisions + 1)
+    };
+    for i in 0..n_vertices {
+        let offset = Quat::from_axis_angle(forward, sweep * i as f32 / subdivisions as f32) * right;
+        positions.push((center + offset).to_array());
+        normals.push((-offset.normalize_or_zero()).to_array());
+        uvs.push([i as f32 / subdivisions as f32, v]);
+    }
+}
+
+/// Pushes the triangles joining the `subdivisions + 1` consecutive vertices starting at
+/// `ring_a_offset` to those starting at `ring_b_offset`, front-facing when viewed from inside a
+/// tube running from ring A to ring B. If the rings are `closed`, they have `subdivisions`
+/// vertices each and the last is joined back to the first, sharing vertices so that there is no
+/// seam in the tube.
+fn connect_rings(
+    indices: &mut Vec<u32>,
+    ring_a_offset: u32,
+    ring_b_offset: u32,
+    subdivisions: usize,
+    closed: bool,
+) {
+    let subdivisions = subdivisions as u32;
+    for j in 0..subdivisions {
+        let next = if closed {
+            (j + 1) % subdivisions
+        } else {
+            j + 1
+        };
+        let (a, a_next) = (ring_a_offset + j, ring_a_offset + next);
+        let (b, b_next) = (ring_b_offset + j, ring_b_offset + next);
+        indices.extend_from_slice(&[a_next, a, b, b, b_next, a_next]);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct HalfCylinderPath {
     pub start: Vec3,
     pub forward: Vec3,
@@ -111,9 +185,59 @@ pub struct HalfCylinderPath {
     pub segment_length: f32,
     pub n_segments: usize,
     pub subdivisions: usize,
+    /// Subdivisions of the tube that colliders are built from, fewer than `subdivisions` to keep
+    /// collisions cheap while the tube is rendered smooth. `0` builds colliders from the same
+    /// subdivisions as the rendered tube.
+    #[serde(default)]
+    pub collider_subdivisions: usize,
     pub seed: u64,
     pub yaw_range: Range<f32>,
     pub pitch_range: Range<f32>,
+    pub max_cumulative_yaw: Option<f32>,
+    pub max_cumulative_pitch: Option<f32>,
+    pub bank_factor: f32,
+    /// Whether the path loops back around to its start, for lap-based racing. The heading of a
+    /// closed path turns through a full circle over its length, and the last segments are then
+    /// bent so that its end meets its start with the same direction.
+    ///
+    /// Closure is approximate: the end meets the start only to within floating point tolerance,
+    /// and the bent segments are stretched or squashed so [`HalfCylinderPath::length`] is only
+    /// an estimate of a closed path's length.
+    pub closed: bool,
+    /// Height of the walls along the open top edges of the tube, which keep fast balls from
+    /// flying out. `0.0` leaves the tube open.
+    #[serde(default)]
+    pub rail_height: f32,
+    /// Radius of the tube at each of its `n_segments + 1` rings, for funnels and pinches. If this
+    /// is empty, every ring has `radius`. If it is shorter, the last radius is repeated for the
+    /// remaining rings, and if it is longer, the extra radii are ignored.
+    #[serde(default)]
+    pub radii: Vec<f32>,
+    /// Indices of segments with no tube, which balls have to jump across. The path continues
+    /// through gaps as normal.
+    #[serde(default)]
+    pub gap_segments: HashSet<usize>,
+    /// Whether the tube goes all the way around, enclosing the balls so that they cannot escape
+    /// out of the top. Enclosed tubes have no rails, so `rail_height` is ignored.
+    #[serde(default)]
+    pub closed_top: bool,
+    /// Whether the tube faces inwards, to be seen from inside as balls and the follow camera are.
+    /// Otherwise its triangles are wound and its normals point to face outwards, for viewing an
+    /// enclosed tube from outside. Backfaces are culled, so the tube is only visible from the
+    /// side it faces.
+    #[serde(default = "default_normals_inward")]
+    pub normals_inward: bool,
+    /// Number of pillars standing in the tube for balls to dodge, placed at random from `seed`.
+    #[serde(default)]
+    pub obstacles: usize,
+    /// Number of bars spinning about the middle of the tube to knock balls about, placed at
+    /// random from `seed`.
+    #[serde(default)]
+    pub hazards: usize,
+}
+
+fn default_normals_inward() -> bool {
+    true
 }
 
 const NEGATIVE_Z: Vec3 = const_vec3!([0.0, 0.0, -1.0]);
@@ -124,7 +248,7 @@ const PITCH_RANGE: Range<f32> =
     (-0.9 * std::f32::consts::FRAC_PI_2)..(-0.1 * std::f32::consts::FRAC_PI_2);
 
 impl HalfCylinderPath {
-    pub const fn new() -> Self {
+    pub fn new() -> Self {
         Self {
             start: Vec3::ZERO,
             forward: NEGATIVE_Z,
@@ -132,11 +256,339 @@ impl HalfCylinderPath {
             segment_length: 1.0,
             n_segments: 100,
             subdivisions: 10,
+            collider_subdivisions: 0,
             seed: 1234,
             yaw_range: YAW_RANGE,
             pitch_range: PITCH_RANGE,
+            max_cumulative_yaw: None,
+            max_cumulative_pitch: None,
+            bank_factor: 0.0,
+            closed: false,
+            rail_height: 0.0,
+            radii: Vec::new(),
+            gap_segments: HashSet::new(),
+            closed_top: false,
+            normals_inward: true,
+            obstacles: 0,
+            hazards: 0,
+        }
+    }
+}
+
+/// Why a [`HalfCylinderPath`] cannot be built, as its tube would be degenerate.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PathError {
+    NonPositiveRadius(f32),
+    /// One of [`HalfCylinderPath::radii`], at the index of its ring, is not positive.
+    NonPositiveRingRadius(usize, f32),
+    NonPositiveSegmentLength(f32),
+    TooFewSubdivisions(usize),
+    TooFewColliderSubdivisions(usize),
+    NoSegments,
+    /// [`HalfCylinderPath::forward`] has no direction, or is not finite.
+    InvalidForward(Vec3),
+    NonFiniteYawRange(Range<f32>),
+    EmptyYawRange(Range<f32>),
+    NonFinitePitchRange(Range<f32>),
+    EmptyPitchRange(Range<f32>),
+}
+
+impl std::fmt::Display for PathError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NonPositiveRadius(radius) => {
+                write!(f, "radius must be positive, but is {}", radius)
+            }
+            Self::NonPositiveRingRadius(index, radius) => {
+                write!(
+                    f,
+                    "radius of ring {} must be positive, but is {}",
+                    index, radius
+                )
+            }
+            Self::NonPositiveSegmentLength(length) => {
+                write!(f, "segment length must be positive, but is {}", length)
+            }
+            Self::TooFewSubdivisions(subdivisions) => {
+                write!(
+                    f,
+                    "subdivisions must be at least 2, but is {}",
+                    subdivisions
+                )
+            }
+            Self::TooFewColliderSubdivisions(subdivisions) => {
+                write!(
+                    f,
+                    "collider subdivisions must be 0 or at least 2, but is {}",
+                    subdivisions
+                )
+            }
+            Self::NoSegments => write!(f, "path must have at least one segment"),
+            Self::InvalidForward(forward) => {
+                write!(
+                    f,
+                    "forward must be a finite, non-zero direction, but is {}",
+                    forward
+                )
+            }
+            Self::NonFiniteYawRange(range) => write!(f, "yaw range {:?} is not finite", range),
+            Self::EmptyYawRange(range) => write!(f, "yaw range {:?} is empty", range),
+            Self::NonFinitePitchRange(range) => {
+                write!(f, "pitch range {:?} is not finite", range)
+            }
+            Self::EmptyPitchRange(range) => write!(f, "pitch range {:?} is empty", range),
+        }
+    }
+}
+
+impl std::error::Error for PathError {}
+
+/// A cross-section of a [`HalfCylinderPath`] at the joint between two segments.
+#[derive(Clone, Copy, Debug)]
+pub struct PathSection {
+    /// Point on the centerline of the path.
+    pub position: Vec3,
+    /// Direction of travel along the path, averaged across the joint.
+    pub forward: Vec3,
+    /// Up direction of the path, averaged across the joint.
+    pub up: Vec3,
+    /// Radius of the tube at this section.
+    pub radius: f32,
+}
+
+impl HalfCylinderPath {
+    /// Checks that the path makes a tube with some volume, and can be generated. Paths that fail
+    /// would give degenerate triangles, from which colliders cannot be made.
+    pub fn validate(&self) -> Result<(), PathError> {
+        // NaN fails too
+        if self.radius.is_nan() || self.radius <= 0.0 {
+            return Err(PathError::NonPositiveRadius(self.radius));
+        }
+        if let Some((index, &radius)) = self
+            .radii
+            .iter()
+            .enumerate()
+            .take(self.n_segments + 1)
+            .find(|(_, &radius)| radius.is_nan() || radius <= 0.0)
+        {
+            return Err(PathError::NonPositiveRingRadius(index, radius));
+        }
+        if self.segment_length.is_nan() || self.segment_length <= 0.0 {
+            return Err(PathError::NonPositiveSegmentLength(self.segment_length));
+        }
+        if self.subdivisions < 2 {
+            return Err(PathError::TooFewSubdivisions(self.subdivisions));
+        }
+        if self.collider_subdivisions == 1 {
+            return Err(PathError::TooFewColliderSubdivisions(
+                self.collider_subdivisions,
+            ));
+        }
+        if self.n_segments < 1 {
+            return Err(PathError::NoSegments);
         }
+        if !self.forward.is_finite() || self.forward == Vec3::ZERO {
+            return Err(PathError::InvalidForward(self.forward));
+        }
+        // Random yaws and pitches are drawn from the ranges, and NaN is not finite
+        let finite = |range: &Range<f32>| range.start.is_finite() && range.end.is_finite();
+        if !finite(&self.yaw_range) {
+            return Err(PathError::NonFiniteYawRange(self.yaw_range.clone()));
+        }
+        if self.yaw_range.is_empty() {
+            return Err(PathError::EmptyYawRange(self.yaw_range.clone()));
+        }
+        if !finite(&self.pitch_range) {
+            return Err(PathError::NonFinitePitchRange(self.pitch_range.clone()));
+        }
+        if self.pitch_range.is_empty() {
+            return Err(PathError::EmptyPitchRange(self.pitch_range.clone()));
+        }
+        Ok(())
+    }
+
+    /// The same path with the subdivisions its colliders are built from, if they differ from
+    /// those of the rendered tube. Sections do not depend on subdivisions, so the meshes of the
+    /// two paths line up along their length.
+    pub fn collider_path(&self) -> Option<Self> {
+        if self.collider_subdivisions == 0 || self.collider_subdivisions == self.subdivisions {
+            return None;
+        }
+        Some(Self {
+            subdivisions: self.collider_subdivisions,
+            ..self.clone()
+        })
+    }
+
+    /// Total length of the path, along its centerline.
+    pub fn length(&self) -> f32 {
+        self.segment_length * self.n_segments as f32
+    }
+
+    /// Radius of the tube at ring `index`. See [`HalfCylinderPath::radii`].
+    pub fn ring_radius(&self, index: usize) -> f32 {
+        self.radii
+            .get(index)
+            .or_else(|| self.radii.last())
+            .copied()
+            .unwrap_or(self.radius)
+    }
+
+    /// The `n_segments + 1` cross-sections of the path from start to end.
+    pub fn sections(&self) -> Vec<PathSection> {
+        let worm_path_iter = WormPathIterator::new(
+            SmallRng::seed_from_u64(self.seed),
+            self.yaw_range.clone(),
+            self.pitch_range.clone(),
+        )
+        .with_max_cumulative_yaw(self.max_cumulative_yaw)
+        .with_max_cumulative_pitch(self.max_cumulative_pitch)
+        .with_bank_factor(self.bank_factor);
+        let mut position = self.start;
+        let mut prev_forward = self.forward;
+        let mut prev_up = Vec3::Y;
+        let mut sections = worm_path_iter
+            .take(self.n_segments + 1)
+            .enumerate()
+            .map(|(i, rotation)| {
+                let rotation = if self.closed {
+                    let turn = std::f32::consts::TAU * i as f32 / self.n_segments.max(1) as f32;
+                    Quat::from_rotation_y(turn) * rotation
+                } else {
+                    rotation
+                };
+                let forward = rotation * self.forward;
+                let up = rotation * Vec3::Y;
+                let section = PathSection {
+                    position,
+                    forward: (prev_forward + forward).normalize_or_zero(),
+                    up: (prev_up + up).normalize_or_zero(),
+                    radius: self.ring_radius(i),
+                };
+                position += forward * self.segment_length;
+                prev_forward = forward;
+                prev_up = up;
+                section
+            })
+            .collect::<Vec<_>>();
+        if self.closed {
+            close_loop(&mut sections);
+        }
+        sections
+    }
+
+    /// Splits the path into one mesh per segment, in order from the start. Normals are smoothed
+    /// across the whole path before it is split so that there are no lighting seams between
+    /// segments. The meshes of gap segments have no triangles.
+    pub fn into_segment_meshes(self) -> Vec<Mesh> {
+        let n_segments = self.n_segments;
+        let subdivisions = self.subdivisions;
+        let closed_top = self.closed_top;
+        let normals_inward = self.normals_inward;
+        let segment_vertex_count = ring_vertex_count(subdivisions, self.rail_height, closed_top);
+        let gap_segments = self.gap_segments.clone();
+        let mesh = Mesh::from(self);
+        let (positions, normals, uvs) = match (
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION),
+            mesh.attribute(Mesh::ATTRIBUTE_NORMAL),
+            mesh.attribute(Mesh::ATTRIBUTE_UV_0),
+        ) {
+            (
+                Some(VertexAttributeValues::Float32x3(positions)),
+                Some(VertexAttributeValues::Float32x3(normals)),
+                Some(VertexAttributeValues::Float32x2(uvs)),
+            ) => (positions, normals, uvs),
+            _ => unreachable!("Half cylinder path meshes have positions, normals, and UVs"),
+        };
+
+        (0..n_segments)
+            .map(|i| {
+                // Each segment spans its own ring of vertices and the next
+                let vertices = (i * segment_vertex_count)..((i + 2) * segment_vertex_count);
+                let mut segment_indices = Vec::new();
+                if !gap_segments.contains(&i) {
+                    connect_rings(
+                        &mut segment_indices,
+                        0,
+                        segment_vertex_count as u32,
+                        ring_quad_count(segment_vertex_count, subdivisions, closed_top),
+                        closed_top,
+                    );
+                    if !normals_inward {
+                        flip_winding(&mut segment_indices);
+                    }
+                }
+
+                let mut segment = Mesh::new(PrimitiveTopology::TriangleList);
+                segment.set_attribute(
+                    Mesh::ATTRIBUTE_POSITION,
+                    positions[vertices.clone()].to_vec(),
+                );
+                segment.set_attribute(Mesh::ATTRIBUTE_NORMAL, normals[vertices.clone()].to_vec());
+                segment.set_attribute(Mesh::ATTRIBUTE_UV_0, uvs[vertices].to_vec());
+                segment.set_indices(Some(Indices::U32(segment_indices)));
+                segment
+            })
+            .collect()
+    }
+}
+
+impl HalfCylinderPath {
+    /// One mesh covering just `segments` of the path, given its precomputed `sections`, for
+    /// building a long path a piece at a time. Positions match those of [`Mesh::from`], but
+    /// normals are only smoothed within the piece and V restarts from zero at its start.
+    pub fn segments_mesh(&self, sections: &[PathSection], segments: Range<usize>) -> Mesh {
+        let gap_segments = self
+            .gap_segments
+            .iter()
+            .filter(|index| segments.contains(index))
+            .map(|index| index - segments.start)
+            .collect();
+        tube_mesh(
+            &sections[segments.start..=segments.end],
+            self.subdivisions,
+            self.rail_height,
+            self.closed_top,
+            self.normals_inward,
+            &gap_segments,
+        )
+    }
+}
+
+/// Bends the final sections of a path so that the last one coincides with the first, easing the
+/// correction in over the second half of the path to avoid a kink. The bent sections are turned to
+/// face along the bent path, and the first and last sections to face along the join between them.
+fn close_loop(sections: &mut [PathSection]) {
+    let (first, last) = match (sections.first(), sections.last()) {
+        (Some(&first), Some(&last)) if sections.len() > 1 => (first, last),
+        _ => return,
+    };
+    let offset = first.position - last.position;
+    let n_bent = ((sections.len() - 1) / 2).max(1);
+    let bend_start = sections.len() - 1 - n_bent;
+    for (i, section) in sections.iter_mut().enumerate().skip(bend_start + 1) {
+        let t = (i - bend_start) as f32 / n_bent as f32;
+        let weight = t * t * (3.0 - 2.0 * t);
+        section.position += offset * weight;
+        section.up = section.up.lerp(first.up, weight).normalize_or_zero();
     }
+    let last_index = sections.len() - 1;
+    let direction = |from: usize, to: usize| {
+        (sections[to].position - sections[from].position).normalize_or_zero()
+    };
+    let forwards = (bend_start + 1..last_index)
+        .map(|i| (direction(i - 1, i) + direction(i, i + 1)).normalize_or_zero())
+        .collect::<Vec<_>>();
+    let join_forward =
+        (direction(last_index - 1, last_index) + direction(0, 1)).normalize_or_zero();
+    for (section, forward) in sections[bend_start + 1..last_index]
+        .iter_mut()
+        .zip(forwards)
+    {
+        section.forward = forward;
+    }
+    sections[0].forward = join_forward;
+    sections[last_index].forward = join_forward;
 }
 
 impl Default for HalfCylinderPath {
@@ -147,94 +599,1260 @@ impl Default for HalfCylinderPath {
 
 impl From<HalfCylinderPath> for Mesh {
     fn from(shape: HalfCylinderPath) -> Self {
-        let HalfCylinderPath {
-            start,
-            forward,
-            radius,
-            segment_length,
-            n_segments,
+        tube_mesh(
+            &shape.sections(),
+            shape.subdivisions,
+            shape.rail_height,
+            shape.closed_top,
+            shape.normals_inward,
+            &shape.gap_segments,
+        )
+    }
+}
+
+/// Number of vertices in each ring of a tube mesh.
+fn ring_vertex_count(subdivisions: usize, rail_height: f32, closed_top: bool) -> usize {
+    if closed_top {
+        return subdivisions;
+    }
+    let n_rail_vertices = if rail_height > 0.0 { 2 } else { 0 };
+    subdivisions + 1 + n_rail_vertices
+}
+
+/// Number of quads around each segment of a tube mesh, between rings of `ring_vertex_count`.
+fn ring_quad_count(ring_vertex_count: usize, subdivisions: usize, closed_top: bool) -> usize {
+    if closed_top {
+        subdivisions
+    } else {
+        ring_vertex_count - 1
+    }
+}
+
+/// Builds a half-cylinder tube through consecutive `sections`, with a ring of `subdivisions + 1`
+/// vertices around the lower half of each, at each section's radius. Segments in `gap_segments`
+/// are left without triangles. If `rail_height` is positive, each ring also has a
+/// vertex that far above each of its top edges, forming walls along the edges of the tube. If
+/// `closed_top` is set, the rings go all the way around instead, and there are no rails. Unless
+/// `normals_inward` is set, the triangles are flipped to face out of the tube.
+fn tube_mesh(
+    sections: &[PathSection],
+    subdivisions: usize,
+    rail_height: f32,
+    closed_top: bool,
+    normals_inward: bool,
+    gap_segments: &HashSet<usize>,
+) -> Mesh {
+    let rail_height = if closed_top { 0.0 } else { rail_height };
+    let n_segments = sections.len().saturating_sub(1);
+    let ring_vertex_count = ring_vertex_count(subdivisions, rail_height, closed_top);
+    let ring_quad_count = ring_quad_count(ring_vertex_count, subdivisions, closed_top);
+    let vertex_count = ring_vertex_count * sections.len();
+
+    let mut positions = Vec::with_capacity(vertex_count);
+    let mut normals = Vec::with_capacity(vertex_count);
+    let mut uvs = Vec::with_capacity(vertex_count);
+
+    let mut v = 0.0;
+    let mut prev_section = sections.first().copied();
+    for section in sections {
+        if let Some(prev_section) = prev_section {
+            // Scale V by the average radius of the segment, as the tube may narrow or widen
+            let distance = section.position.distance(prev_section.position);
+            v += uv_v(distance, 0.5 * (section.radius + prev_section.radius));
+        }
+        prev_section = Some(*section);
+        let right_dir = section.up.cross(-section.forward).normalize_or_zero();
+        let right = right_dir * section.radius;
+        let rail = (-section.forward).cross(right_dir) * rail_height;
+        // Rails extend U beyond 0..1 at the same scale as around the tube
+        let rail_u = rail_height / (std::f32::consts::PI * section.radius);
+        if rail_height > 0.0 {
+            positions.push((section.position + right + rail).to_array());
+            normals.push((-right_dir).to_array());
+            uvs.push([-rail_u, v]);
+        }
+        push_ring(
+            &mut positions,
+            &mut normals,
+            &mut uvs,
+            section.position,
+            section.forward,
+            section.up,
+            section.radius,
             subdivisions,
-            seed,
-            yaw_range,
-            pitch_range,
-        } = shape;
-        let vertex_count = (subdivisions + 1) * (n_segments + 1);
+            closed_top,
+            v,
+        );
+        if rail_height > 0.0 {
+            positions.push((section.position - right + rail).to_array());
+            normals.push(right_dir.to_array());
+            uvs.push([1.0 + rail_u, v]);
+        }
+    }
 
-        let mut positions = Vec::with_capacity(vertex_count);
-        let mut normals = Vec::with_capacity(vertex_count);
-        let mut uvs = Vec::with_capacity(vertex_count);
+    let mut indices = Vec::with_capacity(n_segments * ring_quad_count * 6);
+    for i in (0..n_segments).filter(|i| !gap_segments.contains(i)) {
+        let ring_offset = (i * ring_vertex_count) as u32;
+        connect_rings(
+            &mut indices,
+            ring_offset,
+            ring_offset + ring_vertex_count as u32,
+            ring_quad_count,
+            closed_top,
+        );
+    }
+    if !normals_inward {
+        flip_winding(&mut indices);
+    }
+    let indices = Indices::U32(indices);
 
-        let up = Vec3::Y;
-        let mut position = start;
-        let worm_path_iter = WormPathIterator {
-            rng: SmallRng::seed_from_u64(seed),
-            yaw_range,
-            pitch_range,
-        };
-        let mut prev_forward = forward;
-        for rotation in worm_path_iter.take(n_segments + 1) {
-            let forward = rotation * forward;
-            let forward_avg = (prev_forward + forward).normalize_or_zero();
-            let right = up.cross(-forward_avg).normalize_or_zero() * radius;
-            for i in 0..=subdivisions {
-                let offset = Quat::from_axis_angle(
-                    forward_avg,
-                    std::f32::consts::PI * i as f32 / subdivisions as f32,
-                ) * right;
-                let normal = (-offset.normalize_or_zero()).to_array();
-                positions.push((position + offset).to_array());
-                normals.push(normal);
-                uvs.push([0.0, 0.0]);
-            }
-            position += forward * segment_length;
-            prev_forward = forward;
-        }
-
-        let mut indices = Vec::with_capacity(n_segments * subdivisions * 6);
-        let segment_vertex_count = subdivisions as u32 + 1;
-        for i in 0..n_segments as u32 {
-            let segment_offset = segment_vertex_count * i;
-            for j in 0..subdivisions as u32 {
-                let offset = segment_offset + j;
-                indices.extend_from_slice(&[
-                    offset + 1,
-                    offset,
-                    offset + segment_vertex_count,
-                    offset + segment_vertex_count,
-                    offset + segment_vertex_count + 1,
-                    offset + 1,
-                ]);
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(indices));
+    // Normals follow the winding of the faces, so they point the same way as the faces face
+    smooth_normals(&mut mesh);
+    mesh
+}
+
+/// Reverses the winding of each triangle in a triangle list, so that it faces the other way.
+fn flip_winding(indices: &mut [u32]) {
+    for tri in indices.chunks_exact_mut(3) {
+        tri.swap(1, 2);
+    }
+}
+
+/// A hand-authored half-cylinder tube following a Catmull-Rom spline through `points`.
+pub struct ControlPointPath {
+    pub points: Vec<Vec3>,
+    pub radius: f32,
+    pub subdivisions: usize,
+    /// Number of segments the tube has between each pair of consecutive control points.
+    pub samples_per_segment: usize,
+}
+
+impl ControlPointPath {
+    /// The cross-sections of the path from the first control point to the last. The up direction
+    /// starts as close to +Y as the path allows and is carried along the path without twisting.
+    pub fn sections(&self) -> Vec<PathSection> {
+        let n_points = self.points.len();
+        if n_points < 2 {
+            return Vec::new();
+        }
+        let samples_per_segment = self.samples_per_segment.max(1);
+        // The end points are repeated so that the spline passes through every control point
+        let point = |i: isize| self.points[i.clamp(0, n_points as isize - 1) as usize];
+
+        let mut sections = Vec::with_capacity((n_points - 1) * samples_per_segment + 1);
+        let mut up = Vec3::Y;
+        for i in 0..(n_points - 1) as isize {
+            let [p0, p1, p2, p3] = [point(i - 1), point(i), point(i + 1), point(i + 2)];
+            let last_segment = i == n_points as isize - 2;
+            let n_samples = samples_per_segment + if last_segment { 1 } else { 0 };
+            for j in 0..n_samples {
+                let t = j as f32 / samples_per_segment as f32;
+                let (position, tangent) = catmull_rom(p0, p1, p2, p3, t);
+                let forward = tangent.normalize_or_zero();
+                // Remove the component of the previous up direction along the new forward
+                up = (up - forward * up.dot(forward)).normalize_or_zero();
+                if up == Vec3::ZERO {
+                    // The path turned straight up or down, so pick any perpendicular direction
+                    let axis = if forward.x.abs() < 0.9 {
+                        Vec3::X
+                    } else {
+                        Vec3::Y
+                    };
+                    up = forward.cross(axis).normalize();
+                }
+                sections.push(PathSection {
+                    position,
+                    forward,
+                    up,
+                    radius: self.radius,
+                });
             }
         }
-        let indices = Indices::U32(indices);
+        sections
+    }
+}
 
-        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
-        mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, positions);
-        mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
-        mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
-        mesh.set_indices(Some(indices));
-        mesh
+/// Position and tangent at `t` in 0..=1 on the uniform Catmull-Rom spline segment from `p1` to
+/// `p2`.
+fn catmull_rom(p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3, t: f32) -> (Vec3, Vec3) {
+    let a = 2.0 * p1;
+    let b = p2 - p0;
+    let c = 2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3;
+    let d = -p0 + 3.0 * p1 - 3.0 * p2 + p3;
+    let position = 0.5 * (a + b * t + c * t * t + d * t * t * t);
+    let tangent = 0.5 * (b + 2.0 * c * t + 3.0 * d * t * t);
+    (position, tangent)
+}
+
+impl From<ControlPointPath> for Mesh {
+    fn from(shape: ControlPointPath) -> Self {
+        tube_mesh(
+            &shape.sections(),
+            shape.subdivisions,
+            0.0,
+            false,
+            true,
+            &HashSet::new(),
+        )
     }
 }
 
-pub fn mesh_to_collider_shape(mesh: &Mesh) -> Option<ColliderShape> {
-    let vertices = if let Some(VertexAttributeValues::Float32x3(positions)) =
+/// The vertex indices of `indices`, whichever their width.
+pub fn index_list(indices: &Indices) -> Vec<usize> {
+    match indices {
+        Indices::U16(indices) => indices.iter().map(|&i| i as usize).collect(),
+        Indices::U32(indices) => indices.iter().map(|&i| i as usize).collect(),
+    }
+}
+
+/// Positions closer than this are treated as the same vertex when smoothing normals.
+const WELD_EPSILON: f32 = 1.0e-4;
+
+/// Replaces the normals of an indexed triangle list mesh with area-weighted averages of the normals
+/// of all faces sharing each vertex position, welding coincident vertices so that there are no
+/// lighting seams where separately-generated rings meet. Only positions that coincide are welded,
+/// so the open top edges of a half-cylinder, which are on opposite sides of the tube, stay apart.
+pub fn smooth_normals(mesh: &mut Mesh) {
+    let positions = if let Some(VertexAttributeValues::Float32x3(positions)) =
         mesh.attribute(Mesh::ATTRIBUTE_POSITION)
     {
         positions
             .iter()
-            .map(|p| Point3::from_slice(p))
+            .map(|p| Vec3::from_slice(p))
             .collect::<Vec<_>>()
     } else {
-        return None;
+        return;
+    };
+    let indices = if let Some(indices) = mesh.indices() {
+        index_list(indices)
+    } else {
+        return;
+    };
+
+    let weld_key = |p: Vec3| {
+        let p = (p / WELD_EPSILON).round();
+        [p.x as i64, p.y as i64, p.z as i64]
     };
-    let indices = if let Some(Indices::U32(indices)) = mesh.indices() {
-        indices
+    let mut welded_normals = HashMap::with_capacity(positions.len());
+    for tri in indices.chunks_exact(3) {
+        let [a, b, c] = [positions[tri[0]], positions[tri[1]], positions[tri[2]]];
+        // Counter-clockwise winding is front-facing, and the cross product's length is twice the
+        // triangle's area, giving larger faces more weight
+        let face_normal = (b - a).cross(c - a);
+        for &i in tri {
+            *welded_normals
+                .entry(weld_key(positions[i]))
+                .or_insert(Vec3::ZERO) += face_normal;
+        }
+    }
+
+    let normals = positions
+        .iter()
+        .map(|&p| {
+            welded_normals
+                .get(&weld_key(p))
+                .map_or(Vec3::ZERO, |n| n.normalize_or_zero())
+                .to_array()
+        })
+        .collect::<Vec<_>>();
+    mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+}
+
+/// The kind of collider shape to build from a track mesh.
+#[derive(Clone)]
+pub enum ColliderKind {
+    /// An exact triangle mesh collider. Cheap to build but thin, with many triangles for long paths.
+    TriMesh,
+    /// A compound of convex hulls approximating the mesh. Expensive to build but far fewer, solid
+    /// pieces for the broad phase and CCD to deal with.
+    ConvexDecomposition(VHACDParameters),
+}
+
+impl Default for ColliderKind {
+    fn default() -> Self {
+        Self::TriMesh
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MeshColliderError {
+    MissingPositions,
+    UnsupportedPositionFormat,
+    MissingIndices,
+    /// The indices do not make a list of triangles of the mesh's vertices.
+    UnsupportedIndexFormat,
+}
+
+impl std::fmt::Display for MeshColliderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingPositions => write!(f, "mesh has no vertex positions"),
+            Self::UnsupportedPositionFormat => {
+                write!(f, "mesh vertex positions are not in a supported format")
+            }
+            Self::MissingIndices => write!(f, "mesh has no indices"),
+            Self::UnsupportedIndexFormat => {
+                write!(
+                    f,
+                    "mesh indices are not a list of triangles of its vertices"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for MeshColliderError {}
+
+pub fn mesh_to_collider(
+    mesh: &Mesh,
+    kind: &ColliderKind,
+) -> Result<ColliderShape, MeshColliderError> {
+    match kind {
+        ColliderKind::TriMesh => mesh_to_collider_shape(mesh),
+        ColliderKind::ConvexDecomposition(params) => {
+            mesh_to_convex_decomposition(mesh, params.clone())
+        }
+    }
+}
+
+/// The vertices and triangles of a mesh, as colliders are built from.
+type ColliderTriangles = (Vec<Point3<f32>>, Vec<[u32; 3]>);
+
+fn mesh_vertices_and_indices(mesh: &Mesh) -> Result<ColliderTriangles, MeshColliderError> {
+    let vertices = match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+        Some(VertexAttributeValues::Float32x3(positions)) => positions
+            .iter()
+            .map(|p| Point3::from_slice(p))
+            .collect::<Vec<_>>(),
+        // Homogeneous positions, as sometimes found in imported assets, just drop w
+        Some(VertexAttributeValues::Float32x4(positions)) => positions
+            .iter()
+            .map(|p| Point3::new(p[0], p[1], p[2]))
+            .collect::<Vec<_>>(),
+        Some(_) => return Err(MeshColliderError::UnsupportedPositionFormat),
+        None => return Err(MeshColliderError::MissingPositions),
+    };
+    let indices = match mesh.indices() {
+        Some(Indices::U32(indices)) if indices.len() % 3 == 0 => indices
             .chunks_exact(3)
             .map(|tri| [tri[0], tri[1], tri[2]])
-            .collect::<Vec<_>>()
+            .collect::<Vec<_>>(),
+        Some(Indices::U16(indices)) if indices.len() % 3 == 0 => indices
+            .chunks_exact(3)
+            .map(|tri| [tri[0] as u32, tri[1] as u32, tri[2] as u32])
+            .collect::<Vec<_>>(),
+        Some(_) => return Err(MeshColliderError::UnsupportedIndexFormat),
+        None => return Err(MeshColliderError::MissingIndices),
+    };
+    // Strips and lines would be read as the wrong triangles, and out of range indices would panic
+    if mesh.primitive_topology() != PrimitiveTopology::TriangleList
+        || indices
+            .iter()
+            .flatten()
+            .any(|&index| index as usize >= vertices.len())
+    {
+        return Err(MeshColliderError::UnsupportedIndexFormat);
+    }
+    Ok((vertices, indices))
+}
+
+pub fn mesh_to_collider_shape(mesh: &Mesh) -> Result<ColliderShape, MeshColliderError> {
+    let (vertices, indices) = mesh_vertices_and_indices(mesh)?;
+    Ok(ColliderShape::trimesh(vertices, indices))
+}
+
+pub fn mesh_to_convex_decomposition(
+    mesh: &Mesh,
+    params: VHACDParameters,
+) -> Result<ColliderShape, MeshColliderError> {
+    let (vertices, indices) = mesh_vertices_and_indices(mesh)?;
+    Ok(ColliderShape::convex_decomposition_with_params(
+        &vertices, &indices, &params,
+    ))
+}
+
+/// Subdivisions around the circumference of cylinders built by [`collider_to_mesh`].
+const CYLINDER_SUBDIVISIONS: u32 = 24;
+
+/// Builds a mesh of the triangles of a collider `shape`, the reverse of [`mesh_to_collider`], for
+/// drawing colliders to check them against what is rendered. The shapes that
+/// [`mesh_to_collider`] builds are supported, triangle meshes and compounds of convex polyhedra, as
+/// are the cylinders and cuboids of obstacles.
+///
+/// The mesh has smoothed normals but placeholder texture coordinates.
+pub fn collider_to_mesh(shape: &ColliderShape) -> Option<Mesh> {
+    let (vertices, indices) = if let Some(cylinder) = shape.as_cylinder() {
+        cylinder.to_trimesh(CYLINDER_SUBDIVISIONS)
+    } else if let Some(cuboid) = shape.as_cuboid() {
+        cuboid.to_trimesh()
+    } else if let Some(trimesh) = shape.as_trimesh() {
+        (trimesh.vertices().to_vec(), trimesh.indices().to_vec())
+    } else if let Some(compound) = shape.as_compound() {
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        for (isometry, part) in compound.shapes() {
+            let (part_vertices, part_indices) = part.as_convex_polyhedron()?.to_trimesh();
+            let offset = vertices.len() as u32;
+            vertices.extend(part_vertices.iter().map(|vertex| isometry * vertex));
+            indices.extend(
+                part_indices
+                    .iter()
+                    .map(|tri| [tri[0] + offset, tri[1] + offset, tri[2] + offset]),
+            );
+        }
+        (vertices, indices)
     } else {
         return None;
     };
-    Some(ColliderShape::trimesh(vertices, indices))
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.set_attribute(
+        Mesh::ATTRIBUTE_POSITION,
+        vertices
+            .iter()
+            .map(|vertex| [vertex.x, vertex.y, vertex.z])
+            .collect::<Vec<_>>(),
+    );
+    mesh.set_attribute(
+        Mesh::ATTRIBUTE_NORMAL,
+        vec![[0.0, 1.0, 0.0]; vertices.len()],
+    );
+    mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0.0, 0.0]; vertices.len()]);
+    mesh.set_indices(Some(Indices::U32(indices.into_iter().flatten().collect())));
+    smooth_normals(&mut mesh);
+    Some(mesh)
+}
+
+/// Writes an indexed triangle list `mesh` to `writer` in Wavefront OBJ format, including normals
+/// and texture coordinates if the mesh has them.
+pub fn export_obj(mesh: &Mesh, writer: &mut impl Write) -> io::Result<()> {
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidInput, message.to_string());
+    if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+        return Err(invalid("Only triangle list meshes can be exported"));
+    }
+    let positions = match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+        Some(VertexAttributeValues::Float32x3(positions)) => positions,
+        _ => return Err(invalid("Mesh has no Float32x3 positions")),
+    };
+    let normals = match mesh.attribute(Mesh::ATTRIBUTE_NORMAL) {
+        Some(VertexAttributeValues::Float32x3(normals)) => Some(normals),
+        _ => None,
+    };
+    let uvs = match mesh.attribute(Mesh::ATTRIBUTE_UV_0) {
+        Some(VertexAttributeValues::Float32x2(uvs)) => Some(uvs),
+        _ => None,
+    };
+    let indices = match mesh.indices() {
+        Some(indices) => index_list(indices),
+        None => return Err(invalid("Mesh has no indices")),
+    };
+
+    for [x, y, z] in positions {
+        writeln!(writer, "v {} {} {}", x, y, z)?;
+    }
+    for [u, v] in uvs.into_iter().flatten() {
+        writeln!(writer, "vt {} {}", u, v)?;
+    }
+    for [x, y, z] in normals.into_iter().flatten() {
+        writeln!(writer, "vn {} {} {}", x, y, z)?;
+    }
+    // OBJ indices are 1-based
+    let vertex = |index: usize| match (uvs.is_some(), normals.is_some()) {
+        (true, true) => format!("{0}/{0}/{0}", index + 1),
+        (true, false) => format!("{0}/{0}", index + 1),
+        (false, true) => format!("{0}//{0}", index + 1),
+        (false, false) => format!("{}", index + 1),
+    };
+    for triangle in indices.chunks_exact(3) {
+        writeln!(
+            writer,
+            "f {} {} {}",
+            vertex(triangle[0]),
+            vertex(triangle[1]),
+            vertex(triangle[2])
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uvs(mesh: &Mesh) -> &[[f32; 2]] {
+        match mesh.attribute(Mesh::ATTRIBUTE_UV_0) {
+            Some(VertexAttributeValues::Float32x2(uvs)) => uvs,
+            _ => panic!("Mesh has no UVs"),
+        }
+    }
+
+    fn positions(mesh: &Mesh) -> &[[f32; 3]] {
+        match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+            Some(VertexAttributeValues::Float32x3(positions)) => positions,
+            _ => panic!("Mesh has no positions"),
+        }
+    }
+
+    fn normals(mesh: &Mesh) -> Vec<Vec3> {
+        match mesh.attribute(Mesh::ATTRIBUTE_NORMAL) {
+            Some(VertexAttributeValues::Float32x3(normals)) => {
+                normals.iter().map(|n| Vec3::from_slice(n)).collect()
+            }
+            _ => panic!("Mesh has no normals"),
+        }
+    }
+
+    /// One mesh with all the vertices and triangles of `meshes`, duplicating the vertices where
+    /// they meet.
+    fn concatenate(meshes: &[Mesh]) -> Mesh {
+        let mut all_positions = Vec::new();
+        let mut all_normals = Vec::new();
+        let mut all_indices = Vec::new();
+        for mesh in meshes {
+            let offset = all_positions.len();
+            all_indices.extend(
+                index_list(mesh.indices().unwrap())
+                    .into_iter()
+                    .map(|i| (offset + i) as u32),
+            );
+            all_positions.extend_from_slice(positions(mesh));
+            all_normals.extend(normals(mesh).iter().map(|n| n.to_array()));
+        }
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, all_positions);
+        mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, all_normals);
+        mesh.set_indices(Some(Indices::U32(all_indices)));
+        mesh
+    }
+
+    #[test]
+    fn path_uvs_span_rings_and_increase_along_path() {
+        let path = HalfCylinderPath {
+            n_segments: 5,
+            ..Default::default()
+        };
+        let subdivisions = path.subdivisions;
+        let mesh = Mesh::from(path);
+        let rings = uvs(&mesh)
+            .chunks_exact(subdivisions + 1)
+            .collect::<Vec<_>>();
+        assert_eq!(rings.len(), 6);
+        for ring in &rings {
+            assert_eq!(ring[0][0], 0.0);
+            assert_eq!(ring[subdivisions][0], 1.0);
+            assert!(ring.windows(2).all(|uv| uv[0][0] < uv[1][0]));
+            assert!(ring.iter().all(|uv| uv[1] == ring[0][1]));
+        }
+        assert_eq!(rings[0][0][1], 0.0);
+        assert!(rings
+            .windows(2)
+            .all(|rings| rings[0][0][1] < rings[1][0][1]));
+    }
+
+    #[test]
+    fn half_cylinder_uvs_span_ring_and_length() {
+        let half_cylinder = HalfCylinder::from_radius_and_length(0.5, 2.0);
+        let subdivisions = half_cylinder.subdivisions;
+        let mesh = Mesh::from(half_cylinder);
+        let uvs = uvs(&mesh);
+        let (start, end) = uvs.split_at(subdivisions + 1);
+        assert_eq!((start[0][0], start[subdivisions][0]), (0.0, 1.0));
+        assert_eq!((end[0][0], end[subdivisions][0]), (0.0, 1.0));
+        assert!(start.iter().all(|uv| uv[1] == 0.0));
+        // Texels are square, so V reaches the length over the half circumference
+        assert!(end
+            .iter()
+            .all(|uv| (uv[1] - 2.0 / std::f32::consts::FRAC_PI_2).abs() < 1.0e-5));
+    }
+
+    #[test]
+    fn smoothing_welds_segment_joins_but_not_open_edges() {
+        let segment = |start, end| {
+            Mesh::from(HalfCylinder {
+                start,
+                end,
+                ..Default::default()
+            })
+        };
+        let mut mesh = concatenate(&[
+            segment(Vec3::ZERO, -Vec3::Z),
+            segment(-Vec3::Z, -2.0 * Vec3::Z),
+        ]);
+        let subdivisions = HalfCylinder::default().subdivisions;
+        let ring = subdivisions + 1;
+        let radial = normals(&mesh);
+        smooth_normals(&mut mesh);
+        let smoothed = normals(&mesh);
+
+        // The duplicated rings where the segments join are welded into one, which takes the faces
+        // either side evenly, so a straight tube stays round with normals pointing straight in
+        for i in 0..ring {
+            assert!(smoothed[ring + i].abs_diff_eq(smoothed[2 * ring + i], 1.0e-6));
+            if i != 0 && i != subdivisions {
+                assert!(smoothed[ring + i].abs_diff_eq(radial[ring + i], 1.0e-5));
+            }
+        }
+        // The edges either side of the open top only take the faces on their own side
+        let (left, right) = (smoothed[0], smoothed[subdivisions]);
+        assert!(left.dot(radial[0]) > 0.9);
+        assert!(right.dot(radial[subdivisions]) > 0.9);
+        assert!(left.x * right.x < 0.0);
+    }
+
+    /// A triangle list mesh with `positions` and `indices`, such as [`SQUARE_POSITIONS`].
+    fn square(positions: impl Into<VertexAttributeValues>, indices: Option<Indices>) -> Mesh {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.set_indices(indices);
+        mesh
+    }
+
+    const SQUARE_POSITIONS: [[f32; 3]; 4] = [
+        [0.0, 0.0, 0.0],
+        [1.0, 0.0, 0.0],
+        [1.0, 0.0, 1.0],
+        [0.0, 0.0, 1.0],
+    ];
+
+    #[test]
+    fn malformed_meshes_give_matching_errors() {
+        let no_positions = Mesh::new(PrimitiveTopology::TriangleList);
+        assert_eq!(
+            mesh_to_collider_shape(&no_positions).err(),
+            Some(MeshColliderError::MissingPositions)
+        );
+        let flat_positions = square(
+            vec![[0.0f32, 0.0], [1.0, 0.0], [1.0, 1.0]],
+            Some(Indices::U32(vec![0, 1, 2])),
+        );
+        assert_eq!(
+            mesh_to_collider_shape(&flat_positions).err(),
+            Some(MeshColliderError::UnsupportedPositionFormat)
+        );
+        let no_indices = square(SQUARE_POSITIONS.to_vec(), None);
+        assert_eq!(
+            mesh_to_collider_shape(&no_indices).err(),
+            Some(MeshColliderError::MissingIndices)
+        );
+        let partial_triangle = square(
+            SQUARE_POSITIONS.to_vec(),
+            Some(Indices::U32(vec![0, 2, 1, 0])),
+        );
+        let out_of_range = square(
+            SQUARE_POSITIONS.to_vec(),
+            Some(Indices::U16(vec![0, 2, 1, 0, 4, 2])),
+        );
+        let mut strip = Mesh::new(PrimitiveTopology::TriangleStrip);
+        strip.set_attribute(Mesh::ATTRIBUTE_POSITION, SQUARE_POSITIONS.to_vec());
+        strip.set_indices(Some(Indices::U32(vec![0, 1, 3, 2])));
+        for mesh in [partial_triangle, out_of_range, strip] {
+            assert_eq!(
+                mesh_to_collider_shape(&mesh).err(),
+                Some(MeshColliderError::UnsupportedIndexFormat)
+            );
+        }
+    }
+
+    #[test]
+    fn u16_indexed_mesh_becomes_trimesh() {
+        let mesh = square(
+            SQUARE_POSITIONS.to_vec(),
+            Some(Indices::U16(vec![0, 2, 1, 0, 3, 2])),
+        );
+        let shape = mesh_to_collider_shape(&mesh).unwrap();
+        let trimesh = shape.as_trimesh().expect("Collider is not a trimesh");
+        assert_eq!(trimesh.indices(), &[[0, 2, 1], [0, 3, 2]]);
+        assert_eq!(
+            trimesh
+                .vertices()
+                .iter()
+                .map(|p| [p.x, p.y, p.z])
+                .collect::<Vec<_>>(),
+            SQUARE_POSITIONS
+        );
+    }
+
+    #[test]
+    fn homogeneous_positions_drop_w() {
+        let positions = SQUARE_POSITIONS
+            .iter()
+            .map(|&[x, y, z]| [x, y, z, 1.0])
+            .collect::<Vec<_>>();
+        let mesh = square(positions, Some(Indices::U32(vec![0, 2, 1, 0, 3, 2])));
+        let shape = mesh_to_collider_shape(&mesh).unwrap();
+        let trimesh = shape.as_trimesh().expect("Collider is not a trimesh");
+        assert_eq!(trimesh.vertices()[2], Point3::new(1.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn same_seed_gives_identical_meshes() {
+        let mesh = |seed| {
+            Mesh::from(HalfCylinderPath {
+                seed,
+                ..Default::default()
+            })
+        };
+        let (a, b) = (mesh(7), mesh(7));
+        assert_eq!(positions(&a), positions(&b));
+        assert_eq!(normals(&a), normals(&b));
+        assert_eq!(uvs(&a), uvs(&b));
+        assert_eq!(
+            index_list(a.indices().unwrap()),
+            index_list(b.indices().unwrap())
+        );
+        assert_ne!(positions(&a), positions(&mesh(8)));
+    }
+
+    #[test]
+    fn one_segment_mesh_per_segment() {
+        let path = HalfCylinderPath {
+            n_segments: 7,
+            gap_segments: [3].into_iter().collect(),
+            ..Default::default()
+        };
+        let whole = Mesh::from(path.clone());
+        let segments = path.into_segment_meshes();
+        assert_eq!(segments.len(), 7);
+        let triangles = |mesh: &Mesh| mesh.indices().map_or(0, |i| index_list(i).len() / 3);
+        assert_eq!(triangles(&segments[3]), 0);
+        assert!(segments
+            .iter()
+            .enumerate()
+            .all(|(i, segment)| i == 3 || triangles(segment) > 0));
+        assert_eq!(
+            segments.iter().map(triangles).sum::<usize>(),
+            triangles(&whole)
+        );
+    }
+
+    #[test]
+    fn closed_path_ends_at_its_start() {
+        for seed in 0..5 {
+            let path = HalfCylinderPath {
+                seed,
+                n_segments: 20,
+                closed: true,
+                ..Default::default()
+            };
+            let sections = path.sections();
+            let (first, last) = (sections[0], sections[sections.len() - 1]);
+            assert!(
+                last.position.distance(first.position) < path.radius,
+                "Seed {} ends {} from its start",
+                seed,
+                last.position.distance(first.position)
+            );
+            assert!(last.forward.dot(first.forward) > 0.99);
+            // Rings face along the segments either side of them, so that bending the path does not
+            // pinch the tube
+            for window in sections.windows(3) {
+                for (from, to) in [(window[0], window[1]), (window[1], window[2])] {
+                    let along = (to.position - from.position).normalize();
+                    assert!(
+                        window[1].forward.dot(along) > 0.5,
+                        "Seed {} has a ring facing {} along the path",
+                        seed,
+                        window[1].forward.dot(along)
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn control_point_tube_passes_through_points() {
+        let points = vec![
+            Vec3::ZERO,
+            Vec3::new(0.0, -10.0, -50.0),
+            Vec3::new(30.0, -20.0, -100.0),
+            Vec3::new(0.0, -30.0, -150.0),
+        ];
+        let path = ControlPointPath {
+            points: points.clone(),
+            radius: 5.0,
+            subdivisions: 8,
+            samples_per_segment: 4,
+        };
+        let sections = path.sections();
+        assert_eq!(sections.len(), 3 * 4 + 1);
+        let mesh = Mesh::from(path);
+        for point in points {
+            let nearest_section = sections
+                .iter()
+                .map(|section| section.position.distance(point))
+                .reduce(f32::min)
+                .unwrap();
+            assert!(nearest_section < 1e-4);
+            // The ring around the point is on the surface of the tube
+            assert!(positions(&mesh)
+                .iter()
+                .any(|&p| (Vec3::from(p).distance(point) - 5.0).abs() < 1e-3));
+        }
+    }
+
+    #[test]
+    fn exported_obj_has_every_vertex_and_triangle() {
+        let mut mesh = Mesh::from(HalfCylinder::default());
+        let n_vertices = positions(&mesh).len();
+        let indices = index_list(mesh.indices().unwrap());
+        let u16_indices = Indices::U16(indices.iter().map(|&i| i as u16).collect());
+        for mesh_indices in [mesh.indices().unwrap().clone(), u16_indices] {
+            mesh.set_indices(Some(mesh_indices));
+            let mut obj = Vec::new();
+            export_obj(&mesh, &mut obj).unwrap();
+            let obj = String::from_utf8(obj).unwrap();
+
+            let count = |record: &str| obj.lines().filter(|l| l.starts_with(record)).count();
+            assert_eq!(count("v "), n_vertices);
+            assert_eq!(count("vt "), n_vertices);
+            assert_eq!(count("vn "), n_vertices);
+            let faces = obj
+                .lines()
+                .filter_map(|l| l.strip_prefix("f "))
+                .map(|face| {
+                    face.split(' ')
+                        .map(|vertex| {
+                            let [v, vt, vn]: [usize; 3] = vertex
+                                .split('/')
+                                .map(|i| i.parse().unwrap())
+                                .collect::<Vec<_>>()
+                                .try_into()
+                                .unwrap();
+                            assert_eq!((v, v), (vt, vn));
+                            v
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(faces.len(), indices.len() / 3);
+            assert!(faces.iter().all(|face| face.len() == 3));
+            // OBJ indices are 1-based
+            assert_eq!(
+                faces
+                    .into_iter()
+                    .flatten()
+                    .map(|v| v - 1)
+                    .collect::<Vec<_>>(),
+                indices
+            );
+        }
+    }
+
+    #[test]
+    fn rails_add_a_wall_along_each_top_edge() {
+        let path = HalfCylinderPath {
+            n_segments: 6,
+            pitch_range: -0.01..0.0,
+            yaw_range: -0.01..0.01,
+            ..Default::default()
+        };
+        let railed = HalfCylinderPath {
+            rail_height: 0.3,
+            ..path.clone()
+        };
+        let (n_rings, subdivisions) = (path.n_segments + 1, path.subdivisions);
+        let (open, railed) = (Mesh::from(path), Mesh::from(railed));
+        assert_eq!(positions(&open).len(), n_rings * (subdivisions + 1));
+        assert_eq!(positions(&railed).len(), n_rings * (subdivisions + 3));
+        let triangles = |mesh: &Mesh| index_list(mesh.indices().unwrap()).len() / 3;
+        assert_eq!(triangles(&railed), triangles(&open) + 6 * 2 * 2);
+
+        // The first and last vertex of each ring are the rails, above its top edges
+        for (open_ring, railed_ring) in positions(&open)
+            .chunks_exact(subdivisions + 1)
+            .zip(positions(&railed).chunks_exact(subdivisions + 3))
+        {
+            assert_eq!(&railed_ring[1..subdivisions + 2], open_ring);
+            for (rail, edge) in [
+                (railed_ring[0], open_ring[0]),
+                (railed_ring[subdivisions + 2], open_ring[subdivisions]),
+            ] {
+                let rise = Vec3::from(rail) - Vec3::from(edge);
+                assert!((rise.length() - 0.3).abs() < 1e-4);
+                assert!(rise.y > 0.29);
+            }
+        }
+
+        // The collider is built from the same mesh, so it has the rails too
+        let shape = mesh_to_collider_shape(&railed).unwrap();
+        let trimesh = shape.as_trimesh().unwrap();
+        assert_eq!(trimesh.vertices().len(), positions(&railed).len());
+        assert_eq!(trimesh.indices().len(), triangles(&railed));
+    }
+
+    #[test]
+    fn rings_have_their_own_radii() {
+        let path = HalfCylinderPath {
+            n_segments: 6,
+            radii: vec![1.0, 2.0, 1.0, 2.0, 0.5],
+            ..Default::default()
+        };
+        let subdivisions = path.subdivisions;
+        let sections = path.sections();
+        let mesh = Mesh::from(path);
+        let rings = positions(&mesh)
+            .chunks_exact(subdivisions + 1)
+            .collect::<Vec<_>>();
+        // The last radius is repeated for the rings past the end of `radii`
+        let expected = [1.0, 2.0, 1.0, 2.0, 0.5, 0.5, 0.5];
+        assert_eq!(rings.len(), expected.len());
+        for ((ring, section), radius) in rings.iter().zip(&sections).zip(expected) {
+            assert_eq!(section.radius, radius);
+            let width = Vec3::from(ring[0]).distance(Vec3::from(ring[subdivisions]));
+            assert!((width - 2.0 * radius).abs() < 1e-4);
+            assert!(ring
+                .iter()
+                .all(|&p| (Vec3::from(p).distance(section.position) - radius).abs() < 1e-4));
+        }
+    }
+
+    #[test]
+    fn gap_segments_have_no_triangles() {
+        let path = HalfCylinderPath {
+            n_segments: 8,
+            gap_segments: [2, 5, 6].into_iter().collect(),
+            ..Default::default()
+        };
+        let ring_vertex_count = path.subdivisions + 1;
+        let gapless = Mesh::from(HalfCylinderPath {
+            gap_segments: HashSet::new(),
+            ..path.clone()
+        });
+        let mesh = Mesh::from(path);
+        // The path continues through the gaps, so every ring is still there
+        assert_eq!(positions(&mesh), positions(&gapless));
+        let indices = index_list(mesh.indices().unwrap());
+        assert_eq!(
+            indices.len(),
+            index_list(gapless.indices().unwrap()).len() * 5 / 8
+        );
+        for triangle in indices.chunks_exact(3) {
+            let segment = triangle.iter().min().unwrap() / ring_vertex_count;
+            assert!(![2, 5, 6].contains(&segment));
+        }
+
+        let shape = mesh_to_collider_shape(&mesh).unwrap();
+        assert_eq!(
+            shape.as_trimesh().unwrap().indices().len(),
+            indices.len() / 3
+        );
+    }
+
+    /// The corners of the box bounding the vertices of `mesh`.
+    fn mesh_bounds(mesh: &Mesh) -> (Vec3, Vec3) {
+        positions(mesh).iter().map(|&p| Vec3::from(p)).fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), p| (min.min(p), max.max(p)),
+        )
+    }
+
+    #[test]
+    fn half_cylinder_constructors_give_its_dimensions() {
+        let half_cylinder = HalfCylinder::from_radius_and_length(2.0, 10.0);
+        assert_eq!(half_cylinder.radius, 2.0);
+        assert_eq!(half_cylinder.start, Vec3::new(0.0, 0.0, -5.0));
+        assert_eq!(half_cylinder.end, Vec3::new(0.0, 0.0, 5.0));
+        let (min, max) = mesh_bounds(&Mesh::from(half_cylinder));
+        assert!(min.abs_diff_eq(Vec3::new(-2.0, -2.0, -5.0), 1e-5));
+        assert!(max.abs_diff_eq(Vec3::new(2.0, 0.0, 5.0), 1e-5));
+
+        // A transform of the same scale would stretch a unit half-cylinder to fill the same space
+        let half_cylinder = HalfCylinder::from_scale(Vec3::new(4.0, 2.0, 10.0));
+        assert_eq!(half_cylinder.radius, 2.0);
+        assert_eq!(half_cylinder.start, Vec3::new(0.0, 0.0, -5.0));
+        assert_eq!(half_cylinder.end, Vec3::new(0.0, 0.0, 5.0));
+        assert_eq!(half_cylinder.subdivisions, HalfCylinder::new().subdivisions);
+        // Only the width sets the radius, whatever the depth
+        for depth in [0.5, 4.0, 8.0] {
+            let deeper = HalfCylinder::from_scale(Vec3::new(4.0, depth, 10.0));
+            assert_eq!(deeper.radius, half_cylinder.radius);
+        }
+    }
+
+    #[test]
+    fn half_cylinder_mesh_matches_golden() {
+        let mesh = Mesh::from(HalfCylinder {
+            subdivisions: 2,
+            ..HalfCylinder::from_radius_and_length(1.0, 2.0)
+        });
+        let expected_positions = [
+            [-1.0, 0.0, -1.0],
+            [0.0, -1.0, -1.0],
+            [1.0, 0.0, -1.0],
+            [-1.0, 0.0, 1.0],
+            [0.0, -1.0, 1.0],
+            [1.0, 0.0, 1.0],
+        ];
+        let expected_normals = [
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [-1.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0],
+            [-1.0, 0.0, 0.0],
+        ];
+        let v_end = 2.0 / std::f32::consts::PI;
+        let expected_uvs = [
+            [0.0, 0.0],
+            [0.5, 0.0],
+            [1.0, 0.0],
+            [0.0, v_end],
+            [0.5, v_end],
+            [1.0, v_end],
+        ];
+        for (actual, expected) in positions(&mesh).iter().zip(expected_positions) {
+            assert!(Vec3::from(*actual).abs_diff_eq(Vec3::from(expected), 1e-6));
+        }
+        for (actual, expected) in normals(&mesh).into_iter().zip(expected_normals) {
+            assert!(actual.abs_diff_eq(Vec3::from(expected), 1e-6));
+        }
+        assert_eq!(positions(&mesh).len(), expected_positions.len());
+        assert_eq!(uvs(&mesh), expected_uvs);
+        assert_eq!(
+            index_list(mesh.indices().unwrap()),
+            vec![1, 0, 3, 3, 4, 1, 2, 1, 4, 4, 5, 2]
+        );
+    }
+
+    #[test]
+    fn closed_top_rings_go_all_the_way_around() {
+        let path = HalfCylinderPath {
+            n_segments: 3,
+            subdivisions: 8,
+            rail_height: 1.0,
+            closed_top: true,
+            ..Default::default()
+        };
+        let mesh = Mesh::from(path.clone());
+        // No vertex is repeated where a ring closes, and there are no rails
+        assert_eq!(positions(&mesh).len(), 8 * 4);
+        let indices = index_list(mesh.indices().unwrap());
+        assert_eq!(indices.len(), 3 * 8 * 6);
+        // The last subdivision of the first ring joins back to its first
+        assert!(indices
+            .chunks_exact(3)
+            .any(|triangle| triangle.contains(&7) && triangle.contains(&0)));
+
+        // Every edge is shared by two triangles, except around the open ends of the tube
+        let mut edge_counts = HashMap::new();
+        for triangle in indices.chunks_exact(3) {
+            for k in 0..3 {
+                let (a, b) = (triangle[k], triangle[(k + 1) % 3]);
+                *edge_counts.entry((a.min(b), a.max(b))).or_insert(0) += 1;
+            }
+        }
+        let open_edges = edge_counts.values().filter(|&&count| count == 1).count();
+        assert_eq!(open_edges, 2 * 8);
+        assert!(edge_counts.values().all(|&count| count <= 2));
+
+        // Some of each ring is above the center of its section
+        for (section, ring) in path.sections().iter().zip(positions(&mesh).chunks_exact(8)) {
+            assert!(ring
+                .iter()
+                .any(|&p| (Vec3::from(p) - section.position).dot(section.up) > 0.9 * path.radius));
+        }
+    }
+
+    #[test]
+    fn triangles_face_the_way_of_their_normals() {
+        for (normals_inward, closed_top) in [(true, false), (false, false), (true, true)] {
+            let path = HalfCylinderPath {
+                n_segments: 4,
+                closed_top,
+                normals_inward,
+                ..Default::default()
+            };
+            let sections = path.sections();
+            let mesh = Mesh::from(path);
+            let positions = positions(&mesh)
+                .iter()
+                .map(|&p| Vec3::from(p))
+                .collect::<Vec<_>>();
+            let normals = normals(&mesh);
+            let ring_vertex_count = positions.len() / sections.len();
+            for triangle in index_list(mesh.indices().unwrap()).chunks_exact(3) {
+                let [a, b, c] = [triangle[0], triangle[1], triangle[2]];
+                // Counter-clockwise winding faces the way of the cross product
+                let face_normal = (positions[b] - positions[a]).cross(positions[c] - positions[a]);
+                let vertex_normal = normals[a] + normals[b] + normals[c];
+                assert!(face_normal.dot(vertex_normal) > 0.0);
+                let center = sections[a / ring_vertex_count].position;
+                let inward = face_normal.dot(center - positions[a]) > 0.0;
+                assert_eq!(inward, normals_inward);
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_paths_give_matching_errors() {
+        let path = HalfCylinderPath::default;
+        assert_eq!(path().validate(), Ok(()));
+        let invalid = [
+            (
+                HalfCylinderPath {
+                    radius: 0.0,
+                    ..path()
+                },
+                PathError::NonPositiveRadius(0.0),
+            ),
+            (
+                HalfCylinderPath {
+                    radius: -1.0,
+                    ..path()
+                },
+                PathError::NonPositiveRadius(-1.0),
+            ),
+            (
+                HalfCylinderPath {
+                    n_segments: 3,
+                    radii: vec![2.0, 1.0, -0.5, 1.0],
+                    ..path()
+                },
+                PathError::NonPositiveRingRadius(2, -0.5),
+            ),
+            (
+                HalfCylinderPath {
+                    segment_length: 0.0,
+                    ..path()
+                },
+                PathError::NonPositiveSegmentLength(0.0),
+            ),
+            (
+                HalfCylinderPath {
+                    subdivisions: 1,
+                    ..path()
+                },
+                PathError::TooFewSubdivisions(1),
+            ),
+            (
+                HalfCylinderPath {
+                    n_segments: 0,
+                    ..path()
+                },
+                PathError::NoSegments,
+            ),
+            (
+                HalfCylinderPath {
+                    yaw_range: 0.5..0.5,
+                    ..path()
+                },
+                PathError::EmptyYawRange(0.5..0.5),
+            ),
+            (
+                HalfCylinderPath {
+                    pitch_range: 0.1..-0.1,
+                    ..path()
+                },
+                PathError::EmptyPitchRange(0.1..-0.1),
+            ),
+            (
+                HalfCylinderPath {
+                    forward: Vec3::ZERO,
+                    ..path()
+                },
+                PathError::InvalidForward(Vec3::ZERO),
+            ),
+            (
+                HalfCylinderPath {
+                    forward: Vec3::new(0.0, 0.0, f32::NEG_INFINITY),
+                    ..path()
+                },
+                PathError::InvalidForward(Vec3::new(0.0, 0.0, f32::NEG_INFINITY)),
+            ),
+            (
+                HalfCylinderPath {
+                    yaw_range: -0.1..f32::INFINITY,
+                    ..path()
+                },
+                PathError::NonFiniteYawRange(-0.1..f32::INFINITY),
+            ),
+        ];
+        for (path, error) in invalid {
+            assert_eq!(path.validate(), Err(error));
+        }
+        // NaN is no more valid than zero
+        let nan_radius = HalfCylinderPath {
+            radius: f32::NAN,
+            ..path()
+        };
+        assert!(matches!(
+            nan_radius.validate(),
+            Err(PathError::NonPositiveRadius(radius)) if radius.is_nan()
+        ));
+        let nan_forward = HalfCylinderPath {
+            forward: Vec3::new(f32::NAN, 0.0, -1.0),
+            ..path()
+        };
+        assert!(matches!(
+            nan_forward.validate(),
+            Err(PathError::InvalidForward(forward)) if forward.x.is_nan()
+        ));
+        let nan_pitch_range = HalfCylinderPath {
+            pitch_range: f32::NAN..0.1,
+            ..path()
+        };
+        assert!(matches!(
+            nan_pitch_range.validate(),
+            Err(PathError::NonFinitePitchRange(range)) if range.start.is_nan()
+        ));
+        // Ring radii past the end of the path are never used
+        let extra_radii = HalfCylinderPath {
+            n_segments: 1,
+            radii: vec![1.0, 1.0, 0.0],
+            ..path()
+        };
+        assert_eq!(extra_radii.validate(), Ok(()));
+    }
+
+    #[test]
+    fn colliders_can_be_coarser_than_the_rendered_tube() {
+        let path = HalfCylinderPath {
+            n_segments: 4,
+            subdivisions: 32,
+            collider_subdivisions: 8,
+            ..Default::default()
+        };
+        let collider_path = path.collider_path().unwrap();
+        assert_eq!(collider_path.subdivisions, 8);
+        let vertex_count = |mesh: &Mesh| mesh.attribute(Mesh::ATTRIBUTE_POSITION).unwrap().len();
+        let rings = path.n_segments + 1;
+        let mesh = Mesh::from(path.clone());
+        let collider_mesh = Mesh::from(collider_path);
+        assert_eq!(
+            vertex_count(&mesh),
+            rings * ring_vertex_count(32, 0.0, false)
+        );
+        assert_eq!(
+            vertex_count(&collider_mesh),
+            rings * ring_vertex_count(8, 0.0, false)
+        );
+        let collider = mesh_to_collider_shape(&collider_mesh).unwrap();
+        assert_eq!(
+            collider.as_trimesh().unwrap().vertices().len(),
+            vertex_count(&collider_mesh)
+        );
+
+        // Unset, or the same as the rendered tube, colliders are built from the rendered tube
+        for collider_subdivisions in [0, 32] {
+            let path = HalfCylinderPath {
+                collider_subdivisions,
+                ..path.clone()
+            };
+            assert!(path.collider_path().is_none());
+            assert_eq!(path.validate(), Ok(()));
+        }
+        let path = HalfCylinderPath {
+            collider_subdivisions: 1,
+            ..path
+        };
+        assert_eq!(
+            path.validate(),
+            Err(PathError::TooFewColliderSubdivisions(1))
+        );
+    }
 }
@@ -0,0 +1,977 @@
+use bevy::{prelude::*, transform::TransformPlugin};
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    config::GameConfig,
+    race::{
+        apply_boosts, apply_catch_up, apply_gravity, apply_slow_zones, apply_wind_zones,
+        despawn_balls, detect_finish, log_race_events, prevent_tunneling, record_splits,
+        spawn_balls, spawn_track, spin_hazards, start_round, stream_track, tick_race_clock,
+        track_path, BallCollision, BallPhysics, CountdownTimer, DeterministicMode, GameState,
+        RaceClock, RaceConfig, RaceEvent, RaceSeed, RaceSystem, RoundState, StartSeed,
+    },
+    results::RaceResult,
+    shapes::ColliderKind,
+};
+
+/// Races that have not ended after this many frames are cut short, and unfinished players are
+/// reported as not having finished.
+const MAX_FRAMES: usize = 10 * 60 * 60;
+
+fn setup_track(
+    mut commands: Commands,
+    collider_kind: Res<ColliderKind>,
+    race_config: Res<RaceConfig>,
+    seed: Res<RaceSeed>,
+    game_config: Res<GameConfig>,
+) {
+    let closed = race_config.target_laps.is_some();
+    let path = track_path(seed.0, closed, &game_config);
+    if let Err(e) = spawn_track(&mut commands, path, &collider_kind, &race_config) {
+        warn!(
+            "Failed to spawn the track, using the default instead: {}",
+            e
+        );
+        let path = track_path(seed.0, closed, &GameConfig::default());
+        spawn_track(&mut commands, path, &collider_kind, &race_config)
+            .expect("Failed to spawn the default track");
+    }
+}
+
+/// Runs a race on the track generated from `seed` without a window, rendering, or UI, until every
+/// player has finished or dropped out.
+///
+/// Physics runs in [`DeterministicMode`], and the race clock advances by one physics step per
+/// frame, so the simulation runs as fast as the machine allows.
+pub fn run_headless(config: RaceConfig, seed: u64) -> RaceResult {
+    RaceResult::from(&simulate_round(config, seed, None))
+}
+
+/// Runs a race as [`run_headless`] does, returning the final state of the round. If `start_seed`
+/// is given, the players start from the spawn positions and with the start delays generated from
+/// it, rather than from `seed`.
+pub fn simulate_round(config: RaceConfig, seed: u64, start_seed: Option<u64>) -> RoundState {
+    let mut app = race_app(config, seed, start_seed);
+    run_race(&mut app);
+    app.world
+        .remove_resource::<RoundState>()
+        .expect("Missing RoundState")
+}
+
+/// An app that runs a race headlessly, as [`simulate_round`] does, for resources to be changed
+/// before it is run with [`run_race`].
+fn race_app(config: RaceConfig, seed: u64, start_seed: Option<u64>) -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins)
+        .add_plugin(TransformPlugin)
+        .add_plugin(RapierPhysicsPlugin::<NoUserData>::default());
+    DeterministicMode::default().configure(&mut app);
+    app.insert_resource(config)
+        .insert_resource(RaceSeed(seed))
+        .init_resource::<RoundState>()
+        .init_resource::<CountdownTimer>()
+        .init_resource::<RaceClock>()
+        .init_resource::<ColliderKind>()
+        .init_resource::<BallPhysics>()
+        .init_resource::<BallCollision>()
+        .init_resource::<GameConfig>()
+        .add_event::<RaceEvent>()
+        .add_state(GameState::Playing)
+        .add_system(log_race_events);
+    if let Some(start_seed) = start_seed {
+        app.insert_resource(StartSeed(start_seed));
+    }
+    app.add_system_set(
+        SystemSet::on_enter(GameState::Playing)
+            .with_system(setup_track)
+            .with_system(apply_gravity)
+            .with_system(start_round),
+    )
+    .add_system_set(
+        SystemSet::on_update(GameState::Playing)
+            .label(RaceSystem::Clock)
+            .with_system(tick_race_clock),
+    )
+    .add_system_set(
+        SystemSet::on_update(GameState::Playing)
+            .with_system(stream_track)
+            .with_system(spin_hazards),
+    )
+    .add_system_set(
+        SystemSet::on_update(GameState::Playing)
+            .label(RaceSystem::Spawn)
+            .after(RaceSystem::Clock)
+            .with_system(spawn_balls),
+    )
+    .add_system_set(
+        SystemSet::on_update(GameState::Playing)
+            .label(RaceSystem::Physics)
+            .after(RaceSystem::Spawn)
+            .with_system(apply_boosts)
+            .with_system(apply_slow_zones)
+            .with_system(apply_wind_zones)
+            .with_system(apply_catch_up)
+            .with_system(prevent_tunneling),
+    )
+    .add_system_set(
+        SystemSet::on_update(GameState::Playing)
+            .label(RaceSystem::Finish)
+            .after(RaceSystem::Physics)
+            .with_system(detect_finish)
+            .with_system(record_splits),
+    )
+    .add_system_set(
+        SystemSet::on_update(GameState::Playing)
+            .label(RaceSystem::Despawn)
+            .after(RaceSystem::Finish)
+            .with_system(despawn_balls),
+    );
+    app
+}
+
+/// Runs `app` until its race ends, or for [`MAX_FRAMES`] if it does not.
+fn run_race(app: &mut App) {
+    for _ in 0..MAX_FRAMES {
+        app.update();
+        if !race_running(app) {
+            break;
+        }
+    }
+}
+
+fn race_running(app: &App) -> bool {
+    let state = app
+        .world
+        .get_resource::<State<GameState>>()
+        .expect("Missing GameState");
+    *state.current() == GameState::Playing
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{
+        ecs::{
+            event::{Events, ManualEventReader},
+            system::CommandQueue,
+        },
+        utils::{HashMap, HashSet},
+    };
+    use bevy_rapier3d::{na::Vector3, rapier::parry::transformation::vhacd::VHACDParameters};
+
+    use super::*;
+    use crate::config::{EARTH_GRAVITY, MOON_GRAVITY};
+    use crate::race::{
+        apply_time_scale, nudge_balls, rank_players, spawn_ball, Ball, FinishLine, PlayerState,
+        RotatingHazard, SegmentKind, TimeScale, TrackInfo, TrackSections, TrackSegment, COUNTDOWN,
+        NUDGE_KEYS, N_CHECKPOINTS, N_PLAYERS,
+    };
+    use crate::replay::{record_replay, rewind_race, GhostRun, ReplayRecorder};
+
+    /// Milliseconds spent in the broad phase over the first `frames` frames of a race on tracks
+    /// with `collider_kind` colliders.
+    fn broad_phase_ms(collider_kind: ColliderKind, frames: usize) -> f64 {
+        let mut app = race_app(RaceConfig::default(), 1, None);
+        app.insert_resource(collider_kind);
+        app.world
+            .get_resource_mut::<PhysicsPipeline>()
+            .expect("Missing PhysicsPipeline")
+            .counters
+            .enable();
+        let mut total = 0.0;
+        for _ in 0..frames {
+            app.update();
+            let pipeline = app
+                .world
+                .get_resource::<PhysicsPipeline>()
+                .expect("Missing PhysicsPipeline");
+            total += pipeline.counters.cd.broad_phase_time.time();
+        }
+        total
+    }
+
+    /// Speeds of the balls in play, by entity.
+    fn ball_speeds(app: &mut App) -> HashMap<Entity, f32> {
+        app.world
+            .query_filtered::<(Entity, &RigidBodyVelocityComponent), With<Ball>>()
+            .iter(&app.world)
+            .map(|(entity, velocity)| (entity, velocity.linvel.norm()))
+            .collect()
+    }
+
+    /// The balls that started touching track segments of `kind` in contact events not yet read by
+    /// `reader`.
+    fn balls_touching(
+        app: &mut App,
+        reader: &mut ManualEventReader<ContactEvent>,
+        kind: SegmentKind,
+    ) -> Vec<Entity> {
+        let events = app
+            .world
+            .get_resource::<Events<ContactEvent>>()
+            .expect("Missing ContactEvent events");
+        let mut balls = Vec::new();
+        for event in reader.iter(events) {
+            if let ContactEvent::Started(collider1, collider2) = event {
+                for (segment, ball) in [(collider1, collider2), (collider2, collider1)] {
+                    let is_kind = app
+                        .world
+                        .get::<TrackSegment>(segment.entity())
+                        .map_or(false, |segment| segment.kind == kind);
+                    if let (true, Some(parent)) = (is_kind, app.world.get::<Parent>(ball.entity()))
+                    {
+                        balls.push(parent.0);
+                    }
+                }
+            }
+        }
+        balls
+    }
+
+    /// An app that runs deterministic physics in empty space, with no gravity.
+    fn physics_app() -> App {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .add_plugin(TransformPlugin)
+            .add_plugin(RapierPhysicsPlugin::<NoUserData>::default());
+        DeterministicMode::default().configure(&mut app);
+        app.world
+            .get_resource_mut::<RapierConfiguration>()
+            .expect("Missing RapierConfiguration")
+            .gravity = Vector3::zeros();
+        app
+    }
+
+    /// Contacts started between two overlapping balls over the first frames after they spawn.
+    fn contacts_between_overlapping_balls(ball_collision: BallCollision) -> usize {
+        let mut app = physics_app();
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &app.world);
+        let balls = [0.0, 0.5].map(|x| {
+            spawn_ball(
+                &mut commands,
+                0,
+                Vec3::new(x, 0.0, 0.0),
+                &BallPhysics::default(),
+                &ball_collision,
+            )
+        });
+        queue.apply(&mut app.world);
+        // In races only the track reports contacts, so the balls are made to report their own
+        for ball in balls {
+            let collider = app.world.get::<Children>(ball).expect("Missing collider")[0];
+            app.world
+                .get_mut::<ColliderFlagsComponent>(collider)
+                .expect("Missing ColliderFlags")
+                .active_events = ActiveEvents::CONTACT_EVENTS;
+        }
+        let mut reader = ManualEventReader::<ContactEvent>::default();
+        let mut contacts = 0;
+        for _ in 0..10 {
+            app.update();
+            let events = app
+                .world
+                .get_resource::<Events<ContactEvent>>()
+                .expect("Missing ContactEvent events");
+            contacts += reader
+                .iter(events)
+                .filter(|event| matches!(event, ContactEvent::Started(..)))
+                .count();
+        }
+        contacts
+    }
+
+    #[test]
+    fn ghost_balls_pass_through_each_other() {
+        assert!(
+            contacts_between_overlapping_balls(BallCollision {
+                players_collide: true
+            }) > 0
+        );
+        assert_eq!(
+            contacts_between_overlapping_balls(BallCollision {
+                players_collide: false
+            }),
+            0
+        );
+    }
+
+    #[test]
+    fn headless_race_ends_with_a_result_per_player() {
+        let round = simulate_round(RaceConfig::default(), 1, None);
+        assert!(round.players.iter().all(|player| player.end_secs.is_some()));
+        assert_eq!(RaceResult::from(&round).players.len(), N_PLAYERS);
+    }
+
+    #[test]
+    fn no_balls_spawn_during_countdown() {
+        let mut app = race_app(RaceConfig::new(3), 1, None);
+        let mut frames = 0;
+        loop {
+            app.update();
+            let countdown = app
+                .world
+                .get_resource::<CountdownTimer>()
+                .expect("Missing CountdownTimer");
+            if countdown.0.finished() {
+                break;
+            }
+            let mut balls = app.world.query_filtered::<Entity, With<Ball>>();
+            assert_eq!(balls.iter(&app.world).count(), 0);
+            frames += 1;
+        }
+        let dt = DeterministicMode::default().dt;
+        assert!(frames as f32 * dt >= COUNTDOWN.as_secs_f32() - dt);
+    }
+
+    #[test]
+    fn crossing_the_finish_sensor_finishes() {
+        let mut app = race_app(RaceConfig::new(3), 1, None);
+        let mut balls = app.world.query::<(&Ball, &GlobalTransform)>();
+        let mut finished = HashSet::default();
+        for _ in 0..MAX_FRAMES {
+            let positions = balls
+                .iter(&app.world)
+                .map(|(ball, transform)| (ball.player, transform.translation))
+                .collect::<Vec<_>>();
+            app.update();
+            let (finish_line, finish_transform) = app
+                .world
+                .query::<(&FinishLine, &GlobalTransform)>()
+                .iter(&app.world)
+                .next()
+                .expect("Missing FinishLine");
+            let round = app
+                .world
+                .get_resource::<RoundState>()
+                .expect("Missing RoundState");
+            for (player, position) in positions {
+                if round.players[player].finished && finished.insert(player) {
+                    // Balls finish as they reach the sensor, wherever the end of the track is
+                    let past_line =
+                        (position - finish_transform.translation).dot(finish_line.normal);
+                    assert!(
+                        past_line.abs() < 5.0,
+                        "Finished {}m past the line",
+                        past_line
+                    );
+                }
+            }
+            if !race_running(&app) {
+                break;
+            }
+        }
+        assert!(!finished.is_empty());
+    }
+
+    #[test]
+    fn checkpoints_are_passed_in_order() {
+        let round = simulate_round(RaceConfig::new(3), 1, None);
+        assert!(round
+            .players
+            .iter()
+            .any(|player| player.splits.iter().all(Option::is_some)));
+        for player in &round.players {
+            assert_eq!(player.splits.len(), N_CHECKPOINTS);
+            // Skipped checkpoints are left out, but the rest are passed one after another
+            let splits = player.splits.iter().flatten().collect::<Vec<_>>();
+            assert!(
+                splits.windows(2).all(|pair| pair[0] < pair[1]),
+                "{:?}",
+                splits
+            );
+            if let (Some(&&last), Some(end)) = (splits.last(), player.end_secs) {
+                assert!(last <= end);
+            }
+        }
+    }
+
+    #[test]
+    fn boost_pads_speed_up_balls() {
+        let config = RaceConfig {
+            boost_segments: vec![3],
+            ..RaceConfig::new(3)
+        };
+        let boost_strength = config.boost_strength;
+        let mut app = race_app(config, 1, None);
+        let mut reader = ManualEventReader::default();
+        let mut boosted = HashSet::default();
+        let mut touching = Vec::new();
+        let mut speeds = HashMap::default();
+        for _ in 0..MAX_FRAMES {
+            app.update();
+            let new_speeds = ball_speeds(&mut app);
+            // Balls are boosted in the frame after the physics step in which they touch the pad
+            for ball in touching.drain(..) {
+                if let (Some(before), Some(after)) = (speeds.get(&ball), new_speeds.get(&ball)) {
+                    if boosted.insert(ball) {
+                        assert!(
+                            after - before > 0.5 * boost_strength,
+                            "Speed went from {} to {}",
+                            before,
+                            after
+                        );
+                    }
+                }
+            }
+            touching = balls_touching(&mut app, &mut reader, SegmentKind::Boost);
+            speeds = new_speeds;
+            if !race_running(&app) {
+                break;
+            }
+        }
+        assert!(!boosted.is_empty());
+    }
+
+    #[test]
+    fn slow_zones_slow_balls_within_them() {
+        let config = RaceConfig {
+            slow_segments: vec![3],
+            slow_linear_damping: 2.0,
+            ..RaceConfig::new(3)
+        };
+        let slow_linear_damping = config.slow_linear_damping;
+        let mut app = race_app(config, 1, None);
+        // The speed of each ball as it entered the slow zone, its latest speed within it, and for
+        // how many frames it has been within it
+        let mut slowed = HashMap::<Entity, (f32, f32, usize)>::default();
+        for _ in 0..MAX_FRAMES {
+            app.update();
+            let mut balls = app.world.query_filtered::<(
+                Entity,
+                &RigidBodyVelocityComponent,
+                &RigidBodyDampingComponent,
+            ), With<Ball>>();
+            for (ball, velocity, damping) in balls.iter(&app.world) {
+                if damping.linear_damping == slow_linear_damping {
+                    let speed = velocity.linvel.norm();
+                    let (_, latest, frames) = slowed.entry(ball).or_insert((speed, speed, 0));
+                    *latest = speed;
+                    *frames += 1;
+                }
+            }
+            if !race_running(&app) {
+                break;
+            }
+        }
+        // Balls that only clip the zone on their way past do not stay long enough to slow down
+        let slowed = slowed
+            .values()
+            .filter(|(_, _, frames)| *frames >= 30)
+            .map(|&(entry, latest, _)| (entry, latest))
+            .collect::<Vec<_>>();
+        assert!(!slowed.is_empty());
+        for (entry, latest) in slowed {
+            assert!(
+                latest < 0.5 * entry,
+                "Speed went from {} to {}",
+                entry,
+                latest
+            );
+        }
+    }
+
+    #[test]
+    fn fast_ball_stays_in_the_track() {
+        let mut app = race_app(RaceConfig::new(1), 1, None);
+        let mut balls = app
+            .world
+            .query_filtered::<(&mut RigidBodyVelocityComponent, &GlobalTransform), With<Ball>>();
+        while balls.iter(&app.world).next().is_none() {
+            app.update();
+        }
+        // Fast enough to pass through the floor in a single step
+        for (mut velocity, _) in balls.iter_mut(&mut app.world) {
+            velocity.linvel = Vector3::new(0.0, -400.0, -50.0);
+        }
+        let sections = app
+            .world
+            .get_resource::<TrackSections>()
+            .expect("Missing TrackSections")
+            .0
+            .clone();
+        for _ in 0..60 {
+            app.update();
+            let (_, transform) = balls.iter(&app.world).next().expect("Ball left the race");
+            let ball = transform.translation;
+            // How far the ball is outside the tube, or inside it if negative, by its nearest segment
+            let distance = sections
+                .windows(2)
+                .map(|section| {
+                    let along = section[1].position - section[0].position;
+                    let t = ((ball - section[0].position).dot(along) / along.length_squared())
+                        .clamp(0.0, 1.0);
+                    ball.distance(section[0].position + along * t) - section[0].radius
+                })
+                .reduce(f32::min)
+                .unwrap();
+            assert!(distance < 0.0, "Ball is {} outside the tube", distance);
+        }
+    }
+
+    #[test]
+    fn same_seed_gives_same_race() {
+        let a = simulate_round(RaceConfig::new(3), 5, None);
+        let b = simulate_round(RaceConfig::new(3), 5, None);
+        for (a, b) in a.players.iter().zip(&b.players) {
+            assert_eq!(a.spawn_point, b.spawn_point);
+            assert_eq!(a.start_secs, b.start_secs);
+            assert_eq!(a.end_secs, b.end_secs);
+            assert_eq!(a.finished, b.finished);
+            assert_eq!(a.splits, b.splits);
+            assert_eq!(a.distance, b.distance);
+        }
+    }
+
+    #[test]
+    fn three_player_round_spawns_three_balls() {
+        let mut app = race_app(RaceConfig::new(3), 1, None);
+        let mut balls = HashSet::default();
+        for _ in 0..MAX_FRAMES {
+            app.update();
+            balls.extend(
+                app.world
+                    .query_filtered::<Entity, With<Ball>>()
+                    .iter(&app.world),
+            );
+            if !race_running(&app) {
+                break;
+            }
+        }
+        assert_eq!(balls.len(), 3);
+    }
+
+    #[test]
+    fn two_lap_race_finishes_after_two_laps() {
+        // Balls cannot roll uphill back to the start of a loop, so it is kept nearly level, with
+        // boost pads to keep them going round
+        let config = RaceConfig {
+            target_laps: Some(2),
+            boost_segments: vec![0, 5],
+            ..RaceConfig::new(3)
+        };
+        let mut app = race_app(config, 1, None);
+        app.insert_resource(GameConfig {
+            pitch_range: -0.02..-0.01,
+            ..Default::default()
+        });
+        let live = |player: &&PlayerState| player.entity.is_some() && player.end_secs.is_none();
+        let mut checked_mid_lap = false;
+        for _ in 0..MAX_FRAMES {
+            app.update();
+            if !race_running(&app) {
+                break;
+            }
+            let round = app
+                .world
+                .get_resource::<RoundState>()
+                .expect("Missing RoundState");
+            let mid_lap = round
+                .players
+                .iter()
+                .position(|player| live(&player) && player.laps == 1 && player.progress > 0.5);
+            if let (Some(player), false) = (mid_lap, checked_mid_lap) {
+                // Live balls are ranked by lap, and then by how far round the loop they are
+                let order = rank_players(round);
+                for pair in order.windows(2) {
+                    let (a, b) = (&round.players[pair[0]], &round.players[pair[1]]);
+                    if live(&a) && live(&b) {
+                        assert!((a.laps, a.progress) >= (b.laps, b.progress));
+                    }
+                }
+                let text =
+                    round.standing_text(player, Some(2), app.world.get_resource::<TrackInfo>());
+                let progress = format!("L2/2 {:5.1}%", 100.0 * round.players[player].progress);
+                assert!(text.starts_with(&progress), "{}", text);
+                checked_mid_lap = true;
+            }
+        }
+        assert!(checked_mid_lap);
+        let round = app
+            .world
+            .get_resource::<RoundState>()
+            .expect("Missing RoundState");
+        assert!(round.players.iter().any(|player| player.finished));
+        for player in &round.players {
+            if player.finished {
+                assert_eq!(player.laps, 2);
+            } else {
+                assert!(player.laps < 2);
+            }
+        }
+    }
+
+    /// Building the decomposition takes a while, so this benchmark is only run on request, with
+    /// `cargo test -- --ignored`.
+    #[test]
+    #[ignore]
+    fn convex_decomposition_speeds_up_broad_phase() {
+        let trimesh = broad_phase_ms(ColliderKind::TriMesh, 600);
+        let decomposition = broad_phase_ms(
+            ColliderKind::ConvexDecomposition(VHACDParameters::default()),
+            600,
+        );
+        println!(
+            "Broad phase: {:.2}ms with a trimesh, {:.2}ms with a convex decomposition",
+            trimesh, decomposition
+        );
+        assert!(decomposition < trimesh);
+    }
+
+    #[test]
+    fn finish_times_do_not_depend_on_time_scale() {
+        let finish_secs = |scale| {
+            let mut app = race_app(RaceConfig::new(1), 3, None);
+            app.insert_resource(TimeScale(scale))
+                .add_system(apply_time_scale.before(RaceSystem::Clock));
+            run_race(&mut app);
+            let round = app.world.get_resource::<RoundState>().unwrap();
+            let player = &round.players[0];
+            assert!(player.finished);
+            player.end_secs.unwrap() - round.start_secs
+        };
+        let (normal, slow) = (finish_secs(1.0), finish_secs(0.5));
+        // Smaller physics steps are integrated a little differently
+        assert!((slow - normal).abs() < 0.02 * normal, "{} {}", normal, slow);
+    }
+
+    /// A race app that records a replay of the race, as the game does.
+    fn recorded_race_app(config: RaceConfig, seed: u64) -> App {
+        let mut app = race_app(config, seed, None);
+        app.init_resource::<ReplayRecorder>().add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(record_replay.after(RaceSystem::Despawn)),
+        );
+        app
+    }
+
+    #[test]
+    fn ghost_retraces_the_recorded_run() {
+        let mut app = recorded_race_app(RaceConfig::new(3), 2);
+        run_race(&mut app);
+        let recorder = app
+            .world
+            .get_resource::<ReplayRecorder>()
+            .expect("Missing ReplayRecorder");
+        let ghost = GhostRun::from_replay(&recorder.replay(), 0);
+        assert!(!ghost.frames.is_empty());
+
+        // The same race again, with the ghost sampled at the times the ball was recorded
+        let mut app = recorded_race_app(RaceConfig::new(3), 2);
+        run_race(&mut app);
+        let recorder = app
+            .world
+            .get_resource::<ReplayRecorder>()
+            .expect("Missing ReplayRecorder");
+        let mut compared = 0;
+        for frame in &recorder.frames {
+            if let Some(&(_, translation, _)) =
+                frame.balls.iter().find(|(player, _, _)| *player == 0)
+            {
+                let (ghost_translation, _) = ghost
+                    .transform_at(frame.time)
+                    .expect("Ghost missing while the ball was racing");
+                assert!(
+                    ghost_translation.abs_diff_eq(translation, 1e-3),
+                    "Ghost at {} but ball at {}, {}s in",
+                    ghost_translation,
+                    translation,
+                    frame.time
+                );
+                compared += 1;
+            }
+        }
+        assert_eq!(compared, ghost.frames.len());
+    }
+
+    #[test]
+    fn nudges_steer_balls_gently() {
+        let mut app = physics_app();
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &app.world);
+        let mut round = RoundState::default();
+        for player in 0..3 {
+            let spawn_point = Vec3::new(10.0 * player as f32, 0.0, 0.0);
+            let mut state = PlayerState::new(String::new(), Color::WHITE, spawn_point, 0.0);
+            state.entity = Some(spawn_ball(
+                &mut commands,
+                player,
+                spawn_point,
+                &BallPhysics::default(),
+                &BallCollision::default(),
+            ));
+            round.players.push(state);
+        }
+        queue.apply(&mut app.world);
+        // The first player nudges their ball, the second has nobody nudging theirs, and the third
+        // has already finished
+        round.players[2].end_secs = Some(0.0);
+        let mut race_config = RaceConfig::new(3);
+        race_config.nudge_keys.insert(0, NUDGE_KEYS[0]);
+        race_config.nudge_keys.insert(2, NUDGE_KEYS[1]);
+        let mut keyboard_input = Input::<KeyCode>::default();
+        keyboard_input.press(NUDGE_KEYS[0].right);
+        keyboard_input.press(NUDGE_KEYS[1].right);
+        let nudge_acceleration = race_config.nudge_acceleration;
+        let balls = round
+            .players
+            .iter()
+            .map(|player| player.entity.unwrap())
+            .collect::<Vec<_>>();
+        app.insert_resource(round)
+            .insert_resource(race_config)
+            .insert_resource(keyboard_input)
+            .add_system(nudge_balls);
+
+        let frames = 60;
+        for _ in 0..frames {
+            app.update();
+        }
+        let velocity = |ball| {
+            let velocity = app.world.get::<RigidBodyVelocityComponent>(ball).unwrap();
+            Vec3::from_slice(velocity.linvel.as_slice())
+        };
+        // The ball spawns moving along -z, and the nudge only adds to its speed to the right
+        let max_speed = nudge_acceleration * frames as f32 * DeterministicMode::default().dt;
+        let nudged = velocity(balls[0]);
+        assert!(nudged.x > 0.5 * max_speed, "{}", nudged);
+        assert!(nudged.x <= max_speed + 1e-3, "{}", nudged);
+        assert!((nudged.z - velocity(balls[1]).z).abs() < 1e-3);
+        assert_eq!(velocity(balls[1]).x, 0.0);
+        assert_eq!(velocity(balls[2]).x, 0.0);
+    }
+
+    #[test]
+    fn hazards_spin_about_the_track() {
+        let mut app = race_app(RaceConfig::new(1), 1, None);
+        app.insert_resource(GameConfig {
+            hazards: 2,
+            ..Default::default()
+        });
+        app.update();
+        let mut hazards = app
+            .world
+            .query::<(Entity, &RotatingHazard, &RigidBodyPositionComponent)>();
+        let start = hazards
+            .iter(&app.world)
+            .map(|(entity, hazard, position)| (entity, hazard.speed, position.position.rotation))
+            .collect::<Vec<_>>();
+        assert_eq!(start.len(), 2);
+
+        let frames = 30;
+        for _ in 0..frames {
+            app.update();
+        }
+        let secs = frames as f32 * DeterministicMode::default().dt;
+        for (entity, speed, start_rotation) in start {
+            let (_, _, position) = hazards.get(&app.world, entity).unwrap();
+            let rotation = position.position.rotation;
+            // Turned by the expected angle, about the axis of the track
+            let turn = start_rotation.inverse() * rotation;
+            let angle = turn.angle();
+            assert!((angle - speed.abs() * secs).abs() < 0.01, "{}", angle);
+            let axis = turn.axis().unwrap();
+            assert!(axis.z.abs() > 0.999, "{:?}", axis);
+            assert_eq!(axis.z.signum(), speed.signum());
+        }
+    }
+
+    /// The x coordinate of each player's ball as it first passes `z`, in a race with `config`.
+    fn x_passing(config: RaceConfig, z: f32) -> HashMap<usize, f32> {
+        let mut app = race_app(config, 1, None);
+        let mut balls = app.world.query::<(&Ball, &GlobalTransform)>();
+        let mut passing = HashMap::default();
+        for _ in 0..MAX_FRAMES {
+            app.update();
+            for (ball, transform) in balls.iter(&app.world) {
+                if transform.translation.z < z {
+                    passing
+                        .entry(ball.player)
+                        .or_insert(transform.translation.x);
+                }
+            }
+            if !race_running(&app) {
+                break;
+            }
+        }
+        passing
+    }
+
+    #[test]
+    fn wind_zones_blow_balls_sideways() {
+        let sections = track_path(1, false, &GameConfig::default()).sections();
+        let zone_end = sections[8].position.z;
+        let calm = x_passing(RaceConfig::new(3), zone_end);
+        let windy = x_passing(
+            RaceConfig {
+                wind_zones: (3..8).map(|index| (index, 20.0 * Vec3::X)).collect(),
+                ..RaceConfig::new(3)
+            },
+            zone_end,
+        );
+        assert_eq!(calm.len(), 3);
+        assert_eq!(windy.len(), 3);
+        for (player, x) in windy {
+            assert!(
+                x > calm[&player] + 1.0,
+                "{} drifted from {} to {}",
+                player,
+                calm[&player],
+                x
+            );
+        }
+    }
+
+    #[test]
+    fn rewinding_puts_balls_back_and_the_clock_with_them() {
+        let mut app = recorded_race_app(RaceConfig::new(3), 4);
+        app.init_resource::<Input<KeyCode>>().add_system_set(
+            SystemSet::on_update(GameState::Playing).with_system(
+                rewind_race
+                    .after(RaceSystem::Clock)
+                    .before(RaceSystem::Spawn),
+            ),
+        );
+        for _ in 0..900 {
+            app.update();
+        }
+        assert!(race_running(&app));
+        let elapsed = app.world.get_resource::<RaceClock>().unwrap().elapsed;
+        let start_secs = app.world.get_resource::<RoundState>().unwrap().start_secs;
+        let recorder = app.world.get_resource::<ReplayRecorder>().unwrap();
+        let latest = recorder.frames.back().unwrap().time;
+        let frame = recorder
+            .frames
+            .iter()
+            .rev()
+            .find(|frame| frame.time <= latest - 3.0)
+            .unwrap()
+            .clone();
+
+        let mut keyboard_input = app.world.get_resource_mut::<Input<KeyCode>>().unwrap();
+        keyboard_input.press(KeyCode::R);
+        app.update();
+        let mut keyboard_input = app.world.get_resource_mut::<Input<KeyCode>>().unwrap();
+        keyboard_input.release(KeyCode::R);
+        keyboard_input.clear();
+
+        let clock = app.world.get_resource::<RaceClock>().unwrap();
+        assert!((clock.elapsed - (start_secs + frame.time)).abs() < 1e-4);
+        assert!(clock.elapsed < elapsed - 2.9);
+        let mut balls = app.world.query::<(&Ball, &GlobalTransform)>();
+        let mut moved_back = 0;
+        for (ball, transform) in balls.iter(&app.world) {
+            let &(_, translation, _) = frame
+                .balls
+                .iter()
+                .find(|(player, _, _)| *player == ball.player)
+                .expect("Ball missing from the frame rewound to");
+            // The balls have taken one step of physics since being put back
+            assert!(
+                transform.translation.distance(translation) < 1.0,
+                "Ball of player {} at {} rather than {}",
+                ball.player,
+                transform.translation,
+                translation
+            );
+            moved_back += 1;
+        }
+        assert_eq!(moved_back, frame.balls.len());
+
+        run_race(&mut app);
+        let round = app.world.get_resource::<RoundState>().unwrap();
+        for player in &round.players {
+            let end_secs = player.end_secs.expect("Player still racing");
+            assert!(end_secs > player.start_secs);
+        }
+    }
+
+    #[test]
+    fn trailing_balls_are_pushed_hardest() {
+        let mut app = physics_app();
+        let mut queue = CommandQueue::default();
+        let mut commands = Commands::new(&mut queue, &app.world);
+        let mut round = RoundState::default();
+        // The leader, a ball 20m behind it, and one further behind than the largest deficit
+        for (player, distance) in [-100.0, -80.0, -10.0].into_iter().enumerate() {
+            let spawn_point = Vec3::new(10.0 * player as f32, 0.0, 0.0);
+            let mut state = PlayerState::new(String::new(), Color::WHITE, spawn_point, 0.0);
+            state.distance = distance;
+            state.entity = Some(spawn_ball(
+                &mut commands,
+                player,
+                spawn_point,
+                &BallPhysics::default(),
+                &BallCollision::default(),
+            ));
+            round.players.push(state);
+        }
+        queue.apply(&mut app.world);
+        let balls = round
+            .players
+            .iter()
+            .map(|player| player.entity.unwrap())
+            .collect::<Vec<_>>();
+        app.insert_resource(round)
+            .insert_resource(GameConfig {
+                catch_up_strength: 2.0,
+                catch_up_max_deficit: 50.0,
+                ..Default::default()
+            })
+            .add_system(apply_catch_up);
+
+        for _ in 0..30 {
+            app.update();
+        }
+        let speed = |ball| {
+            let velocity = app.world.get::<RigidBodyVelocityComponent>(ball).unwrap();
+            -velocity.linvel.z
+        };
+        // Balls spawn with the same velocity, so only the pushes tell them apart
+        let leader = speed(balls[0]);
+        let behind = speed(balls[1]) - leader;
+        let far_behind = speed(balls[2]) - leader;
+        assert!(behind > 0.0, "{}", behind);
+        assert!(
+            (far_behind / behind - 2.5).abs() < 1e-3,
+            "{} and {}",
+            behind,
+            far_behind
+        );
+        let mut forces = app.world.query::<&RigidBodyForcesComponent>();
+        let leader_forces = forces.get(&app.world, balls[0]).unwrap();
+        assert_eq!(leader_forces.force, Vector3::zeros());
+    }
+
+    #[test]
+    fn configured_gravity_reaches_the_physics() {
+        for gravity in [EARTH_GRAVITY, MOON_GRAVITY] {
+            let mut app = race_app(RaceConfig::new(3), 1, None);
+            app.insert_resource(GameConfig {
+                gravity,
+                ..Default::default()
+            });
+            app.update();
+            let rapier_config = app
+                .world
+                .get_resource::<RapierConfiguration>()
+                .expect("Missing RapierConfiguration");
+            assert_eq!(rapier_config.gravity, Vector3::new(0.0, -gravity, 0.0));
+        }
+    }
+
+    #[test]
+    fn invalid_tracks_fall_back_to_the_default() {
+        let mut app = race_app(RaceConfig::new(3), 1, None);
+        app.insert_resource(GameConfig {
+            segment_length: 0.0,
+            ..Default::default()
+        });
+        app.update();
+        let track_info = app
+            .world
+            .get_resource::<TrackInfo>()
+            .expect("Missing TrackInfo");
+        let default_path = track_path(1, false, &GameConfig::default());
+        assert_eq!(track_info.length, default_path.length());
+    }
+}
@@ -0,0 +1,115 @@
+use bevy::prelude::*;
+
+use crate::race::{rank_players, RoundState};
+
+/// Points awarded for each place in a round, from first. Places beyond these, and players who did
+/// not finish, score nothing.
+pub const POINTS: [u32; 10] = [25, 18, 15, 12, 10, 8, 6, 4, 2, 1];
+
+pub fn points_for_place(place: usize) -> u32 {
+    POINTS.get(place).copied().unwrap_or(0)
+}
+
+/// A series of rounds, over which players score points by where they place in each round.
+pub struct Tournament {
+    pub n_rounds: usize,
+    pub rounds_played: usize,
+    /// Points scored by each player so far in the series.
+    pub points: Vec<u32>,
+}
+
+impl Tournament {
+    pub fn new(n_rounds: usize) -> Self {
+        Self {
+            n_rounds,
+            rounds_played: 0,
+            points: Vec::new(),
+        }
+    }
+
+    /// Awards each player of `round` points for the place they finished in.
+    pub fn award(&mut self, round: &RoundState) {
+        if self.points.len() < round.players.len() {
+            self.points.resize(round.players.len(), 0);
+        }
+        for (place, player) in rank_players(round).into_iter().enumerate() {
+            if round.players[player].finished {
+                self.points[player] += points_for_place(place);
+            }
+        }
+        self.rounds_played += 1;
+    }
+
+    pub fn finished(&self) -> bool {
+        self.rounds_played >= self.n_rounds
+    }
+
+    /// Indices of the players in order of points scored, from most to fewest. Players on equal
+    /// points keep their order.
+    pub fn standings(&self) -> Vec<usize> {
+        let mut order = (0..self.points.len()).collect::<Vec<_>>();
+        order.sort_by(|&a, &b| self.points[b].cmp(&self.points[a]));
+        order
+    }
+
+    pub fn reset(&mut self) {
+        self.rounds_played = 0;
+        self.points.clear();
+    }
+}
+
+pub fn award_points(round: Res<RoundState>, mut tournament: ResMut<Tournament>) {
+    tournament.award(&round);
+}
+
+/// Starts a new series on leaving the standings of a finished one.
+pub fn reset_finished_tournament(mut tournament: ResMut<Tournament>) {
+    if tournament.finished() {
+        tournament.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::race::PlayerState;
+
+    /// A round in which the players in `finishers` finished, in that order, and nobody else did.
+    fn round(n_players: usize, finishers: &[usize]) -> RoundState {
+        let mut players = (0..n_players)
+            .map(|i| PlayerState::new(i.to_string(), Color::WHITE, Vec3::ZERO, 0.0))
+            .collect::<Vec<_>>();
+        for (place, &player) in finishers.iter().enumerate() {
+            players[player].finished = true;
+            players[player].end_secs = Some(10.0 + place as f32);
+        }
+        RoundState {
+            start_secs: 0.0,
+            players,
+        }
+    }
+
+    #[test]
+    fn points_are_awarded_by_place() {
+        let mut tournament = Tournament::new(3);
+        tournament.award(&round(4, &[2, 0, 3]));
+        assert_eq!(tournament.points, vec![18, 0, 25, 15]);
+        tournament.award(&round(4, &[0, 2]));
+        assert_eq!(tournament.points, vec![43, 0, 43, 15]);
+        // Players on equal points keep their order
+        assert_eq!(tournament.standings(), vec![0, 2, 3, 1]);
+        assert_eq!(points_for_place(POINTS.len()), 0);
+    }
+
+    #[test]
+    fn series_end_after_their_rounds_and_start_again() {
+        let mut tournament = Tournament::new(2);
+        tournament.award(&round(3, &[0]));
+        assert!(!tournament.finished());
+        tournament.award(&round(3, &[1]));
+        assert!(tournament.finished());
+        tournament.reset();
+        assert!(!tournament.finished());
+        assert!(tournament.points.is_empty());
+    }
+}
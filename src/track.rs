@@ -0,0 +1,132 @@
+use std::{fs::File, io, path::Path};
+
+use bevy::math::Vec3;
+use serde::{Deserialize, Serialize};
+
+use crate::{race::RaceConfig, shapes::HalfCylinderPath};
+
+/// Everything needed to regenerate a track exactly. The track mesh is fully determined by its path
+/// parameters, including the seed, so no geometry is stored.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TrackDescriptor {
+    pub path: HalfCylinderPath,
+    #[serde(default)]
+    pub race: RaceOverrides,
+}
+
+impl TrackDescriptor {
+    pub fn new(path: HalfCylinderPath) -> Self {
+        Self {
+            path,
+            race: RaceOverrides::default(),
+        }
+    }
+}
+
+/// Settings of a [`RaceConfig`] that a track replaces, where set.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RaceOverrides {
+    pub bouncy_segments: Option<Vec<usize>>,
+    pub boost_segments: Option<Vec<usize>>,
+    pub boost_strength: Option<f32>,
+    pub slow_segments: Option<Vec<usize>>,
+    pub slow_linear_damping: Option<f32>,
+    pub slow_angular_damping: Option<f32>,
+    pub target_laps: Option<u32>,
+    pub wind_zones: Option<Vec<(usize, Vec3)>>,
+}
+
+impl RaceOverrides {
+    pub fn apply(&self, race_config: &mut RaceConfig) {
+        if let Some(bouncy_segments) = &self.bouncy_segments {
+            race_config.bouncy_segments = bouncy_segments.clone();
+        }
+        if let Some(boost_segments) = &self.boost_segments {
+            race_config.boost_segments = boost_segments.clone();
+        }
+        if let Some(boost_strength) = self.boost_strength {
+            race_config.boost_strength = boost_strength;
+        }
+        if let Some(slow_segments) = &self.slow_segments {
+            race_config.slow_segments = slow_segments.clone();
+        }
+        if let Some(slow_linear_damping) = self.slow_linear_damping {
+            race_config.slow_linear_damping = slow_linear_damping;
+        }
+        if let Some(slow_angular_damping) = self.slow_angular_damping {
+            race_config.slow_angular_damping = slow_angular_damping;
+        }
+        if self.target_laps.is_some() {
+            race_config.target_laps = self.target_laps;
+        }
+        if let Some(wind_zones) = &self.wind_zones {
+            race_config.wind_zones = wind_zones.clone();
+        }
+    }
+}
+
+/// Writes `desc` to `path` in RON format.
+pub fn save_track(desc: &TrackDescriptor, path: &Path) -> io::Result<()> {
+    let ron = ron::ser::to_string_pretty(desc, ron::ser::PrettyConfig::default())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    std::fs::write(path, ron)
+}
+
+/// Reads a track previously written by [`save_track`] from `path`.
+pub fn load_track(path: &Path) -> io::Result<TrackDescriptor> {
+    let file = File::open(path)?;
+    let desc: TrackDescriptor =
+        ron::de::from_reader(file).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    desc.path
+        .validate()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok(desc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tracks_round_trip_through_ron() {
+        let desc = TrackDescriptor {
+            path: HalfCylinderPath {
+                seed: 42,
+                n_segments: 30,
+                closed: true,
+                rail_height: 0.5,
+                radii: vec![2.0, 1.5, 2.5],
+                gap_segments: [4, 9].into_iter().collect(),
+                ..Default::default()
+            },
+            race: RaceOverrides {
+                boost_segments: Some(vec![1, 2]),
+                boost_strength: Some(12.0),
+                target_laps: Some(3),
+                wind_zones: Some(vec![(5, Vec3::new(1.0, 0.0, -2.0))]),
+                ..Default::default()
+            },
+        };
+        let path = std::env::temp_dir().join("bavy-balls-track-round-trip.ron");
+        save_track(&desc, &path).unwrap();
+        let loaded = load_track(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.unwrap(), desc);
+    }
+
+    #[test]
+    fn invalid_tracks_fail_to_load() {
+        let desc = TrackDescriptor::new(HalfCylinderPath {
+            radius: 0.0,
+            ..Default::default()
+        });
+        let path = std::env::temp_dir().join("bavy-balls-invalid-track.ron");
+        save_track(&desc, &path).unwrap();
+        let error = load_track(&path).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
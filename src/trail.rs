@@ -0,0 +1,182 @@
+use std::collections::VecDeque;
+
+use bevy::{
+    prelude::*,
+    render::{mesh::Indices, render_resource::PrimitiveTopology},
+};
+
+use crate::race::Ball;
+
+/// Settings for the trails left by balls.
+#[derive(Clone, Debug)]
+pub struct TrailConfig {
+    /// Number of recent positions of a ball that its trail passes through, which bounds the number
+    /// of vertices in the trail to twice this.
+    pub length: usize,
+    /// Width of a trail where it meets its ball.
+    pub width: f32,
+}
+
+impl Default for TrailConfig {
+    fn default() -> Self {
+        Self {
+            length: 30,
+            width: 1.0,
+        }
+    }
+}
+
+/// A ribbon following the recent path of `ball`. Trails are separate from their balls so that
+/// their meshes can be in world space.
+#[derive(Component)]
+pub struct Trail {
+    pub ball: Entity,
+    /// Recent positions of the ball, oldest first.
+    pub positions: VecDeque<Vec3>,
+}
+
+impl Trail {
+    pub fn new(ball: Entity) -> Self {
+        Self {
+            ball,
+            positions: VecDeque::new(),
+        }
+    }
+
+    /// Adds the ball's latest position, forgetting the oldest once there are more than `length`.
+    pub fn push(&mut self, position: Vec3, length: usize) {
+        self.positions.push_back(position);
+        while self.positions.len() > length {
+            self.positions.pop_front();
+        }
+    }
+}
+
+/// Builds a flat ribbon through `positions`, oldest first, from a strip of quads between each pair
+/// of consecutive positions. The ribbon narrows from `width` at the newest position to nothing at
+/// the oldest, so that it fades out along its length. Both faces of the ribbon are included so
+/// that it can be seen from above or below.
+pub fn ribbon_mesh(positions: &[Vec3], width: f32) -> Mesh {
+    let n = positions.len();
+    let mut vertices = Vec::with_capacity(2 * n);
+    let mut normals = Vec::with_capacity(2 * n);
+    let mut uvs = Vec::with_capacity(2 * n);
+    for (i, &position) in positions.iter().enumerate() {
+        let forward = positions[(i + 1).min(n - 1)] - positions[i.saturating_sub(1)];
+        let side = forward.cross(Vec3::Y).try_normalize().unwrap_or(Vec3::X);
+        let along = if n > 1 {
+            i as f32 / (n - 1) as f32
+        } else {
+            1.0
+        };
+        let half_width = 0.5 * width * along;
+        for (sign, u) in [(-1.0, 0.0), (1.0, 1.0)] {
+            vertices.push((position + sign * half_width * side).to_array());
+            normals.push(
+                side.cross(forward)
+                    .try_normalize()
+                    .unwrap_or(Vec3::Y)
+                    .to_array(),
+            );
+            uvs.push([u, along]);
+        }
+    }
+
+    let mut indices = Vec::with_capacity(12 * n.saturating_sub(1));
+    for i in 0..n.saturating_sub(1) as u32 {
+        let offset = i * 2;
+        indices.extend_from_slice(&[
+            offset,
+            offset + 1,
+            offset + 2,
+            offset + 1,
+            offset + 3,
+            offset + 2,
+            // The back face
+            offset + 2,
+            offset + 1,
+            offset,
+            offset + 2,
+            offset + 3,
+            offset + 1,
+        ]);
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, vertices);
+    mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
+/// Records the latest position of each trail's ball and rebuilds the trail's mesh through them.
+pub fn update_trails(
+    trail_config: Res<TrailConfig>,
+    balls: Query<&GlobalTransform, With<Ball>>,
+    mut trails: Query<(&mut Trail, &Handle<Mesh>)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    for (mut trail, mesh) in trails.iter_mut() {
+        if let Ok(transform) = balls.get(trail.ball) {
+            trail.push(transform.translation, trail_config.length);
+        }
+        // A ribbon needs at least two positions to have any length
+        if trail.positions.len() < 2 {
+            continue;
+        }
+        if let Some(mesh) = meshes.get_mut(mesh) {
+            let positions = trail.positions.iter().copied().collect::<Vec<_>>();
+            *mesh = ribbon_mesh(&positions, trail_config.width);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::mesh::VertexAttributeValues;
+
+    use super::*;
+
+    #[test]
+    fn trails_keep_only_the_latest_positions() {
+        let mut trail = Trail::new(Entity::from_raw(0));
+        for z in 0..5 {
+            trail.push(Vec3::new(0.0, 0.0, -z as f32), 3);
+        }
+        assert_eq!(
+            trail.positions,
+            [-2.0, -3.0, -4.0]
+                .into_iter()
+                .map(|z| Vec3::new(0.0, 0.0, z))
+                .collect::<VecDeque<_>>()
+        );
+    }
+
+    #[test]
+    fn ribbon_widens_from_oldest_to_newest_position() {
+        // Along -z on level ground, so the ribbon is flat and spans x
+        let positions = [0.0, -1.0, -2.0].map(|z| Vec3::new(0.0, 0.0, z));
+        let mesh = ribbon_mesh(&positions, 2.0);
+        let vertices = match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+            Some(VertexAttributeValues::Float32x3(vertices)) => vertices.clone(),
+            _ => panic!("Mesh has no positions"),
+        };
+        assert_eq!(
+            vertices,
+            vec![
+                [0.0, 0.0, 0.0],
+                [0.0, 0.0, 0.0],
+                [-0.5, 0.0, -1.0],
+                [0.5, 0.0, -1.0],
+                [-1.0, 0.0, -2.0],
+                [1.0, 0.0, -2.0],
+            ]
+        );
+        // Two quads, each with a front and back face
+        match mesh.indices() {
+            Some(Indices::U32(indices)) => assert_eq!(indices.len(), 2 * 4 * 3),
+            _ => panic!("Mesh has no u32 indices"),
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+#[cfg(not(target_arch = "wasm32"))]
+use std::io::BufRead;
+
+use bevy::prelude::*;
+
+use crate::race::{BallInfo, RaceConfig};
+
+/// Where chat messages voting for balls come from, such as a relay from a stream's chat.
+pub trait VoteSource {
+    /// The messages received since the last poll.
+    fn poll(&mut self) -> Vec<String>;
+}
+
+/// The [`VoteSource`] from which votes are tallied.
+pub struct VoteFeed(pub Box<dyn VoteSource + Send + Sync>);
+
+/// Reads votes from standard input, one message per line, on a thread of its own so that waiting
+/// for input never holds up a frame.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct StdinVotes {
+    receiver: crossbeam_channel::Receiver<String>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl StdinVotes {
+    pub fn spawn() -> Self {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        std::thread::spawn(move || {
+            let stdin = std::io::stdin();
+            for line in stdin.lock().lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        Self { receiver }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl VoteSource for StdinVotes {
+    fn poll(&mut self) -> Vec<String> {
+        self.receiver.try_iter().collect()
+    }
+}
+
+/// Votes for which ball the camera follows, counted per player since the last count was taken.
+#[derive(Default)]
+pub struct ChatVotes {
+    pub counts: HashMap<usize, u32>,
+    /// Race clock time at which counting started.
+    pub since: f32,
+}
+
+impl ChatVotes {
+    /// The player with the most votes of those for whom `in_play` holds, if any of them has any.
+    /// Ties go to the first player.
+    pub fn most_voted(&self, in_play: impl Fn(usize) -> bool) -> Option<usize> {
+        self.counts
+            .iter()
+            .filter(|&(&player, &count)| count > 0 && in_play(player))
+            .max_by(|(a_player, a_count), (b_player, b_count)| {
+                a_count.cmp(b_count).then(b_player.cmp(a_player))
+            })
+            .map(|(&player, _)| player)
+    }
+
+    /// Starts counting afresh from `now`.
+    pub fn reset(&mut self, now: f32) {
+        self.counts.clear();
+        self.since = now;
+    }
+}
+
+/// The player whose ball `message` names, ignoring case and surrounding whitespace.
+pub fn voted_player(message: &str, balls: &[BallInfo]) -> Option<usize> {
+    let message = message.trim();
+    balls
+        .iter()
+        .position(|ball| ball.name.eq_ignore_ascii_case(message))
+}
+
+pub fn tally_chat_votes(
+    mut feed: ResMut<VoteFeed>,
+    race_config: Res<RaceConfig>,
+    mut votes: ResMut<ChatVotes>,
+) {
+    let balls = &race_config.balls[..race_config.n_players.min(race_config.balls.len())];
+    for message in feed.0.poll() {
+        if let Some(player) = voted_player(&message, balls) {
+            *votes.counts.entry(player).or_insert(0) += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::race::BALL_INFO;
+
+    /// Chat messages received all at once.
+    struct Chat(Vec<&'static str>);
+
+    impl VoteSource for Chat {
+        fn poll(&mut self) -> Vec<String> {
+            self.0.drain(..).map(str::to_string).collect()
+        }
+    }
+
+    #[test]
+    fn votes_name_balls_in_any_case() {
+        assert_eq!(voted_player("red", &BALL_INFO), Some(0));
+        assert_eq!(voted_player("  Dark_Gray \n", &BALL_INFO), Some(8));
+        assert_eq!(voted_player("go red!", &BALL_INFO), None);
+        assert_eq!(voted_player("BLACK", &BALL_INFO[..3]), None);
+    }
+
+    #[test]
+    fn most_votes_among_balls_in_play_wins() {
+        let mut votes = ChatVotes::default();
+        assert_eq!(votes.most_voted(|_| true), None);
+        votes
+            .counts
+            .extend([(0, 2), (1, 5), (2, 5), (3, 9), (4, 0)]);
+        assert_eq!(votes.most_voted(|_| true), Some(3));
+        // Ties go to the first player
+        assert_eq!(votes.most_voted(|player| player != 3), Some(1));
+        assert_eq!(votes.most_voted(|player| player == 4), None);
+        votes.reset(7.0);
+        assert_eq!(votes.most_voted(|_| true), None);
+        assert_eq!(votes.since, 7.0);
+    }
+
+    #[test]
+    fn chat_messages_are_tallied_per_player() {
+        let mut app = App::new();
+        app.insert_resource(VoteFeed(Box::new(Chat(vec![
+            "RED", "blue", "Red", "hello", "BLACK", "red",
+        ]))))
+        .insert_resource(RaceConfig::new(5))
+        .init_resource::<ChatVotes>()
+        .add_system(tally_chat_votes);
+        app.update();
+        // Black is not racing with only five players
+        let votes = app.world.get_resource::<ChatVotes>().unwrap();
+        assert_eq!(votes.counts, HashMap::from([(0, 3), (4, 1)]));
+    }
+}